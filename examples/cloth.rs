@@ -0,0 +1,66 @@
+//! A cloth hanging from two corners next to a soft cube, stepped at a fixed rate and streamed to the GPU.
+//! Clicking an object selects it, W, E and R switch its gizmo between moving, turning and scaling.
+
+use mirror::{App, Color, Cx, GizmoMode, InputEvent, Key, Mesh, MeshRef, MouseButton, Object, Time};
+use mirror::physics::{TetMesh, World};
+
+struct Demo {
+  world: World,
+  cloth: Mesh,
+  cube: Mesh,
+  first: [usize;2],
+  meshes: Vec<MeshRef>,
+}
+
+impl Demo {
+  fn new()->Demo {
+    let mut world = World{ground: Some(-0.5), ..World::new()};
+    let mut cloth = Mesh::grid([0.6, 0.6], [24, 24]);
+    for v in &mut cloth.vertices { *v = [v[0] - 0.35, 0.3 - v[2], 0.0] }
+    let first_cloth = world.add_mesh(&cloth, 0.001, 0.0, 0.5);
+    world.add_self_collision(first_cloth, cloth.vertices.len(), 0.01);
+    // The first row of the grid is on top.
+    world.pin(first_cloth);
+    world.pin(first_cloth + 24);
+    let mut tets = TetMesh::cuboid([0.15; 3], [3; 3]);
+    for v in &mut tets.vertices { *v += glam::Vec3::new(0.35, 0.2, 0.0) }
+    let first_cube = world.add_tet_mesh(&tets, 100.0, 1e-4, 0.0);
+    Demo{world, cloth, cube: tets.to_mesh(), first: [first_cloth, first_cube], meshes: vec![]}
+  }
+}
+
+impl App for Demo {
+  fn init(&mut self, cx:&mut Cx) {
+    self.meshes = vec![cx.add_mesh(&self.cloth), cx.add_mesh(&self.cube)];
+    cx.add_object(Object::new(self.meshes[0], Color::YELLOW.into()));
+    cx.add_object(Object::new(self.meshes[1], Color::BLUE.into()));
+  }
+
+  fn event(&mut self, cx:&mut Cx, e:&InputEvent) {
+    match *e {
+      InputEvent::MouseButton{button: MouseButton::Left, pressed: true} => {
+        let hovered = cx.selection().hovered;
+        let selection = cx.selection_mut();
+        selection.clear();
+        if let Some(object) = hovered { selection.select(object); }
+      }
+      InputEvent::Key{key: Key::W, pressed: true} => cx.gizmo_mut().mode = GizmoMode::Translate,
+      InputEvent::Key{key: Key::E, pressed: true} => cx.gizmo_mut().mode = GizmoMode::Rotate,
+      InputEvent::Key{key: Key::R, pressed: true} => cx.gizmo_mut().mode = GizmoMode::Scale,
+      _ => {}
+    }
+  }
+
+  fn update(&mut self, _cx:&mut Cx, dt:f32) { self.world.step(dt) }
+
+  fn render(&mut self, cx:&mut Cx, _time:&mut Time) {
+    self.world.write_mesh(self.first[0], &mut self.cloth);
+    self.world.write_mesh(self.first[1], &mut self.cube);
+    cx.update_mesh(self.meshes[0], &self.cloth);
+    cx.update_mesh(self.meshes[1], &self.cube);
+  }
+}
+
+fn main() {
+  mirror::run_app(Demo::new());
+}
@@ -0,0 +1,103 @@
+use winit::{event::{Event, WindowEvent}, event_loop::ControlFlow};
+use crate::{Cx, Time, Window};
+use crate::input::{InputEvent, Key, MouseButton};
+
+/// An application driven by [`run_app`], every method does nothing unless implemented.
+pub trait App {
+  /// Called once the window and GPU are ready, to add meshes and objects.
+  fn init(&mut self, _cx:&mut Cx) {}
+
+  /// Advance the simulation by one fixed step of `dt` seconds, see [`Time::step`].
+  fn update(&mut self, _cx:&mut Cx, _dt:f32) {}
+
+  /// React to keyboard, mouse and touch input as it arrives.
+  fn event(&mut self, _cx:&mut Cx, _e:&InputEvent) {}
+
+  /// Prepare the frame that is drawn right after, `time` says how far it is between two updates.
+  fn render(&mut self, _cx:&mut Cx, _time:&mut Time) {}
+
+  /// Add windows to the debug UI, drawn over the frame with the built-in panels, see [`crate::Ui`].
+  fn ui(&mut self, _cx:&mut Cx, _ctx:&egui::Context) {}
+}
+
+/// Run an app until its window is closed. On the web this returns right away and the app runs in the browser's event loop.
+/// P pauses and resumes the updates, N takes a single step while paused. The object under the cursor is hovered
+/// and the gizmo of the selection is dragged with the left mouse button. F1 shows and hides the debug UI, which
+/// takes the input it is under or focused by. These are the `"pause"`, `"step"`, `"gizmo"` and `"ui"` actions of
+/// [`crate::Input`], which [`App::init`] can rebind or unbind.
+pub fn run_app<A:App + 'static>(app:A) {
+  cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+      wasm_bindgen_futures::spawn_local(start(app));
+    } else {
+      pollster::block_on(start(app));
+    }
+  }
+}
+
+/// An app made of an `update` and a `render` callback.
+struct Callbacks<U, F> {
+  update: U,
+  frame: F,
+}
+
+impl<U:FnMut(&mut Cx, f32), F:FnMut(&mut Cx, &mut Time)> App for Callbacks<U, F> {
+  fn update(&mut self, cx:&mut Cx, dt:f32) { (self.update)(cx, dt) }
+
+  fn render(&mut self, cx:&mut Cx, time:&mut Time) { (self.frame)(cx, time) }
+}
+
+/// Open a window and call `update` for every fixed step of [`Time::step`] seconds, then `frame` once before each
+/// frame is drawn.
+pub async fn run_with<U, F>(update:U, frame:F)
+where U:FnMut(&mut Cx, f32) + 'static, F:FnMut(&mut Cx, &mut Time) + 'static {
+  start(Callbacks{update, frame}).await
+}
+
+pub(crate) async fn start<A:App + 'static>(mut app:A) {
+  crate::logging();
+  let window = Window::new();
+  let mut cx = Cx::new(&window).await;
+  let mut time = Time::default();
+  cx.ui_mut().pixels_per_point = window.window.scale_factor() as f32;
+  let input = cx.input_mut();
+  input.bind("pause", Key::P);
+  input.bind("step", Key::N);
+  input.bind("ui", Key::F1);
+  input.bind("gizmo", MouseButton::Left);
+  app.init(&mut cx);
+  window.event_loop.run(move |e, _, control_flow| {
+    match e {
+      Event::RedrawRequested(window_id) if (window_id==window.window.id()) => {
+        for _ in 0..time.tick() { app.update(&mut cx, time.step) }
+        cx.update_graph();
+        cx.update_gizmo();
+        cx.update_hover();
+        app.render(&mut cx, &mut time);
+        cx.run_ui(&mut time, |cx, ctx| app.ui(cx, ctx));
+        cx.render();
+        cx.input_mut().end_frame();
+      }
+      Event::MainEventsCleared => { window.window.request_redraw() }
+      Event::WindowEvent{ref event,window_id} if (window_id==window.window.id()) => {
+        if let WindowEvent::CloseRequested = event { *control_flow = ControlFlow::Exit }
+        if let WindowEvent::ScaleFactorChanged{scale_factor, ..} = event { cx.ui_mut().pixels_per_point = *scale_factor as f32 }
+        let input = match InputEvent::from_window_event(event) { Some(input) => input, None => return };
+        if cx.ui_mut().handle(&input) { return }
+        cx.input_mut().handle(&input);
+        if let InputEvent::Resized{width, height} = input { cx.resize(width, height) }
+        let bound = cx.input();
+        let (pause, step, ui) = (bound.pressed_by("pause", &input), bound.pressed_by("step", &input), bound.pressed_by("ui", &input));
+        let (grab, release) = (bound.pressed_by("gizmo", &input), bound.released_by("gizmo", &input));
+        if pause { time.toggle_pause() }
+        if step { time.step_once() }
+        if ui { cx.ui_mut().toggle() }
+        // Grabbing the gizmo doesn't reach the app, so clicks on it don't change the selection.
+        if grab && cx.begin_gizmo() { return }
+        if release { cx.end_gizmo() }
+        app.event(&mut cx, &input);
+      }
+      _ => {}
+    }
+  });
+}
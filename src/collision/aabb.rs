@@ -0,0 +1,84 @@
+use glam::{Affine3A, Vec3};
+use crate::mesh::Mesh;
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+  pub min: Vec3,
+  pub max: Vec3,
+}
+
+impl Aabb {
+  pub fn new(min:Vec3, max:Vec3)->Aabb { Aabb{min, max} }
+
+  /// The box that contains nothing, the identity of [`Aabb::union`].
+  pub const EMPTY: Aabb = Aabb{min: glam::const_vec3!([f32::INFINITY; 3]), max: glam::const_vec3!([f32::NEG_INFINITY; 3])};
+
+  pub fn from_points<I:IntoIterator<Item=Vec3>>(points:I)->Aabb {
+    points.into_iter().fold(Self::EMPTY, |aabb, p| Aabb{min: aabb.min.min(p), max: aabb.max.max(p)})
+  }
+
+  pub fn from_mesh(mesh:&Mesh)->Aabb { Self::from_points(mesh.vertices.iter().map(|&v| Vec3::from(v))) }
+
+  pub fn is_empty(&self)->bool { self.min.cmpgt(self.max).any() }
+
+  pub fn union(&self, other:&Aabb)->Aabb { Aabb{min: self.min.min(other.min), max: self.max.max(other.max)} }
+
+  /// Grow by `margin` on every side.
+  pub fn expand(&self, margin:f32)->Aabb { Aabb{min: self.min - Vec3::splat(margin), max: self.max + Vec3::splat(margin)} }
+
+  pub fn center(&self)->Vec3 { (self.min + self.max) * 0.5 }
+
+  pub fn half_extents(&self)->Vec3 { (self.max - self.min) * 0.5 }
+
+  pub fn contains(&self, other:&Aabb)->bool { self.min.cmple(other.min).all() && self.max.cmpge(other.max).all() }
+
+  pub fn contains_point(&self, p:Vec3)->bool { self.min.cmple(p).all() && self.max.cmpge(p).all() }
+
+  pub fn overlaps(&self, other:&Aabb)->bool { self.min.cmple(other.max).all() && self.max.cmpge(other.min).all() }
+
+  /// Surface area, the cost measure of the bounding volume hierarchy.
+  pub fn area(&self)->f32 {
+    if self.is_empty() { return 0.0 }
+    let d = self.max - self.min;
+    2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+  }
+
+  /// The box around this one after a transform.
+  pub fn transform(&self, m:&Affine3A)->Aabb {
+    let center = m.transform_point3(self.center());
+    let h = self.half_extents();
+    let extent = m.matrix3.x_axis.abs() * h.x + m.matrix3.y_axis.abs() * h.y + m.matrix3.z_axis.abs() * h.z;
+    let extent:Vec3 = extent.into();
+    Aabb{min: center - extent, max: center + extent}
+  }
+
+  /// Distance along the ray to where it enters the box, zero when it starts inside.
+  pub fn ray(&self, origin:Vec3, direction:Vec3)->Option<f32> {
+    let inv = direction.recip();
+    let (t0, t1) = ((self.min - origin) * inv, (self.max - origin) * inv);
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element();
+    if near <= far { Some(near) } else { None }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn aabb() {
+    let a = Aabb::new(Vec3::ZERO, Vec3::ONE);
+    let b = Aabb::new(Vec3::splat(0.5), Vec3::splat(2.0));
+    assert!(a.overlaps(&b) && !a.contains(&b));
+    assert_eq!(a.union(&b), Aabb::new(Vec3::ZERO, Vec3::splat(2.0)));
+    assert_eq!(a.area(), 6.0);
+    assert!(Aabb::EMPTY.is_empty() && Aabb::EMPTY.union(&a) == a);
+    assert_eq!(a.ray(Vec3::new(-1.0, 0.5, 0.5), Vec3::X), Some(1.0));
+    assert_eq!(a.ray(Vec3::new(-1.0, 2.0, 0.5), Vec3::X), None);
+    assert_eq!(a.ray(Vec3::splat(0.5), Vec3::Y), Some(0.0));
+    let turned = Affine3A::from_rotation_z(std::f32::consts::FRAC_PI_4).transform_point3(Vec3::X);
+    let rotated = a.transform(&Affine3A::from_rotation_z(std::f32::consts::FRAC_PI_4));
+    assert!(rotated.contains_point(turned * 0.99));
+  }
+}
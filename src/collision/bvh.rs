@@ -0,0 +1,255 @@
+use glam::Vec3;
+use super::Aabb;
+
+/// Handle to a leaf of a [`Bvh`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProxyRef(pub(crate) u32);
+
+#[derive(Clone, Debug)]
+enum Kind<T> {
+  Leaf(T),
+  Branch([u32;2]),
+  Free,
+}
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+  aabb: Aabb,
+  parent: Option<u32>,
+  kind: Kind<T>,
+}
+
+/// Dynamic bounding volume hierarchy. Leaves are stored with boxes enlarged by a margin so
+/// that objects moving a little don't need to be reinserted.
+#[derive(Clone, Debug)]
+pub struct Bvh<T> {
+  nodes: Vec<Node<T>>,
+  root: Option<u32>,
+  free: Vec<u32>,
+  pub margin: f32,
+}
+
+impl<T:Copy> Default for Bvh<T> {
+  fn default() -> Self { Self::new(0.1) }
+}
+
+impl<T:Copy> Bvh<T> {
+  pub fn new(margin:f32)->Self { Bvh{nodes: Vec::new(), root: None, free: Vec::new(), margin} }
+
+  pub fn is_empty(&self)->bool { self.root.is_none() }
+
+  fn allocate(&mut self, node:Node<T>)->u32 {
+    match self.free.pop() {
+      Some(i) => { self.nodes[i as usize] = node; i }
+      None => { self.nodes.push(node); (self.nodes.len() - 1) as u32 }
+    }
+  }
+
+  fn children(&self, i:u32)->Option<[u32;2]> {
+    match self.nodes[i as usize].kind { Kind::Branch(children) => Some(children), _ => None }
+  }
+
+  pub fn insert(&mut self, aabb:Aabb, data:T)->ProxyRef {
+    let leaf = self.allocate(Node{aabb: aabb.expand(self.margin), parent: None, kind: Kind::Leaf(data)});
+    self.insert_leaf(leaf);
+    ProxyRef(leaf)
+  }
+
+  /// Descend to the sibling that grows the total surface area the least.
+  fn insert_leaf(&mut self, leaf:u32) {
+    let mut index = match self.root { Some(root) => root, None => { self.root = Some(leaf); return } };
+    let aabb = self.nodes[leaf as usize].aabb;
+    while let Some(children) = self.children(index) {
+      let node = &self.nodes[index as usize].aabb;
+      let combined = node.union(&aabb).area();
+      let cost = 2.0 * combined;
+      let inheritance = 2.0 * (combined - node.area());
+      let child_cost = |c:u32| {
+        let child = &self.nodes[c as usize];
+        let grown = child.aabb.union(&aabb).area();
+        let growth = match child.kind { Kind::Leaf(_) => grown, _ => grown - child.aabb.area() };
+        growth + inheritance
+      };
+      let (c0, c1) = (child_cost(children[0]), child_cost(children[1]));
+      if cost < c0 && cost < c1 { break }
+      index = if c0 < c1 { children[0] } else { children[1] };
+    }
+    let sibling = index;
+    let parent = self.nodes[sibling as usize].parent;
+    let branch = self.allocate(Node{aabb: self.nodes[sibling as usize].aabb.union(&aabb), parent, kind: Kind::Branch([sibling, leaf])});
+    self.nodes[sibling as usize].parent = Some(branch);
+    self.nodes[leaf as usize].parent = Some(branch);
+    match parent {
+      Some(p) => self.replace_child(p, sibling, branch),
+      None => self.root = Some(branch),
+    }
+    self.refit(parent);
+  }
+
+  fn replace_child(&mut self, parent:u32, old:u32, new:u32) {
+    if let Kind::Branch(ref mut children) = self.nodes[parent as usize].kind {
+      for c in children.iter_mut() { if *c == old { *c = new } }
+    }
+  }
+
+  /// Recompute the boxes from `index` up to the root.
+  fn refit(&mut self, mut index:Option<u32>) {
+    while let Some(i) = index {
+      let [a, b] = self.children(i).unwrap();
+      self.nodes[i as usize].aabb = self.nodes[a as usize].aabb.union(&self.nodes[b as usize].aabb);
+      index = self.nodes[i as usize].parent;
+    }
+  }
+
+  pub fn remove(&mut self, proxy:ProxyRef) {
+    self.remove_leaf(proxy.0);
+    self.nodes[proxy.0 as usize].kind = Kind::Free;
+    self.free.push(proxy.0);
+  }
+
+  fn remove_leaf(&mut self, leaf:u32) {
+    let parent = match self.nodes[leaf as usize].parent { Some(parent) => parent, None => { self.root = None; return } };
+    let [a, b] = self.children(parent).unwrap();
+    let sibling = if a == leaf { b } else { a };
+    let grandparent = self.nodes[parent as usize].parent;
+    self.nodes[sibling as usize].parent = grandparent;
+    match grandparent {
+      Some(g) => self.replace_child(g, parent, sibling),
+      None => self.root = Some(sibling),
+    }
+    self.nodes[parent as usize].kind = Kind::Free;
+    self.free.push(parent);
+    self.nodes[leaf as usize].parent = None;
+    self.refit(grandparent);
+  }
+
+  /// Move a leaf, it is only reinserted when it left its enlarged box. Returns whether it was.
+  pub fn update(&mut self, proxy:ProxyRef, aabb:Aabb)->bool {
+    if self.nodes[proxy.0 as usize].aabb.contains(&aabb) { return false }
+    self.remove_leaf(proxy.0);
+    self.nodes[proxy.0 as usize].aabb = aabb.expand(self.margin);
+    self.insert_leaf(proxy.0);
+    true
+  }
+
+  /// Set the box of every leaf and grow the branches to fit, keeping the shape of the tree. Cheaper than
+  /// reinserting when everything moved a little, queries slow down if things move far from where they were.
+  pub fn refit_all(&mut self, mut aabb:impl FnMut(T)->Aabb) {
+    let root = match self.root { Some(root) => root, None => return };
+    // A walk that visits parents before children, backwards.
+    let (mut stack, mut order) = (vec![root], Vec::new());
+    while let Some(i) = stack.pop() {
+      order.push(i);
+      if let Some(children) = self.children(i) { stack.extend(children) }
+    }
+    for &i in order.iter().rev() {
+      self.nodes[i as usize].aabb = match self.nodes[i as usize].kind {
+        Kind::Leaf(data) => aabb(data).expand(self.margin),
+        Kind::Branch([a, b]) => self.nodes[a as usize].aabb.union(&self.nodes[b as usize].aabb),
+        Kind::Free => continue,
+      };
+    }
+  }
+
+  pub fn data(&self, proxy:ProxyRef)->T {
+    match self.nodes[proxy.0 as usize].kind { Kind::Leaf(data) => data, _ => panic!("not a leaf") }
+  }
+
+  /// The enlarged box stored for a leaf.
+  pub fn fat_aabb(&self, proxy:ProxyRef)->Aabb { self.nodes[proxy.0 as usize].aabb }
+
+  /// Visit the nodes whose boxes pass `test`, calling `f` for leaves.
+  fn visit(&self, mut test:impl FnMut(&Aabb)->bool, mut f:impl FnMut(ProxyRef, T)) {
+    let mut stack:Vec<u32> = self.root.into_iter().collect();
+    while let Some(i) = stack.pop() {
+      let node = &self.nodes[i as usize];
+      if !test(&node.aabb) { continue }
+      match node.kind {
+        Kind::Leaf(data) => f(ProxyRef(i), data),
+        Kind::Branch(children) => stack.extend(children),
+        Kind::Free => {}
+      }
+    }
+  }
+
+  /// Call `f` for every leaf whose box overlaps `aabb`.
+  pub fn query(&self, aabb:&Aabb, f:impl FnMut(ProxyRef, T)) { self.visit(|node| node.overlaps(aabb), f) }
+
+  /// Call `f` for every leaf whose box is hit by the ray before `max_distance`.
+  pub fn query_ray(&self, origin:Vec3, direction:Vec3, max_distance:f32, f:impl FnMut(ProxyRef, T)) {
+    self.visit(|node| matches!(node.ray(origin, direction), Some(t) if t <= max_distance), f)
+  }
+
+  /// Every pair of leaves with overlapping boxes, once each.
+  pub fn pairs(&self)->Vec<(T, T)> {
+    let mut pairs = Vec::new();
+    for (i, node) in self.nodes.iter().enumerate() {
+      if let Kind::Leaf(a) = node.kind {
+        self.query(&node.aabb, |proxy, b| if proxy.0 > i as u32 { pairs.push((a, b)) });
+      }
+    }
+    pairs
+  }
+
+  /// Number of levels below the root, a measure of how balanced the tree is.
+  pub fn height(&self)->usize {
+    fn height<T>(nodes:&[Node<T>], i:u32)->usize {
+      match nodes[i as usize].kind { Kind::Branch([a, b]) => 1 + height(nodes, a).max(height(nodes, b)), _ => 0 }
+    }
+    self.root.map_or(0, |root| height(&self.nodes, root))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cube(i:usize)->Aabb {
+    // Deterministic scatter in a 10 unit cube.
+    let p = Vec3::new((i * 37 % 100) as f32, (i * 61 % 100) as f32, (i * 17 % 100) as f32) * 0.1;
+    Aabb::new(p, p + Vec3::splat(0.5))
+  }
+
+  #[test] fn query() {
+    let mut bvh = Bvh::new(0.0);
+    let proxies:Vec<_> = (0..100).map(|i| bvh.insert(cube(i), i)).collect();
+    let region = Aabb::new(Vec3::splat(2.0), Vec3::splat(6.0));
+    let mut found = vec![];
+    bvh.query(&region, |_, i| found.push(i));
+    found.sort();
+    let expected:Vec<_> = (0..100).filter(|&i| cube(i).overlaps(&region)).collect();
+    assert_eq!(found, expected);
+    assert!(bvh.height() < 20);
+    for &proxy in &proxies[..50] { bvh.remove(proxy) }
+    let mut found = vec![];
+    bvh.query(&region, |_, i| found.push(i));
+    assert!(found.iter().all(|&i| i >= 50));
+    assert_eq!(found.len(), expected.iter().filter(|&&i| i >= 50).count());
+  }
+
+  #[test] fn update() {
+    let mut bvh = Bvh::new(0.1);
+    let a = bvh.insert(Aabb::new(Vec3::ZERO, Vec3::ONE), 'a');
+    let b = bvh.insert(Aabb::new(Vec3::splat(5.0), Vec3::splat(6.0)), 'b');
+    assert!(bvh.pairs().is_empty());
+    assert!(!bvh.update(a, Aabb::new(Vec3::splat(0.05), Vec3::splat(1.05))));
+    assert!(bvh.update(a, Aabb::new(Vec3::splat(4.5), Vec3::splat(5.5))));
+    assert_eq!(bvh.pairs().len(), 1);
+    assert_eq!(bvh.data(b), 'b');
+    let mut hits = vec![];
+    bvh.query_ray(Vec3::ZERO, Vec3::ONE.normalize(), 100.0, |_, c| hits.push(c));
+    assert_eq!(hits.len(), 2);
+  }
+
+  #[test] fn refit_all() {
+    let mut bvh = Bvh::new(0.0);
+    for i in 0..100 { bvh.insert(cube(i), i); }
+    let moved = |i:usize| { let c = cube(i); Aabb::new(c.min + Vec3::X * 20.0, c.max + Vec3::X * 20.0) };
+    bvh.refit_all(moved);
+    let region = Aabb::new(Vec3::new(22.0, 2.0, 2.0), Vec3::new(26.0, 6.0, 6.0));
+    let mut found = vec![];
+    bvh.query(&region, |_, i| found.push(i));
+    found.sort();
+    assert_eq!(found, (0..100).filter(|&i| moved(i).overlaps(&region)).collect::<Vec<_>>());
+  }
+}
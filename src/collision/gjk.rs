@@ -0,0 +1,242 @@
+//! Distance and penetration between convex sets given by their support functions.
+
+use glam::{Mat3, Vec3};
+
+/// A convex set, described by its farthest point in any direction.
+pub trait Support {
+  fn support(&self, direction:Vec3)->Vec3;
+}
+
+/// A point of the Minkowski difference `a - b` with the points it came from.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Vertex {
+  w: Vec3,
+  a: Vec3,
+  b: Vec3,
+}
+
+fn vertex(a:&impl Support, b:&impl Support, d:Vec3)->Vertex {
+  let (a, b) = (a.support(d), b.support(-d));
+  Vertex{w: a - b, a, b}
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Gjk {
+  /// The closest points on each set.
+  Separated{distance:f32, a:Vec3, b:Vec3},
+  /// A simplex of the difference that contains the origin.
+  Overlapping(Vec<Vertex>),
+}
+
+const TOLERANCE:f32 = 1e-6;
+
+/// Closest points between two convex sets, or a simplex proving they overlap.
+pub(crate) fn gjk(a:&impl Support, b:&impl Support)->Gjk {
+  let mut simplex = vec![vertex(a, b, Vec3::X)];
+  let mut weights = vec![1.0];
+  let mut v = simplex[0].w;
+  for _ in 0..64 {
+    if v.length_squared() < TOLERANCE * TOLERANCE { return Gjk::Overlapping(simplex) }
+    let next = vertex(a, b, -v);
+    // Stop when the new point doesn't get closer to the origin than the current one.
+    if v.length_squared() - v.dot(next.w) <= TOLERANCE * v.length_squared().max(1.0)
+      || simplex.iter().any(|s| s.w.distance_squared(next.w) < TOLERANCE * TOLERANCE) { break }
+    simplex.push(next);
+    let (point, lambdas) = closest(&simplex);
+    let kept:Vec<_> = (0..simplex.len()).filter(|&i| lambdas[i] > 0.0).collect();
+    simplex = kept.iter().map(|&i| simplex[i]).collect();
+    weights = kept.iter().map(|&i| lambdas[i]).collect();
+    if simplex.len() == 4 { return Gjk::Overlapping(simplex) }
+    v = point;
+  }
+  let a = simplex.iter().zip(&weights).fold(Vec3::ZERO, |sum, (s, &l)| sum + s.a * l);
+  let b = simplex.iter().zip(&weights).fold(Vec3::ZERO, |sum, (s, &l)| sum + s.b * l);
+  Gjk::Separated{distance: v.length(), a, b}
+}
+
+/// Point of the simplex closest to the origin with its barycentric coordinates, found by
+/// projecting onto every face and keeping the nearest projection that lands inside its face.
+fn closest(simplex:&[Vertex])->(Vec3, Vec<f32>) {
+  let mut best = (Vec3::ZERO, vec![0.0; simplex.len()], f32::INFINITY);
+  for mask in 1..(1u32 << simplex.len()) {
+    let indices:Vec<usize> = (0..simplex.len()).filter(|i| mask & (1 << i) != 0).collect();
+    let points:Vec<Vec3> = indices.iter().map(|&i| simplex[i].w).collect();
+    let lambdas = match barycentric(&points) { Some(l) => l, None => continue };
+    if lambdas.iter().any(|&l| l <= 0.0) { continue }
+    let p:Vec3 = points.iter().zip(&lambdas).fold(Vec3::ZERO, |sum, (p, &l)| sum + *p * l);
+    if p.length_squared() < best.2 {
+      best.1 = vec![0.0; simplex.len()];
+      for (&i, &l) in indices.iter().zip(&lambdas) { best.1[i] = l }
+      best = (p, best.1, p.length_squared());
+    }
+  }
+  (best.0, best.1)
+}
+
+/// Barycentric coordinates of the origin projected onto the affine hull of up to 4 points.
+fn barycentric(points:&[Vec3])->Option<Vec<f32>> {
+  let p0 = points[0];
+  let edges:Vec<Vec3> = points[1..].iter().map(|p| *p - p0).collect();
+  if edges.is_empty() { return Some(vec![1.0]) }
+  // Normal equations of minimizing |p0 + Σ μ e|, padded to 3x3.
+  let mut gram = Mat3::IDENTITY;
+  let mut rhs = Vec3::ZERO;
+  for (i, e) in edges.iter().enumerate() {
+    for (j, f) in edges.iter().enumerate() { gram.col_mut(j)[i] = e.dot(*f) }
+    rhs[i] = -e.dot(p0);
+  }
+  let scale = edges.iter().map(|e| e.length_squared()).fold(0.0, f32::max);
+  if gram.determinant().abs() <= 1e-10 * scale.powi(edges.len() as i32) { return None }
+  let mu = gram.inverse() * rhs;
+  let mut lambdas = vec![1.0 - mu.x - mu.y - mu.z];
+  lambdas.extend((0..edges.len()).map(|i| mu[i]));
+  if edges.len() < 3 { lambdas[0] += mu.z }
+  if edges.len() < 2 { lambdas[0] += mu.y }
+  Some(lambdas)
+}
+
+/// How deep two overlapping sets are, `normal` points from `a` towards `b` and moving `b`
+/// by `normal * depth` separates them.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Penetration {
+  pub normal: Vec3,
+  pub depth: f32,
+  pub a: Vec3,
+  pub b: Vec3,
+}
+
+/// Expanding polytope algorithm, grows the simplex from [`gjk`] towards the boundary of the
+/// difference until the face closest to the origin stops moving.
+pub(crate) fn epa(a:&impl Support, b:&impl Support, mut simplex:Vec<Vertex>)->Option<Penetration> {
+  // Blow a touching simplex up into a tetrahedron.
+  let directions = [Vec3::X, Vec3::Y, Vec3::Z, -Vec3::X, -Vec3::Y, -Vec3::Z];
+  while simplex.len() < 4 {
+    let candidates:Vec<Vec3> = match simplex.len() {
+      1 => directions.to_vec(),
+      2 => {
+        let line = simplex[1].w - simplex[0].w;
+        directions.iter().map(|d| line.cross(*d)).filter(|d| d.length_squared() > 1e-12).collect()
+      }
+      _ => {
+        let n = (simplex[1].w - simplex[0].w).cross(simplex[2].w - simplex[0].w);
+        vec![n, -n]
+      }
+    };
+    let before = simplex.len();
+    for d in candidates {
+      let v = vertex(a, b, d);
+      if affinely_independent(&simplex, v.w) { simplex.push(v); break }
+    }
+    if simplex.len() == before { return None }
+  }
+  let mut vertices = simplex;
+  let mut faces:Vec<[usize;3]> = vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+  let center = vertices.iter().fold(Vec3::ZERO, |sum, v| sum + v.w) / 4.0;
+  for f in &mut faces {
+    let n = (vertices[f[1]].w - vertices[f[0]].w).cross(vertices[f[2]].w - vertices[f[0]].w);
+    if n.dot(vertices[f[0]].w - center) < 0.0 { f.swap(1, 2) }
+  }
+  let plane = |vertices:&[Vertex], f:&[usize;3]| {
+    let n = (vertices[f[1]].w - vertices[f[0]].w).cross(vertices[f[2]].w - vertices[f[0]].w).normalize_or_zero();
+    (n, n.dot(vertices[f[0]].w))
+  };
+  for _ in 0..64 {
+    let (closest, (normal, distance)) = faces.iter().enumerate()
+      .map(|(i, f)| (i, plane(&vertices, f)))
+      .filter(|(_, (n, _))| *n != Vec3::ZERO)
+      .min_by(|x, y| x.1.1.total_cmp(&y.1.1))?;
+    let next = vertex(a, b, normal);
+    if next.w.dot(normal) - distance < 1e-4 {
+      return Some(penetration(&vertices, faces[closest], normal, distance))
+    }
+    // Replace the faces the new point can see with a fan from their outline.
+    vertices.push(next);
+    let new = vertices.len() - 1;
+    let mut horizon:Vec<(usize, usize)> = Vec::new();
+    faces.retain(|f| {
+      let (n, d) = plane(&vertices, f);
+      if n.dot(next.w) - d <= 1e-6 { return true }
+      for (i, j) in [(f[0], f[1]), (f[1], f[2]), (f[2], f[0])] {
+        match horizon.iter().position(|&e| e == (j, i)) {
+          Some(k) => { horizon.swap_remove(k); }
+          None => horizon.push((i, j)),
+        }
+      }
+      false
+    });
+    faces.extend(horizon.into_iter().map(|(i, j)| [i, j, new]));
+  }
+  let (closest, (normal, distance)) = faces.iter().enumerate()
+    .map(|(i, f)| (i, plane(&vertices, f)))
+    .min_by(|x, y| x.1.1.total_cmp(&y.1.1))?;
+  Some(penetration(&vertices, faces[closest], normal, distance))
+}
+
+fn affinely_independent(simplex:&[Vertex], w:Vec3)->bool {
+  let mut points:Vec<Vec3> = simplex.iter().map(|v| v.w - w).collect();
+  points.push(Vec3::ZERO);
+  match points.len() {
+    2 => points[0].length_squared() > 1e-12,
+    3 => points[0].cross(points[1]).length_squared() > 1e-12,
+    _ => points[0].cross(points[1]).dot(points[2]).abs() > 1e-12,
+  }
+}
+
+/// Map the origin's projection onto the closest face back to the two sets.
+fn penetration(vertices:&[Vertex], face:[usize;3], normal:Vec3, depth:f32)->Penetration {
+  let [v0, v1, v2] = face.map(|i| vertices[i]);
+  let p = normal * depth;
+  let area = |a:Vec3, b:Vec3, c:Vec3| (b - a).cross(c - a).dot(normal);
+  let total = area(v0.w, v1.w, v2.w);
+  let (l1, l2) = if total.abs() > 1e-12 { (area(v0.w, p, v2.w) / total, area(v0.w, v1.w, p) / total) } else { (0.0, 0.0) };
+  let l0 = 1.0 - l1 - l2;
+  Penetration{normal, depth, a: v0.a * l0 + v1.a * l1 + v2.a * l2, b: v0.b * l0 + v1.b * l1 + v2.b * l2}
+}
+
+impl Support for [Vec3] {
+  fn support(&self, d:Vec3)->Vec3 {
+    self.iter().copied().max_by(|a, b| a.dot(d).total_cmp(&b.dot(d))).unwrap_or(Vec3::ZERO)
+  }
+}
+
+impl<const N:usize> Support for [Vec3;N] {
+  fn support(&self, d:Vec3)->Vec3 { self[..].support(d) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cube(center:Vec3)->[Vec3;8] {
+    let mut points = [Vec3::ZERO; 8];
+    for (i, p) in points.iter_mut().enumerate() {
+      *p = center + Vec3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32) - Vec3::splat(0.5);
+    }
+    points
+  }
+
+  #[test] fn distance() {
+    match gjk(&cube(Vec3::ZERO), &cube(Vec3::new(3.0, 0.2, 0.0))) {
+      Gjk::Separated{distance, a, b} => {
+        assert!((distance - 2.0).abs() < 1e-4);
+        assert!((a.x - 0.5).abs() < 1e-4 && (b.x - 2.5).abs() < 1e-4);
+      }
+      _ => panic!("expected separated cubes"),
+    }
+    // Triangle above a point.
+    let triangle = [Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, -1.0), Vec3::new(0.0, 1.0, 1.0)];
+    match gjk(&triangle, &[Vec3::ZERO]) {
+      Gjk::Separated{distance, a, ..} => assert!((distance - 1.0).abs() < 1e-5 && a.abs_diff_eq(Vec3::Y, 1e-5)),
+      _ => panic!("expected separated"),
+    }
+  }
+
+  #[test] fn penetration() {
+    let (a, b) = (cube(Vec3::ZERO), cube(Vec3::new(0.8, 0.1, 0.0)));
+    let simplex = match gjk(&a, &b) { Gjk::Overlapping(s) => s, other => panic!("{:?}", other) };
+    let p = epa(&a, &b, simplex).unwrap();
+    assert!((p.depth - 0.2).abs() < 1e-4, "{:?}", p);
+    assert!(p.normal.abs_diff_eq(Vec3::X, 1e-4));
+    assert!((p.a.x - 0.5).abs() < 1e-4 && (p.b.x - 0.3).abs() < 1e-4);
+  }
+}
@@ -0,0 +1,255 @@
+//! Collision queries between convex shapes and triangle meshes, shared by physics and picking.
+//! A dynamic [`Bvh`] finds the pairs whose boxes overlap, GJK and EPA then measure the
+//! distance or penetration of each pair and report a [`Contact`].
+
+mod aabb;
+mod bvh;
+mod gjk;
+mod ray;
+mod trimesh;
+
+pub use aabb::Aabb;
+pub use bvh::{Bvh, ProxyRef};
+pub use ray::{Ray, RayHit};
+pub use trimesh::TriMesh;
+
+use glam::{Affine3A, Vec3};
+use gjk::{Gjk, Support};
+use crate::mesh::Mesh;
+
+/// Convex shape in its own frame, placed in the world by a motor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+  Aabb(Aabb),
+  Sphere{center:Vec3, radius:f32},
+  /// All points within `radius` of the segment from `a` to `b`.
+  Capsule{a:Vec3, b:Vec3, radius:f32},
+  /// The convex hull of a set of points.
+  Hull(Vec<Vec3>),
+}
+
+impl Shape {
+  pub fn aabb_from_mesh(mesh:&Mesh)->Shape { Shape::Aabb(Aabb::from_mesh(mesh)) }
+
+  /// Sphere around the center of the mesh bounds that encloses every vertex.
+  pub fn sphere_from_mesh(mesh:&Mesh)->Shape {
+    let center = Aabb::from_mesh(mesh).center();
+    let radius = vertices(mesh).map(|v| v.distance(center)).fold(0.0, f32::max);
+    Shape::Sphere{center, radius}
+  }
+
+  /// Capsule along the longest side of the mesh bounds that encloses every vertex.
+  pub fn capsule_from_mesh(mesh:&Mesh)->Shape {
+    let bounds = Aabb::from_mesh(mesh);
+    let center = bounds.center();
+    let h = bounds.half_extents();
+    let axis = Vec3::AXES[if h.x >= h.y && h.x >= h.z { 0 } else if h.y >= h.z { 1 } else { 2 }];
+    let split = |v:Vec3| { let t = (v - center).dot(axis); (t, (v - center - axis * t).length()) };
+    let radius = vertices(mesh).map(|v| split(v).1).fold(0.0, f32::max);
+    // Shortest segment whose end caps still reach every vertex.
+    let half = vertices(mesh).map(|v| { let (t, r) = split(v); t.abs() - (radius * radius - r * r).max(0.0).sqrt() }).fold(0.0, f32::max);
+    Shape::Capsule{a: center - axis * half, b: center + axis * half, radius}
+  }
+
+  pub fn hull_from_mesh(mesh:&Mesh)->Shape { Shape::Hull(vertices(mesh).collect()) }
+
+  /// How far the shape extends beyond its core, spheres and capsules are a point or segment grown by it.
+  pub fn radius(&self)->f32 {
+    match self { Shape::Sphere{radius, ..} | Shape::Capsule{radius, ..} => *radius, _ => 0.0 }
+  }
+
+  /// Farthest point of the core of the shape in a direction, see [`Shape::radius`].
+  fn core_support(&self, d:Vec3)->Vec3 {
+    match self {
+      Shape::Aabb(aabb) => Vec3::select(d.cmpge(Vec3::ZERO), aabb.max, aabb.min),
+      Shape::Sphere{center, ..} => *center,
+      Shape::Capsule{a, b, ..} => if (*b - *a).dot(d) > 0.0 { *b } else { *a },
+      Shape::Hull(points) => points.support(d),
+    }
+  }
+
+  /// Farthest point of the shape in a direction.
+  pub fn support(&self, d:Vec3)->Vec3 { self.core_support(d) + d.normalize_or_zero() * self.radius() }
+
+  /// Tight world space bounds of the shape at a pose.
+  pub fn bounds(&self, pose:&g3::Motor)->Aabb {
+    let posed = Posed::new(self, pose, true);
+    let (mut min, mut max) = (Vec3::ZERO, Vec3::ZERO);
+    for i in 0..3 {
+      min[i] = posed.support(-Vec3::AXES[i])[i];
+      max[i] = posed.support(Vec3::AXES[i])[i];
+    }
+    Aabb::new(min, max)
+  }
+}
+
+fn vertices(mesh:&Mesh)->impl Iterator<Item=Vec3> + '_ { mesh.vertices.iter().map(|&v| Vec3::from(v)) }
+
+/// A shape or its core moved into the world.
+struct Posed<'a> {
+  shape: &'a Shape,
+  to_world: Affine3A,
+  to_local: Affine3A,
+  rounded: bool,
+}
+
+impl<'a> Posed<'a> {
+  fn new(shape:&'a Shape, pose:&g3::Motor, rounded:bool)->Self {
+    let to_world = Affine3A::from_mat4(crate::scene::motor_to_mat4(pose));
+    Posed{shape, to_world, to_local: to_world.inverse(), rounded}
+  }
+}
+
+impl Support for Posed<'_> {
+  fn support(&self, d:Vec3)->Vec3 {
+    let d = self.to_local.transform_vector3(d);
+    let p = if self.rounded { self.shape.support(d) } else { self.shape.core_support(d) };
+    self.to_world.transform_point3(p)
+  }
+}
+
+/// Where two shapes touch. `a` and `b` are the deepest points of each shape inside the other,
+/// `normal` is the plane halfway between them facing from the first shape towards the second.
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+  pub a: g3::Point,
+  pub b: g3::Point,
+  pub normal: g3::Plane,
+  /// How far the shapes overlap along the normal.
+  pub depth: f32,
+}
+
+impl Contact {
+  fn new(a:Vec3, b:Vec3, normal:Vec3, depth:f32)->Contact {
+    let middle = (a + b) * 0.5;
+    Contact{
+      a: g3::point(a.x, a.y, a.z),
+      b: g3::point(b.x, b.y, b.z),
+      normal: g3::plane(normal.x, normal.y, normal.z, -normal.dot(middle)),
+      depth,
+    }
+  }
+}
+
+/// Separation and closest points of two convex sets grown by a radius each, negative when they overlap.
+fn closest(a:&impl Support, ra:f32, b:&impl Support, rb:f32, core_a:&impl Support, core_b:&impl Support)->Option<(f32, Vec3, Vec3, Vec3)> {
+  // Rounded shapes are exact from their cores as long as the cores stay apart.
+  if let Gjk::Separated{distance, a: pa, b: pb} = gjk::gjk(core_a, core_b) {
+    if distance > 1e-5 {
+      let n = (pb - pa) / distance;
+      return Some((distance - ra - rb, n, pa + n * ra, pb - n * rb))
+    }
+  }
+  let simplex = match gjk::gjk(a, b) {
+    Gjk::Overlapping(simplex) => simplex,
+    Gjk::Separated{distance, a: pa, b: pb} => return Some((distance, (pb - pa).normalize_or_zero(), pa, pb)),
+  };
+  gjk::epa(a, b, simplex).map(|p| (-p.depth, p.normal, p.a, p.b))
+}
+
+fn posed_closest(a:&Shape, pose_a:&g3::Motor, b:&Shape, pose_b:&g3::Motor)->Option<(f32, Vec3, Vec3, Vec3)> {
+  closest(&Posed::new(a, pose_a, true), a.radius(), &Posed::new(b, pose_b, true), b.radius(),
+    &Posed::new(a, pose_a, false), &Posed::new(b, pose_b, false))
+}
+
+/// Distance between two posed shapes, negative by the penetration depth when they overlap.
+/// `None` when the depth of an overlap can't be measured, as for flat shapes lying in one plane.
+pub fn distance(a:&Shape, pose_a:&g3::Motor, b:&Shape, pose_b:&g3::Motor)->Option<f32> {
+  posed_closest(a, pose_a, b, pose_b).map(|c| c.0)
+}
+
+/// The contact between two posed shapes, if they touch.
+pub fn contact(a:&Shape, pose_a:&g3::Motor, b:&Shape, pose_b:&g3::Motor)->Option<Contact> {
+  let (distance, normal, pa, pb) = posed_closest(a, pose_a, b, pose_b)?;
+  if distance > 0.0 { return None }
+  Some(Contact::new(pa, pb, normal, -distance))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scene::identity;
+
+  fn at(x:f32, y:f32, z:f32)->g3::Motor { g3::Motor::new(1.0, 0.0, 0.0, 0.0, -0.5 * x, -0.5 * y, -0.5 * z, 0.0) }
+
+  fn normal(c:&Contact)->Vec3 { Vec3::new(c.normal.x(), c.normal.y(), c.normal.z()) }
+
+  fn point(p:g3::Point)->Vec3 { let p:[f32;3] = p.into(); Vec3::from(p) }
+
+  #[test] fn spheres() {
+    let sphere = Shape::Sphere{center: Vec3::ZERO, radius: 1.0};
+    assert!((distance(&sphere, &identity(), &sphere, &at(3.0, 0.0, 0.0)).unwrap() - 1.0).abs() < 1e-4);
+    let c = contact(&sphere, &identity(), &sphere, &at(0.0, 1.5, 0.0)).unwrap();
+    assert!((c.depth - 0.5).abs() < 1e-4);
+    assert!(normal(&c).abs_diff_eq(Vec3::Y, 1e-4));
+    assert!(point(c.a).abs_diff_eq(Vec3::Y, 1e-4) && point(c.b).abs_diff_eq(Vec3::new(0.0, 0.5, 0.0), 1e-4));
+    // The plane passes halfway between the contact points.
+    assert!((c.normal.d() + 0.75).abs() < 1e-4);
+    // Concentric spheres still get pushed apart.
+    assert!(contact(&sphere, &identity(), &sphere, &at(0.0, 0.0, 0.01)).unwrap().depth > 1.9);
+  }
+
+  #[test] fn boxes() {
+    let cube = Shape::Aabb(Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5)));
+    let hull = Shape::hull_from_mesh(&Mesh::cuboid([0.5; 3]));
+    let c = contact(&cube, &identity(), &hull, &at(0.1, 0.9, 0.2)).unwrap();
+    assert!((c.depth - 0.1).abs() < 1e-4, "{:?}", c);
+    assert!(normal(&c).abs_diff_eq(Vec3::Y, 1e-4));
+    assert!(contact(&cube, &identity(), &hull, &at(0.0, 1.1, 0.0)).is_none());
+    // A quarter turn about y leaves the cube's bounds unchanged.
+    let turned = g3::Motor::from(g3::rotor(std::f32::consts::FRAC_PI_2, 0.0, 1.0, 0.0));
+    assert!(hull.bounds(&turned).min.abs_diff_eq(Vec3::splat(-0.5), 1e-5));
+  }
+
+  #[test] fn flat() {
+    // Two squares in the same plane overlap without a direction to push them apart along.
+    let square = Shape::Hull(vec![Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, 1.0, 0.0)]);
+    assert_eq!(distance(&square, &identity(), &square, &at(0.5, 0.0, 0.0)), None);
+    assert!(contact(&square, &identity(), &square, &at(0.5, 0.0, 0.0)).is_none());
+    // Apart they still have a distance.
+    assert!((distance(&square, &identity(), &square, &at(3.0, 0.0, 0.0)).unwrap() - 1.0).abs() < 1e-4);
+  }
+
+  #[test] fn capsule() {
+    let mesh = Mesh::cuboid([0.25, 1.0, 0.25]);
+    let capsule = Shape::capsule_from_mesh(&mesh);
+    match capsule {
+      Shape::Capsule{a, b, radius} => {
+        assert!((a.x.abs() + b.x.abs() + a.z.abs() + b.z.abs()) < 1e-6);
+        assert!(vertices(&mesh).all(|v| (v - Vec3::new(0.0, v.y.clamp(a.y, b.y), 0.0)).length() <= radius + 1e-5));
+      }
+      _ => unreachable!(),
+    }
+    let sphere = Shape::Sphere{center: Vec3::ZERO, radius: 0.5};
+    let r = capsule.radius();
+    let side = distance(&capsule, &identity(), &sphere, &at(1.0, 0.3, 0.0)).unwrap();
+    assert!((side - (0.5 - r)).abs() < 1e-4);
+  }
+
+  #[test] fn floor() {
+    let floor = TriMesh::from_mesh(&Mesh::cuboid([5.0, 0.5, 5.0]));
+    let floor_pose = at(0.0, -0.5, 0.0);
+    let sphere = Shape::Sphere{center: Vec3::ZERO, radius: 0.5};
+    let contacts = floor.contacts(&floor_pose, &sphere, &at(1.3, 0.45, -2.2));
+    assert!(!contacts.is_empty());
+    for c in &contacts {
+      assert!(normal(c).abs_diff_eq(Vec3::Y, 1e-4), "{:?}", c);
+      assert!((c.depth - 0.05).abs() < 1e-4);
+    }
+    assert!(floor.contacts(&floor_pose, &sphere, &at(0.0, 0.6, 0.0)).is_empty());
+    let hit = floor.ray(&Ray::new(Vec3::new(1.0, 3.0, 2.0), -Vec3::Y)).unwrap();
+    assert!((hit.distance - 2.5).abs() < 1e-5);
+    let [a, b, c] = floor.triangles[hit.triangle].map(|v| floor.vertices[v as usize]);
+    assert!((a * hit.barycentric[0] + b * hit.barycentric[1] + c * hit.barycentric[2]).abs_diff_eq(Vec3::new(1.0, 0.5, 2.0), 1e-5));
+    let cube = Shape::hull_from_mesh(&Mesh::cuboid([0.5; 3]));
+    let contacts = floor.contacts(&floor_pose, &cube, &at(0.0, 0.4, 0.0));
+    assert!(contacts.iter().all(|c| normal(c).abs_diff_eq(Vec3::Y, 1e-3) && (c.depth - 0.1).abs() < 1e-3), "{:?}", contacts);
+    // Raising the vertices refits the tree, rays find the triangles where they moved to.
+    let mut raised = Mesh::cuboid([5.0, 0.5, 5.0]);
+    for v in &mut raised.vertices { v[1] += 1.0 }
+    let mut floor = floor;
+    floor.update(&raised);
+    assert!((floor.ray(&Ray::new(Vec3::new(1.0, 3.0, 2.0), -Vec3::Y)).unwrap().distance - 1.5).abs() < 1e-5);
+    assert!((floor.bounds().max.y - 1.5).abs() < 1e-6);
+  }
+}
@@ -0,0 +1,80 @@
+use glam::{Affine3A, Vec3};
+
+/// Half line from `origin` along `direction`, distances along it are in units of the direction's length.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+  pub origin: Vec3,
+  pub direction: Vec3,
+}
+
+impl Ray {
+  pub fn new(origin:Vec3, direction:Vec3)->Ray { Ray{origin, direction} }
+
+  /// The ray along a line with unit speed, starting at the point of the line closest to the origin.
+  pub fn from_line(line:&g3::Line)->Ray {
+    let d = Vec3::new(line.e23(), line.e31(), line.e12());
+    let m = Vec3::new(line.e01(), line.e02(), line.e03());
+    let length = d.length();
+    Ray{origin: d.cross(m) / (length * length), direction: d / length}
+  }
+
+  /// The line through the ray, oriented along it.
+  pub fn to_line(&self)->g3::Line {
+    let (d, m) = (self.direction, self.origin.cross(self.direction));
+    g3::line(m.x, m.y, m.z, d.x, d.y, d.z)
+  }
+
+  pub fn at(&self, t:f32)->Vec3 { self.origin + self.direction * t }
+
+  pub fn transform(&self, m:&Affine3A)->Ray { Ray{origin: m.transform_point3(self.origin), direction: m.transform_vector3(self.direction)} }
+
+  /// Distance to where the ray crosses a triangle, and the barycentric coordinates of the crossing.
+  /// Both sides of the triangle are hit.
+  pub fn triangle(&self, [a, b, c]:[Vec3;3])->Option<(f32, [f32;3])> {
+    let (e1, e2) = (b - a, c - a);
+    let p = self.direction.cross(e2);
+    let det = e1.dot(p);
+    if det.abs() < 1e-12 { return None }
+    let s = self.origin - a;
+    let u = s.dot(p) / det;
+    if !(0.0..=1.0).contains(&u) { return None }
+    let q = s.cross(e1);
+    let v = self.direction.dot(q) / det;
+    if v < 0.0 || u + v > 1.0 { return None }
+    let t = e2.dot(q) / det;
+    if t < 0.0 { return None }
+    Some((t, [1.0 - u - v, u, v]))
+  }
+}
+
+/// Where a ray crosses a triangle mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+  pub distance: f32,
+  /// Index of the triangle, its vertices are `indices[3 * triangle..3 * triangle + 3]` of the mesh.
+  pub triangle: usize,
+  /// Weights of the triangle's vertices at the hit.
+  pub barycentric: [f32;3],
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn line() {
+    let ray = Ray::new(Vec3::new(1.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+    let back = Ray::from_line(&ray.to_line());
+    assert!(back.origin.abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-6) && back.direction.abs_diff_eq(Vec3::Z, 1e-6));
+    // The same line as the join of two of its points.
+    let join = Ray::from_line(&(g3::point(1.0, 2.0, 5.0) & g3::point(1.0, 2.0, 7.0)));
+    assert!(join.origin.abs_diff_eq(back.origin, 1e-5) && join.direction.abs_diff_eq(Vec3::Z, 1e-5));
+  }
+
+  #[test] fn triangle() {
+    let triangle = [Vec3::ZERO, Vec3::X, Vec3::Y];
+    let (t, [w, u, v]) = Ray::new(Vec3::new(0.25, 0.5, 2.0), -Vec3::Z).triangle(triangle).unwrap();
+    assert!((t - 2.0).abs() < 1e-6 && (u - 0.25).abs() < 1e-6 && (v - 0.5).abs() < 1e-6 && (w - 0.25).abs() < 1e-6);
+    assert!(Ray::new(Vec3::new(0.75, 0.5, 2.0), -Vec3::Z).triangle(triangle).is_none());
+    assert!(Ray::new(Vec3::new(0.25, 0.5, 2.0), Vec3::Z).triangle(triangle).is_none());
+  }
+}
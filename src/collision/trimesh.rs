@@ -0,0 +1,78 @@
+use glam::{Affine3A, Vec3};
+use crate::mesh::Mesh;
+use super::{closest, Aabb, Bvh, Contact, Posed, Ray, RayHit, Shape};
+use super::gjk::Support;
+
+/// Triangle soup for static level geometry, with a tree over its triangles.
+/// Only the front faces collide, shapes that end up behind a triangle are pushed back out the front.
+#[derive(Clone, Debug)]
+pub struct TriMesh {
+  pub vertices: Vec<Vec3>,
+  pub triangles: Vec<[u32;3]>,
+  bounds: Aabb,
+  bvh: Bvh<u32>,
+}
+
+impl TriMesh {
+  pub fn from_mesh(mesh:&Mesh)->TriMesh {
+    let vertices:Vec<Vec3> = mesh.vertices.iter().map(|&v| Vec3::from(v)).collect();
+    let triangles:Vec<[u32;3]> = mesh.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+    let mut bvh = Bvh::new(0.0);
+    for (i, t) in triangles.iter().enumerate() {
+      bvh.insert(Aabb::from_points(t.map(|v| vertices[v as usize])), i as u32);
+    }
+    let bounds = Aabb::from_points(vertices.iter().copied());
+    TriMesh{vertices, triangles, bounds, bvh}
+  }
+
+  /// Move the vertices to those of a mesh with the same triangles, refitting the tree instead of rebuilding it.
+  pub fn update(&mut self, mesh:&Mesh) {
+    for (v, &p) in self.vertices.iter_mut().zip(&mesh.vertices) { *v = Vec3::from(p) }
+    let (vertices, triangles) = (&self.vertices, &self.triangles);
+    self.bvh.refit_all(|i| Aabb::from_points(triangles[i as usize].map(|v| vertices[v as usize])));
+    self.bounds = Aabb::from_points(self.vertices.iter().copied());
+  }
+
+  pub fn bounds(&self)->Aabb { self.bounds }
+
+  /// The nearest triangle hit by a ray in the frame of the mesh.
+  pub fn ray(&self, ray:&Ray)->Option<RayHit> {
+    let mut nearest:Option<RayHit> = None;
+    self.bvh.query_ray(ray.origin, ray.direction, f32::INFINITY, |_, i| {
+      let triangle = self.triangles[i as usize].map(|v| self.vertices[v as usize]);
+      let (distance, barycentric) = match ray.triangle(triangle) { Some(hit) => hit, None => return };
+      match nearest {
+        Some(hit) if hit.distance <= distance => {}
+        _ => nearest = Some(RayHit{distance, triangle: i as usize, barycentric}),
+      }
+    });
+    nearest
+  }
+
+  /// A contact for every triangle of the posed mesh that touches the posed shape.
+  pub fn contacts(&self, pose:&g3::Motor, shape:&Shape, shape_pose:&g3::Motor)->Vec<Contact> {
+    let to_world = Affine3A::from_mat4(crate::scene::motor_to_mat4(pose));
+    let region = shape.bounds(shape_pose).transform(&to_world.inverse());
+    let (rounded, core) = (Posed::new(shape, shape_pose, true), Posed::new(shape, shape_pose, false));
+    let mut contacts = Vec::new();
+    self.bvh.query(&region, |_, i| {
+      let triangle = self.triangles[i as usize].map(|v| to_world.transform_point3(self.vertices[v as usize]));
+      let face = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]).normalize_or_zero();
+      let (distance, normal, a, b) = match closest(&triangle, 0.0, &rounded, shape.radius(), &triangle, &core) {
+        Some(c) => c,
+        None => return,
+      };
+      if distance > 0.0 { return }
+      // Resolve along the face normal instead of through the back of a thin triangle.
+      if normal.dot(face) < 0.5 {
+        let depth = face.dot(triangle[0]) - face.dot(rounded.support(-face));
+        if depth < 0.0 { return }
+        let b = rounded.support(-face);
+        contacts.push(Contact::new(b + face * depth, b, face, depth));
+        return
+      }
+      contacts.push(Contact::new(a, b, normal, -distance));
+    });
+    contacts
+  }
+}
@@ -1,21 +1,79 @@
+use std::str::FromStr;
+
+mod names;
+
+pub use names::NAMED_COLORS;
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, PartialOrd)]
 pub struct Color(pub u32);
 
 impl Color {
   pub const BLACK: Self = Self(0x000000FF);
   pub const WHITE: Self = Self(0xFFFFFFFF);
-  pub const GREY: Self = Self(0xFF888888);
+  pub const GREY: Self = Self(0x888888FF);
   pub const RED: Self = Self(0xFF0000FF);
   pub const GREEN: Self = Self(0x00FF00FF);
   pub const BLUE: Self = Self(0x0000FFFF);
-  pub const YELLOW: Self = Self(0xFF00FFFF);
-  pub const CYAN: Self = Self(0xFFFF00FF);
-  pub const MAGENTA: Self = Self(0x00FFFFFF);
+  pub const YELLOW: Self = Self(0xFFFF00FF);
+  pub const CYAN: Self = Self(0x00FFFFFF);
+  pub const MAGENTA: Self = Self(0xFF00FFFF);
 
   pub fn red(&self)->f32 { ((self.0 >> 24) & 0xff) as f32 / 255.0 }
   pub fn green(&self)->f32 { ((self.0 >> 16) & 0xff) as f32 / 255.0 }
   pub fn blue(&self)->f32 { ((self.0 >> 8) & 0xff) as f32 / 255.0 }
   pub fn alpha(&self)->f32 { ((self.0) & 0xff) as f32 / 255.0 }
+
+  /// Pack sRGB encoded channels in the range `0.0..=1.0`.
+  pub fn from_rgba_f32(r:f32, g:f32, b:f32, a:f32)->Self {
+    let c = |v:f32| (v.clamp(0.0, 1.0) * 255.0).round() as u32;
+    Self(c(r) << 24 | c(g) << 16 | c(b) << 8 | c(a))
+  }
+
+  /// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, the leading `#` is optional.
+  pub fn from_hex(hex:&str)->Option<Self> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) { return None }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    // Every digit of the short forms is repeated, 0xf becomes 0xff.
+    let expand = |v:u32, n:u32| (0..n).rev().fold(0, |acc, i| (acc << 8) | (((v >> (4 * i)) & 0xf) * 0x11));
+    match hex.len() {
+      3 => Some(Self(expand(value, 3) << 8 | 0xff)),
+      4 => Some(Self(expand(value, 4))),
+      6 => Some(Self(value << 8 | 0xff)),
+      8 => Some(Self(value)),
+      _ => None,
+    }
+  }
+
+  /// Look up a CSS color name, ignoring case.
+  pub fn named(name:&str)->Option<Self> {
+    let name = name.to_ascii_lowercase();
+    NAMED_COLORS.binary_search_by(|(n, _)| (*n).cmp(name.as_str())).ok().map(|i| Self(NAMED_COLORS[i].1))
+  }
+
+  /// Decode the sRGB channels to linear light, which is what shaders compute and blend in.
+  pub fn to_linear(&self)->[f32;4] {
+    [srgb_to_linear(self.red()), srgb_to_linear(self.green()), srgb_to_linear(self.blue()), self.alpha()]
+  }
+
+  /// Encode linear channels back to sRGB.
+  pub fn from_linear(rgba:[f32;4])->Self {
+    Self::from_rgba_f32(linear_to_srgb(rgba[0]), linear_to_srgb(rgba[1]), linear_to_srgb(rgba[2]), rgba[3])
+  }
+
+  /// Clear value for a target, sRGB formats encode on write so they take linear values, other formats take the stored value.
+  pub fn to_wgpu(&self, format:wgpu::TextureFormat)->wgpu::Color {
+    let [r, g, b, a] = if format.describe().srgb { self.to_linear() } else { (*self).into() };
+    wgpu::Color{r: r as f64, g: g as f64, b: b as f64, a: a as f64}
+  }
+}
+
+pub fn srgb_to_linear(c:f32)->f32 {
+  if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(l:f32)->f32 {
+  if l <= 0.0031308 { l * 12.92 } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 }
 }
 
 impl Into<[f32;4]> for Color {
@@ -34,6 +92,334 @@ impl Into<[f32;4]> for Color {
 //   fn into(self) -> glam::Vec3 { glam::Vec3::new(self.red(), self.blue(), self.green()) }
 // }
 
+/// Hue in degrees, saturation and value in `0.0..=1.0`, computed from the sRGB encoded channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hsv { pub h:f32, pub s:f32, pub v:f32, pub a:f32 }
+
+/// Hue in degrees, saturation and lightness in `0.0..=1.0`, computed from the sRGB encoded channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hsl { pub h:f32, pub s:f32, pub l:f32, pub a:f32 }
+
+/// Perceptual color space by Björn Ottosson, <https://bottosson.github.io/posts/oklab/>.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Oklab { pub l:f32, pub a:f32, pub b:f32, pub alpha:f32 }
+
+/// Polar form of [`Oklab`], chroma and hue in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Oklch { pub l:f32, pub c:f32, pub h:f32, pub alpha:f32 }
+
+/// Hue of sRGB channels in degrees, with the largest and smallest channel.
+fn hue(r:f32, g:f32, b:f32)->(f32, f32, f32) {
+  let max = r.max(g).max(b); let min = r.min(g).min(b); let d = max - min;
+  let h = if d == 0.0 { 0.0 }
+    else if max == r { 60.0 * ((g - b) / d).rem_euclid(6.0) }
+    else if max == g { 60.0 * ((b - r) / d + 2.0) }
+    else { 60.0 * ((r - g) / d + 4.0) };
+  (h, max, min)
+}
+
+/// sRGB channels from hue, chroma and the amount added to every channel.
+fn from_hue(h:f32, c:f32, m:f32)->[f32;3] {
+  let h = h.rem_euclid(360.0) / 60.0;
+  let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+  let [r, g, b] = match h as u32 { 0 => [c, x, 0.0], 1 => [x, c, 0.0], 2 => [0.0, c, x], 3 => [0.0, x, c], 4 => [x, 0.0, c], _ => [c, 0.0, x] };
+  [r + m, g + m, b + m]
+}
+
+impl From<Color> for Hsv {
+  fn from(c:Color) -> Hsv {
+    let (h, max, min) = hue(c.red(), c.green(), c.blue());
+    Hsv{h, s: if max == 0.0 { 0.0 } else { (max - min) / max }, v: max, a: c.alpha()}
+  }
+}
+
+impl From<Hsv> for Color {
+  fn from(c:Hsv) -> Color {
+    let chroma = c.v * c.s;
+    let [r, g, b] = from_hue(c.h, chroma, c.v - chroma);
+    Color::from_rgba_f32(r, g, b, c.a)
+  }
+}
+
+impl From<Color> for Hsl {
+  fn from(c:Color) -> Hsl {
+    let (h, max, min) = hue(c.red(), c.green(), c.blue());
+    let l = (max + min) / 2.0;
+    let s = if max == min { 0.0 } else { (max - min) / (1.0 - (2.0 * l - 1.0).abs()) };
+    Hsl{h, s, l, a: c.alpha()}
+  }
+}
+
+impl From<Hsl> for Color {
+  fn from(c:Hsl) -> Color {
+    let chroma = (1.0 - (2.0 * c.l - 1.0).abs()) * c.s;
+    let [r, g, b] = from_hue(c.h, chroma, c.l - chroma / 2.0);
+    Color::from_rgba_f32(r, g, b, c.a)
+  }
+}
+
+impl From<Color> for Oklab {
+  fn from(c:Color) -> Oklab {
+    let [r, g, b, alpha] = c.to_linear();
+    let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+    Oklab {
+      l: 0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+      a: 1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+      b: 0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+      alpha}
+  }
+}
+
+impl From<Oklab> for Color {
+  fn from(c:Oklab) -> Color {
+    let l = (c.l + 0.39633778 * c.a + 0.21580376 * c.b).powi(3);
+    let m = (c.l - 0.105561346 * c.a - 0.06385417 * c.b).powi(3);
+    let s = (c.l - 0.08948418 * c.a - 1.2914855 * c.b).powi(3);
+    Color::from_linear([
+      4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+      -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+      -0.0041960863 * l - 0.7034186 * m + 1.7076147 * s,
+      c.alpha])
+  }
+}
+
+impl From<Oklab> for Oklch {
+  fn from(c:Oklab) -> Oklch {
+    Oklch{l: c.l, c: c.a.hypot(c.b), h: c.b.atan2(c.a).to_degrees().rem_euclid(360.0), alpha: c.alpha}
+  }
+}
+
+impl From<Oklch> for Oklab {
+  fn from(c:Oklch) -> Oklab {
+    let (sin, cos) = c.h.to_radians().sin_cos();
+    Oklab{l: c.l, a: c.c * cos, b: c.c * sin, alpha: c.alpha}
+  }
+}
+
+impl From<Color> for Oklch {
+  fn from(c:Color) -> Oklch { Oklab::from(c).into() }
+}
+
+impl From<Oklch> for Color {
+  fn from(c:Oklch) -> Color { Oklab::from(c).into() }
+}
+
+/// Space in which [`Color::lerp`] blends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace { Srgb, Linear, Hsv, Hsl, Oklab, Oklch }
+
+fn mix(a:f32, b:f32, t:f32)->f32 { a + (b - a) * t }
+
+/// Interpolate hues in degrees along the shortest arc.
+fn mix_hue(a:f32, b:f32, t:f32)->f32 {
+  let d = (b - a + 180.0).rem_euclid(360.0) - 180.0;
+  (a + d * t).rem_euclid(360.0)
+}
+
+impl Color {
+  /// Blend towards `other`, `t` of 0.0 gives `self` and 1.0 gives `other`.
+  pub fn lerp(self, other:Color, t:f32, space:ColorSpace)->Color {
+    match space {
+      ColorSpace::Srgb => {
+        let (a, b):([f32;4], [f32;4]) = (self.into(), other.into());
+        Color::from_rgba_f32(mix(a[0], b[0], t), mix(a[1], b[1], t), mix(a[2], b[2], t), mix(a[3], b[3], t))
+      }
+      ColorSpace::Linear => {
+        let (a, b) = (self.to_linear(), other.to_linear());
+        Color::from_linear([mix(a[0], b[0], t), mix(a[1], b[1], t), mix(a[2], b[2], t), mix(a[3], b[3], t)])
+      }
+      ColorSpace::Hsv => {
+        let (a, b) = (Hsv::from(self), Hsv::from(other));
+        Hsv{h: mix_hue(a.h, b.h, t), s: mix(a.s, b.s, t), v: mix(a.v, b.v, t), a: mix(a.a, b.a, t)}.into()
+      }
+      ColorSpace::Hsl => {
+        let (a, b) = (Hsl::from(self), Hsl::from(other));
+        Hsl{h: mix_hue(a.h, b.h, t), s: mix(a.s, b.s, t), l: mix(a.l, b.l, t), a: mix(a.a, b.a, t)}.into()
+      }
+      ColorSpace::Oklab => {
+        let (a, b) = (Oklab::from(self), Oklab::from(other));
+        Oklab{l: mix(a.l, b.l, t), a: mix(a.a, b.a, t), b: mix(a.b, b.b, t), alpha: mix(a.alpha, b.alpha, t)}.into()
+      }
+      ColorSpace::Oklch => {
+        let (a, b) = (Oklch::from(self), Oklch::from(other));
+        Oklch{l: mix(a.l, b.l, t), c: mix(a.c, b.c, t), h: mix_hue(a.h, b.h, t), alpha: mix(a.alpha, b.alpha, t)}.into()
+      }
+    }
+  }
+}
+
+/// Piecewise interpolation between color stops placed in `0.0..=1.0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+  stops: Vec<(f32, Color)>,
+  space: ColorSpace,
+}
+
+impl Gradient {
+  pub fn new(mut stops:Vec<(f32, Color)>, space:ColorSpace)->Self {
+    assert!(!stops.is_empty(), "a gradient needs at least one stop");
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Gradient{stops, space}
+  }
+
+  /// Stops spread evenly over `0.0..=1.0`.
+  pub fn even(colors:&[Color], space:ColorSpace)->Self {
+    let n = (colors.len().max(2) - 1) as f32;
+    Self::new(colors.iter().enumerate().map(|(i, c)| (i as f32 / n, *c)).collect(), space)
+  }
+
+  pub fn sample(&self, t:f32)->Color {
+    let i = self.stops.partition_point(|(p, _)| *p <= t);
+    if i == 0 { return self.stops[0].1 }
+    if i == self.stops.len() { return self.stops[i - 1].1 }
+    let ((p0, c0), (p1, c1)) = (self.stops[i - 1], self.stops[i]);
+    c0.lerp(c1, (t - p0) / (p1 - p0), self.space)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ramp { Gradient(Gradient), Turbo }
+
+/// Maps scalars in `range` to colors, values outside the range are clamped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Colormap {
+  ramp: Ramp,
+  pub range: std::ops::Range<f32>,
+}
+
+impl Colormap {
+  pub fn new(gradient:Gradient)->Self { Colormap{ramp: Ramp::Gradient(gradient), range: 0.0..1.0} }
+
+  pub fn with_range(mut self, min:f32, max:f32)->Self { self.range = min..max; self }
+
+  /// Perceptually uniform blue to yellow map from matplotlib.
+  pub fn viridis()->Self {
+    Self::new(Gradient::even(&[0x440154, 0x482878, 0x3E4A89, 0x31688E, 0x26828E, 0x1F9E89, 0x35B779, 0x6DCD59, 0xB4DE2C, 0xFDE725].map(rgb), ColorSpace::Oklab))
+  }
+
+  /// Perceptually uniform black to light yellow map from matplotlib.
+  pub fn magma()->Self {
+    Self::new(Gradient::even(&[0x000004, 0x180F3E, 0x451077, 0x721F81, 0x9F2F7F, 0xCD4071, 0xF1605D, 0xFD9567, 0xFEC98D, 0xFCFDBF].map(rgb), ColorSpace::Oklab))
+  }
+
+  /// Rainbow map with smooth lightness, polynomial fit by Google, <https://ai.googleblog.com/2019/08/turbo-improved-rainbow-colormap-for.html>.
+  pub fn turbo()->Self { Colormap{ramp: Ramp::Turbo, range: 0.0..1.0} }
+
+  /// Two hues diverging from a neutral middle, the middle of the range maps to `mid`.
+  pub fn diverging(low:Color, mid:Color, high:Color)->Self {
+    Self::new(Gradient::even(&[low, mid, high], ColorSpace::Oklab))
+  }
+
+  /// Blue to red diverging map.
+  pub fn coolwarm()->Self { Self::diverging(rgb(0x3B4CC0), rgb(0xDDDDDD), rgb(0xB40426)) }
+
+  pub fn map(&self, value:f32)->Color {
+    let t = ((value - self.range.start) / (self.range.end - self.range.start)).clamp(0.0, 1.0);
+    let t = if t.is_nan() { 0.0 } else { t };
+    match self.ramp {
+      Ramp::Gradient(ref gradient) => gradient.sample(t),
+      Ramp::Turbo => {
+        let r = 0.13572138 + t * (4.6153927 + t * (-42.660324 + t * (132.13109 + t * (-152.9424 + t * 59.28638))));
+        let g = 0.09140261 + t * (2.1941884 + t * (4.8429666 + t * (-14.185034 + t * (4.2772985 + t * 2.829566))));
+        let b = 0.1066733 + t * (12.641946 + t * (-60.582047 + t * (110.36277 + t * (-89.90311 + t * 27.34825))));
+        Color::from_rgba_f32(r, g, b, 1.0)
+      }
+    }
+  }
+
+  /// Color every value, e.g. one scalar per vertex of a mesh.
+  pub fn map_all(&self, values:&[f32])->Vec<Color> {
+    values.iter().map(|v| self.map(*v)).collect()
+  }
+}
+
+/// Opaque color from `0xrrggbb`.
+const fn rgb(hex:u32)->Color { Color(hex << 8 | 0xff) }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+impl std::fmt::Display for ParseColorError {
+  fn fmt(&self, f:&mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "invalid color: {:?}", self.0) }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Number or percentage of `full`.
+fn parse_component(s:&str, full:f32)->Option<f32> {
+  match s.strip_suffix('%') {
+    Some(p) => p.trim().parse::<f32>().ok().map(|v| v / 100.0 * full),
+    None => s.parse::<f32>().ok(),
+  }
+}
+
+fn parse_hue(s:&str)->Option<f32> {
+  if let Some(v) = s.strip_suffix("deg") { return v.trim().parse().ok() }
+  if let Some(v) = s.strip_suffix("turn") { return v.trim().parse::<f32>().ok().map(|v| v * 360.0) }
+  if let Some(v) = s.strip_suffix("rad") { return v.trim().parse::<f32>().ok().map(f32::to_degrees) }
+  s.parse().ok()
+}
+
+/// Arguments of `name(...)` separated by commas, or by spaces with an optional `/ alpha`.
+fn parse_function<'a>(s:&'a str, names:&[&str])->Option<Vec<&'a str>> {
+  let open = s.find('(')?;
+  if !names.contains(&&s[..open]) { return None }
+  let args = s[open + 1..].strip_suffix(')')?;
+  let args:Vec<&str> = if args.contains(',') {
+    args.split(',').map(str::trim).collect()
+  } else {
+    let (color, alpha) = match args.split_once('/') { Some((c, a)) => (c, Some(a.trim())), None => (args, None) };
+    color.split_whitespace().chain(alpha).collect()
+  };
+  if args.len() == 3 || args.len() == 4 { Some(args) } else { None }
+}
+
+impl FromStr for Color {
+  type Err = ParseColorError;
+
+  /// Accepts `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`, `hsla()` and CSS color names.
+  fn from_str(s:&str) -> Result<Self, Self::Err> {
+    let err = || ParseColorError(s.to_string());
+    let lower = s.trim().to_ascii_lowercase();
+    if lower.starts_with('#') { return Color::from_hex(&lower).ok_or_else(err) }
+    if let Some(args) = parse_function(&lower, &["rgb", "rgba"]) {
+      let c = |i:usize| parse_component(args[i], 255.0).map(|v| v / 255.0);
+      let a = if args.len() == 4 { parse_component(args[3], 1.0) } else { Some(1.0) };
+      return match (c(0), c(1), c(2), a) {
+        (Some(r), Some(g), Some(b), Some(a)) => Ok(Color::from_rgba_f32(r, g, b, a)),
+        _ => Err(err()),
+      }
+    }
+    if let Some(args) = parse_function(&lower, &["hsl", "hsla"]) {
+      let a = if args.len() == 4 { parse_component(args[3], 1.0) } else { Some(1.0) };
+      return match (parse_hue(args[0]), parse_component(args[1], 1.0), parse_component(args[2], 1.0), a) {
+        (Some(h), Some(s), Some(l), Some(a)) => Ok(Hsl{h, s: s.clamp(0.0, 1.0), l: l.clamp(0.0, 1.0), a}.into()),
+        _ => Err(err()),
+      }
+    }
+    Color::named(&lower).ok_or_else(err)
+  }
+}
+
+impl std::fmt::Display for Color {
+  /// Canonical `#rrggbbaa` form, which parses back to the same color.
+  fn fmt(&self, f:&mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "#{:08x}", self.0) }
+}
+
+/// Saved in the `#rrggbbaa` form, read back from anything [`Color::from_str`] accepts.
+impl serde::Serialize for Color {
+  fn serialize<S:serde::Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> { serializer.collect_str(self) }
+}
+
+impl<'de> serde::Deserialize<'de> for Color {
+  fn deserialize<D:serde::Deserializer<'de>>(deserializer:D) -> Result<Self, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -42,4 +428,115 @@ mod tests {
     assert_eq!([Color::RED.red(), Color::RED.green(), Color::RED.blue(), Color::RED.alpha()], [1.0, 0.0, 0.0, 1.0]);
     assert_eq!([Color::CYAN.red(), Color::CYAN.green(), Color::CYAN.blue(), Color::CYAN.alpha()], [0.0, 1.0, 1.0, 1.0]);
   }
+
+  #[test] fn hex() {
+    assert_eq!(Color::from_hex("#ff0000ff"), Some(Color::RED));
+    assert_eq!(Color::from_hex("#00ff00"), Some(Color::GREEN));
+    assert_eq!(Color::from_hex("0000ffff"), Some(Color::BLUE));
+    assert_eq!(Color::from_hex("#12345"), None);
+    assert_eq!(Color::from_hex("#+1234567"), None);
+  }
+
+  #[test] fn linear() {
+    assert_eq!(Color::WHITE.to_linear(), [1.0, 1.0, 1.0, 1.0]);
+    assert_eq!(Color::BLACK.to_linear(), [0.0, 0.0, 0.0, 1.0]);
+    let grey = Color::from_rgba_f32(0.5, 0.5, 0.5, 1.0);
+    assert!((grey.to_linear()[0] - 0.2158605).abs() < 1e-3);
+    for v in 0..=255u32 {
+      let c = Color(v << 24 | v << 16 | v << 8 | 0xff);
+      assert_eq!(Color::from_linear(c.to_linear()), c);
+    }
+  }
+
+  fn close(a:Color, b:Color)->bool {
+    let (a, b):([f32;4], [f32;4]) = (a.into(), b.into());
+    a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= 1.5 / 255.0)
+  }
+
+  #[test] fn models() {
+    let colors = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE, Color::BLACK, Color(0x336699ff), Color(0xfa8072ff)];
+    for c in colors {
+      assert!(close(Hsv::from(c).into(), c), "hsv {:?}", c);
+      assert!(close(Hsl::from(c).into(), c), "hsl {:?}", c);
+      assert!(close(Oklab::from(c).into(), c), "oklab {:?}", c);
+      assert!(close(Oklch::from(c).into(), c), "oklch {:?}", c);
+    }
+    assert_eq!(Hsv::from(Color::BLUE), Hsv{h: 240.0, s: 1.0, v: 1.0, a: 1.0});
+    assert_eq!(Hsl::from(Color::GREEN), Hsl{h: 120.0, s: 1.0, l: 0.5, a: 1.0});
+    let white = Oklab::from(Color::WHITE);
+    assert!((white.l - 1.0).abs() < 1e-3 && white.a.abs() < 1e-3 && white.b.abs() < 1e-3);
+  }
+
+  #[test] fn lerp() {
+    for space in [ColorSpace::Srgb, ColorSpace::Linear, ColorSpace::Hsv, ColorSpace::Hsl, ColorSpace::Oklab, ColorSpace::Oklch] {
+      assert!(close(Color::RED.lerp(Color::BLUE, 0.0, space), Color::RED), "{:?}", space);
+      assert!(close(Color::RED.lerp(Color::BLUE, 1.0, space), Color::BLUE), "{:?}", space);
+    }
+    assert_eq!(Color::BLACK.lerp(Color::WHITE, 0.5, ColorSpace::Srgb), Color(0x808080ff));
+    // red to blue passes through magenta on the short way around the hue circle
+    assert!(close(Color::RED.lerp(Color::BLUE, 0.5, ColorSpace::Hsv), Color(0xff00ffff)));
+  }
+
+  #[test] fn colormap() {
+    let viridis = Colormap::viridis().with_range(-1.0, 1.0);
+    assert_eq!(viridis.map(-1.0), Color(0x440154ff));
+    assert_eq!(viridis.map(1.0), Color(0xfde725ff));
+    assert_eq!(viridis.map(5.0), Color(0xfde725ff));
+    assert_eq!(Colormap::magma().map(0.0), Color(0x000004ff));
+    assert!(close(Colormap::turbo().map(0.0), Color(0x23171bff)));
+    assert!(close(Colormap::turbo().map(1.0), Color(0x900c00ff)));
+    let coolwarm = Colormap::coolwarm().with_range(-2.0, 2.0);
+    assert_eq!(coolwarm.map(0.0), Color(0xddddddff));
+    assert_eq!(coolwarm.map_all(&[-2.0, 2.0]), vec![Color(0x3b4cc0ff), Color(0xb40426ff)]);
+  }
+
+  #[test] fn constants() {
+    assert_eq!("white".parse(), Ok(Color::WHITE));
+    assert_eq!("black".parse(), Ok(Color::BLACK));
+    assert_eq!("#888".parse(), Ok(Color::GREY));
+    assert_eq!("red".parse(), Ok(Color::RED));
+    assert_eq!("lime".parse(), Ok(Color::GREEN));
+    assert_eq!("blue".parse(), Ok(Color::BLUE));
+    assert_eq!("yellow".parse(), Ok(Color::YELLOW));
+    assert_eq!("cyan".parse(), Ok(Color::CYAN));
+    assert_eq!("magenta".parse(), Ok(Color::MAGENTA));
+  }
+
+  #[test] fn parse() {
+    assert_eq!("#f00".parse(), Ok(Color::RED));
+    assert_eq!("#f008".parse(), Ok(Color(0xff000088)));
+    assert_eq!("#FF0000".parse(), Ok(Color::RED));
+    assert_eq!("#ff000080".parse(), Ok(Color(0xff000080)));
+    assert_eq!("rgb(255, 0, 0)".parse(), Ok(Color::RED));
+    assert_eq!("rgba(0, 0, 255, 0.5)".parse(), Ok(Color(0x0000ff80)));
+    assert_eq!("rgb(100% 0% 0% / 50%)".parse(), Ok(Color(0xff000080)));
+    assert_eq!("hsl(120, 100%, 50%)".parse(), Ok(Color::GREEN));
+    assert_eq!("hsla(240deg, 100%, 50%, 1)".parse(), Ok(Color::BLUE));
+    assert_eq!(" RebeccaPurple ".parse(), Ok(Color(0x663399ff)));
+    assert_eq!("transparent".parse(), Ok(Color(0)));
+    assert!("#ff00".parse::<Color>().is_ok());
+    assert!("#ff00f".parse::<Color>().is_err());
+    assert!("rgb(1, 2)".parse::<Color>().is_err());
+    assert!("notacolor".parse::<Color>().is_err());
+  }
+
+  #[test] fn names() {
+    assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0), "names must be sorted for binary search");
+    for (name, value) in NAMED_COLORS {
+      assert_eq!(Color::named(name), Some(Color(*value)));
+    }
+  }
+
+  #[test] fn display() {
+    assert_eq!(Color::RED.to_string(), "#ff0000ff");
+    for c in [Color::GREY, Color(0x12345678), Color(0)] {
+      assert_eq!(c.to_string().parse(), Ok(c));
+    }
+  }
+
+  #[test] fn clear_color() {
+    let c = Color::from_rgba_f32(0.5, 0.5, 0.5, 1.0);
+    assert!((c.to_wgpu(wgpu::TextureFormat::Bgra8Unorm).r - 0.5).abs() < 1e-2);
+    assert!((c.to_wgpu(wgpu::TextureFormat::Bgra8UnormSrgb).r - 0.2158605).abs() < 1e-3);
+  }
 }
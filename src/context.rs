@@ -1,7 +1,19 @@
 use std::f32::consts::PI;
 use wgpu::util::DeviceExt;
 use crate::Color;
-use crate::mesh::{create_plane_mesh, Vertex};
+use crate::mesh::{create_plane_mesh, Mesh, MeshBuffers, MeshRef};
+use crate::scene::{Hit, Material, Object, ObjectRef, Selection};
+use crate::texture::{CompressedImage, Image, SamplerConfig, Texture, TextureRef};
+use crate::collision::{Ray, TriMesh};
+use crate::input::Input;
+use crate::gizmo::{Delta, Gizmo, GizmoMode, Handle};
+use crate::graph::{Attachment, Graph, Light, NodeRef};
+use crate::debug::DebugDraw;
+use crate::text::{Anchor, Font, FontRef, Label, TextStyle};
+use crate::time::Time;
+use crate::ui::Ui;
+use crate::file::{self, BackgroundData, CameraData, MaterialData, MeshSource, NodeData, ObjectData, SceneFile};
+use crate::pass::{axis_color, Grid, GridPass, LineStyle, LinePass, Lines, Mipmaps, Outline, Overlay, Phong, Picking, Sky, SkyRef, Background, TextPass, UiPass};
 
 pub struct Window {
   pub event_loop: winit::event_loop::EventLoop<()>,
@@ -33,27 +45,80 @@ impl Window {
   }
 }
 
+/// Depth format shared by all targets and pipelines.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Multisampled color and depth textures that are rendered into before resolving to the target.
+struct Attachments {
+  msaa: Option<wgpu::TextureView>,
+  depth: wgpu::TextureView,
+  sample_count: u32,
+}
+
+impl Attachments {
+  fn new(device:&wgpu::Device, format:wgpu::TextureFormat, size:wgpu::Extent3d, sample_count:u32)->Self {
+    let msaa = if sample_count > 1 {
+      let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled Color"), size, mip_level_count: 1, sample_count,
+        dimension: wgpu::TextureDimension::D2, format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT});
+      Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    } else { None };
+    let depth = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Depth"), size, mip_level_count: 1, sample_count,
+      dimension: wgpu::TextureDimension::D2, format: DEPTH_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT});
+    let depth = depth.create_view(&wgpu::TextureViewDescriptor::default());
+    Attachments{msaa, depth, sample_count}
+  }
+
+  /// The view to draw into and the view it resolves to, if any.
+  fn color<'a>(&'a self, view:&'a wgpu::TextureView)->(&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+    match self.msaa {
+      Some(ref msaa) => (msaa, Some(view)),
+      None => (view, None),
+    }
+  }
+}
+
 struct SurfaceContext {
   raw: wgpu::Surface,
   config: wgpu::SurfaceConfiguration,
+  attachments: Attachments,
 }
 
 impl SurfaceContext {
   pub fn aspect_ratio(&self)->f32 {
     self.config.width as f32 / self.config.height as f32
   }
+
+  fn size(&self)->wgpu::Extent3d {
+    wgpu::Extent3d{width: self.config.width, height: self.config.height, depth_or_array_layers: 1}
+  }
+
+  fn info(&self)->TargetInfo {
+    TargetInfo{format: self.config.format, sample_count: self.attachments.sample_count, aspect_ratio: self.aspect_ratio()}
+  }
 }
 
 pub struct Target {
   pub view: wgpu::TextureView,
   pub format: wgpu::TextureFormat,
   pub size: wgpu::Extent3d,
+  texture: wgpu::Texture,
+  attachments: Attachments,
 }
 
 impl Target {
   pub fn aspect(&self) -> f32 {
     self.size.width as f32 / self.size.height as f32
   }
+
+  pub fn info(&self) -> TargetInfo {
+    TargetInfo{format: self.format, sample_count: self.attachments.sample_count, aspect_ratio: self.aspect()}
+  }
+
+  pub fn texture(&self) -> &wgpu::Texture { &self.texture }
 }
 
 /// Parameters of a texture target that affect its pipeline compatibility.
@@ -67,15 +132,45 @@ pub struct TargetInfo {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TargetRef(u8);
 
+/// Sample counts that can be used for a format, 1 and 4 are guaranteed by WebGPU when multisampling is supported at all,
+/// 2 and 8 are only available when the adapter exposes its format specific features.
+fn supported_sample_counts(adapter:&wgpu::Adapter, format:wgpu::TextureFormat)->Vec<u32> {
+  let flags = adapter.get_texture_format_features(format).flags;
+  if !flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE | wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE) {
+    return vec![1];
+  }
+  if adapter.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+    vec![1, 2, 4, 8]
+  } else {
+    vec![1, 4]
+  }
+}
+
+/// The largest supported sample count that does not exceed the requested count.
+fn select_sample_count(supported:&[u32], requested:u32)->u32 {
+  supported.iter().copied().filter(|&c| c <= requested).max().unwrap_or(1)
+}
+
 pub struct Cx {
   #[allow(unused)]
   // window: Window,
   instance: wgpu::Instance,
+  adapter: wgpu::Adapter,
   surface: Option<SurfaceContext>,
   device: wgpu::Device,
   queue: wgpu::Queue,
   targets: Vec<Target>,
-  render_pipeline: wgpu::RenderPipeline,
+  sample_count: u32,
+  phong: Phong,
+  sky: Sky,
+  mipmaps: Mipmaps,
+  picking: Picking,
+  outline: Outline,
+  overlay: Overlay,
+  line_pass: LinePass,
+  grid_pass: GridPass,
+  text_pass: TextPass,
+  ui_pass: UiPass,
 
   camera: Camera,
   global_uniform: Globals,
@@ -86,53 +181,102 @@ pub struct Cx {
   local_buffer: wgpu::Buffer,
   local_bind_group_layout: wgpu::BindGroupLayout,
   local_bind_group: wgpu::BindGroup,
+  /// Distance between the locals of consecutive objects, as required for dynamic offsets.
+  local_stride: wgpu::BufferAddress,
+  local_capacity: usize,
+  /// Per handle uniforms of the gizmo, drawn with [`Overlay`].
+  overlay_buffer: wgpu::Buffer,
+  overlay_bind_group: wgpu::BindGroup,
+
+  texture_layout: wgpu::BindGroupLayout,
+  textures: Vec<Texture>,
+  /// Files the textures were loaded from, for saving materials.
+  texture_paths: Vec<Option<String>>,
+  meshes: Vec<MeshBuffers>,
+  /// Where the geometry of each mesh came from, for saving the scene. Meshes without one are saved inline.
+  sources: Vec<Option<MeshSource>>,
+  /// The vertices of each mesh as they were last uploaded.
+  geometry: Vec<Mesh>,
+  /// The geometry of each mesh on the CPU, for picking.
+  shapes: Vec<TriMesh>,
+  objects: Vec<Object>,
+  selection: Selection,
+  graph: Graph,
+  gizmo: Gizmo,
+  /// The geometry of every gizmo handle, made once.
+  handles: Vec<(GizmoMode, Handle, MeshBuffers)>,
+  lines: Lines,
+  debug: DebugDraw,
+  grid: Option<Grid>,
+  fonts: Vec<Font>,
+  /// Text drawn until the next frame is rendered.
+  labels: Vec<Label>,
+  ui: Ui,
+  input: Input,
+}
 
-  vertex_buffer: wgpu::Buffer,
-  index_buffer: wgpu::Buffer,
-  num_indices: u32,
+/// A texture file that was read, in a form the device can sample.
+enum TextureData {
+  /// Pixels and whether they are sRGB.
+  Image(Image, bool),
+  Compressed(CompressedImage),
+}
 
-  // images: Vec<Image>,
-  // meshes: Vec<Mesh>,
+fn create_locals(device:&wgpu::Device, layout:&wgpu::BindGroupLayout, stride:wgpu::BufferAddress, capacity:usize)->(wgpu::Buffer, wgpu::BindGroup) {
+  let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("Local Buffer"),
+    size: stride * capacity as wgpu::BufferAddress,
+    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false
+  });
+  let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    label: Some("solid locals"), layout,
+    entries: &[wgpu::BindGroupEntry {binding: 0, resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+      buffer: &buffer, offset: 0, size: wgpu::BufferSize::new(std::mem::size_of::<Locals>() as wgpu::BufferAddress)})}]});
+  (buffer, bind_group)
 }
 
 impl Cx {
   pub async fn new(window:&Window) -> Self {
     let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
     let size = window.window.inner_size();
-    let mut surface = SurfaceContext {
-      raw: unsafe { instance.create_surface(&window.window) },
-      config: wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        // using an erroneous format, it is changed before used
-        format: wgpu::TextureFormat::Depth24Plus,
-        width: size.width, height: size.height,
-        present_mode: wgpu::PresentMode::Fifo, // Use best available options..
-      },
-    };
+    let raw = unsafe { instance.create_surface(&window.window) };
 
     let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-        compatible_surface: Some(&surface.raw),
+        compatible_surface: Some(&raw),
         ..Default::default()}).await.unwrap();
 
+    // Format specific features allow sample counts other than 1 and 4, they are not available on the web.
+    // Compressed formats are sampled directly where supported and decoded on the CPU otherwise.
+    let features = adapter.features() & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+      | wgpu::Features::TEXTURE_COMPRESSION_BC | wgpu::Features::TEXTURE_COMPRESSION_ETC2 | wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR);
+
     // WebGL doesn't support all of wgpu's features, so if we're building for the web we'll have to disable some.
     let (device, queue) = adapter
       .request_device(&wgpu::DeviceDescriptor {
-        label: None, features: wgpu::Features::empty(),
+        label: None, features,
         limits: if cfg!(target_arch = "wasm32") {wgpu::Limits::downlevel_webgl2_defaults()}else{wgpu::Limits::default()}}, None).await.unwrap();
 
-    let format = surface.raw.get_supported_formats(&adapter)[0];
-    surface.config.format = format;
-    surface.raw.configure(&device, &surface.config);
+    // Prefer a format that encodes sRGB on write, shaders take care of it otherwise.
+    let formats = raw.get_supported_formats(&adapter);
+    let format = formats.iter().copied().find(|f| f.describe().srgb).unwrap_or(formats[0]);
+    let config = wgpu::SurfaceConfiguration {
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+      format,
+      width: size.width, height: size.height,
+      present_mode: wgpu::PresentMode::Fifo, // Use best available options..
+    };
+    raw.configure(&device, &config);
 
-    println!("{}, {}", surface.config.width, surface.config.height);
+    println!("{}, {}", config.width, config.height);
 
-    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-      label: Some("Phong Shader"),
-      source: wgpu::ShaderSource::Wgsl(include_str!("pass/phong.wgsl").into()),
-    });
+    let sample_count = select_sample_count(&supported_sample_counts(&adapter, format), 4);
+    let extent = wgpu::Extent3d{width: size.width, height: size.height, depth_or_array_layers: 1};
+    let attachments = Attachments::new(&device, format, extent, sample_count);
+    let surface = SurfaceContext{raw, config, attachments};
 
     let aspect_ratio = size.width as f32 / size.height as f32;
-    let camera = Camera::default();
+    let camera = Camera{aspect: aspect_ratio, ..Camera::default()};
     let mut global_uniform = Globals::new();
     global_uniform.update_view_proj(&camera, aspect_ratio);
     let global_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -143,7 +287,7 @@ impl Cx {
       label: Some("global_bind_group_layout"),
       entries: &[wgpu::BindGroupLayoutEntry {
           binding: 0, count: None,
-          visibility: wgpu::ShaderStages::VERTEX,
+          visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
           ty: wgpu::BindingType::Buffer {
             ty: wgpu::BufferBindingType::Uniform,
             has_dynamic_offset: false,
@@ -152,12 +296,6 @@ impl Cx {
       label: Some("local_bind_group"), layout: &global_bind_group_layout,
       entries: &[wgpu::BindGroupEntry {binding: 0, resource: global_buffer.as_entire_binding()}]});
 
-    let local_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-      label: Some("Local Buffer"),
-      size: std::mem::size_of::<Locals>() as wgpu::BufferAddress,
-      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-      mapped_at_creation: false
-    });
     let local_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
       label: Some("solid locals"),
       entries: &[wgpu::BindGroupLayoutEntry {
@@ -171,62 +309,53 @@ impl Cx {
         count: None,
       }],
     });
-    let local_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-      label: Some("solid locals"), layout: &local_bind_group_layout,
-      entries: &[wgpu::BindGroupEntry {binding: 0, resource: local_buffer.as_entire_binding()}]});
-
-    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-      label: Some("Render Pipeline Layout"),
-      bind_group_layouts: &[&global_bind_group_layout, &local_bind_group_layout],
-      push_constant_ranges: &[]});
-
-    let target_info_format = &[Some(wgpu::ColorTargetState {
-      format: surface.config.format,
-      blend: Some(wgpu::BlendState {
-        color: wgpu::BlendComponent::REPLACE,
-        alpha: wgpu::BlendComponent::REPLACE,
-      }),
-      write_mask: wgpu::ColorWrites::ALL})];
-    let primitive = wgpu::PrimitiveState{cull_mode:Some(wgpu::Face::Back),..Default::default()};
-    let multisample = wgpu::MultisampleState{count:1, ..Default::default()};
-    let depth_stencil = None;
+    let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+    let local_stride = (std::mem::size_of::<Locals>() as wgpu::BufferAddress).div_ceil(alignment) * alignment;
+    let local_capacity = 16;
+    let (local_buffer, local_bind_group) = create_locals(&device, &local_bind_group_layout, local_stride, local_capacity);
+    let texture_layout = Texture::bind_group_layout(&device);
 
     // render pipeline for phong...
-    let vertex_buffers = &[Vertex::desc()];
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-      label: Some("phong"),
-      layout: Some(&render_pipeline_layout),
-      vertex: wgpu::VertexState {buffers: vertex_buffers, module: &shader_module, entry_point: "vs_main"},
-      primitive,
-      depth_stencil: depth_stencil.clone(),
-      multisample,
-      fragment: Some(wgpu::FragmentState {
-        targets: target_info_format,
-        module: &shader_module, entry_point: "fs_main",
-      }),
-      multiview: None,
-    });
+    let phong = Phong::new(&device, &global_bind_group_layout, &local_bind_group_layout, &texture_layout, &surface.info());
+    let sky = Sky::new(&device, &surface.info());
+    let mipmaps = Mipmaps::new(&device);
+    let picking = Picking::new(&device, &global_bind_group_layout, &local_bind_group_layout);
+    let outline = Outline::new(&device, &global_bind_group_layout, &local_bind_group_layout, &surface.info());
+    let overlay = Overlay::new(&device, &global_bind_group_layout, &local_bind_group_layout, &surface.info());
+    let line_pass = LinePass::new(&device, &global_bind_group_layout, &surface.info());
+    let grid_pass = GridPass::new(&device, &global_bind_group_layout, &surface.info());
+    let text_pass = TextPass::new(&device, &global_bind_group_layout, &surface.info());
+    let ui_pass = UiPass::new(&device, &global_bind_group_layout, &texture_layout, &surface.info());
+    let (overlay_buffer, overlay_bind_group) = create_locals(&device, &local_bind_group_layout, local_stride, 8);
+    let handles = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale].iter()
+      .flat_map(|&mode| mode.handles().iter().map(move |&handle| (mode, handle)))
+      .map(|(mode, handle)| (mode, handle, handle.mesh(mode).upload(&device))).collect();
 
-    let mesh = &create_plane_mesh(g3::E3);
-
-    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-      label: Some("Vertex Buffer"),
-      contents: bytemuck::cast_slice(&mesh.vertices),
-      usage: wgpu::BufferUsages::VERTEX});
-    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-      label: Some("Index Buffer"),
-      contents: bytemuck::cast_slice(&mesh.indices),
-      usage: wgpu::BufferUsages::INDEX});
-    let num_indices = mesh.indices.len() as u32;
+    let mesh = create_plane_mesh(g3::E3);
+    let meshes = vec![mesh.upload(&device)];
+    let shapes = vec![TriMesh::from_mesh(&mesh)];
+    let sources = vec![None];
+    let objects = vec![Object::new(MeshRef(0), Color::GREEN.into())];
 
     Self {
       // window,
       instance,
+      adapter,
       surface: Some(surface),
       device,
       queue,
       targets: Vec::new(),
-      render_pipeline,
+      sample_count,
+      phong,
+      sky,
+      mipmaps,
+      picking,
+      outline,
+      overlay,
+      line_pass,
+      grid_pass,
+      text_pass,
+      ui_pass,
 
       camera,
       global_uniform,
@@ -236,16 +365,480 @@ impl Cx {
       local_buffer,
       local_bind_group_layout,
       local_bind_group,
+      local_stride,
+      local_capacity,
+      overlay_buffer,
+      overlay_bind_group,
 
-      vertex_buffer,
-      index_buffer,
-      num_indices,
+      texture_layout,
+      textures: Vec::new(),
+      texture_paths: Vec::new(),
+      meshes,
+      sources,
+      geometry: vec![mesh],
+      shapes,
+      objects,
+      selection: Selection::default(),
+      graph: Graph::new(),
+      gizmo: Gizmo::default(),
+      handles,
+      lines: Lines::new(),
+      debug: DebugDraw::default(),
+      grid: None,
+      fonts: Vec::new(),
+      labels: Vec::new(),
+      ui: Ui::new(),
+      input: Input::new(size.width, size.height),
     }
   }
 
   pub fn add_plane(&self) {
   }
 
+  /// Upload a mesh so objects can be drawn with it. Saved scenes hold a copy of it, unless another source is set.
+  pub fn add_mesh(&mut self, mesh:&Mesh)->MeshRef {
+    let handle = MeshRef(u16::try_from(self.meshes.len()).expect("more meshes than a MeshRef can address"));
+    self.meshes.push(mesh.upload(&self.device));
+    self.shapes.push(TriMesh::from_mesh(mesh));
+    self.sources.push(None);
+    self.geometry.push(mesh.clone());
+    handle
+  }
+
+  /// Save a mesh in scenes by where it comes from instead of by its vertices. Relative file paths are read from
+  /// the working directory.
+  pub fn set_mesh_source(&mut self, mesh:MeshRef, source:MeshSource) {
+    let source = match source {
+      MeshSource::File(path) => MeshSource::File(file::absolute_path(std::path::Path::new(""), &path)),
+      source => source,
+    };
+    self.sources[mesh.0 as usize] = Some(source);
+  }
+
+  /// Upload the vertices of a mesh that changed shape, like one moved by [`crate::physics::World::write_mesh`].
+  /// When the number of vertices or the triangles stay the same the buffers are written in place, otherwise they
+  /// are made anew.
+  pub fn update_mesh(&mut self, mesh:MeshRef, data:&Mesh) {
+    let i = mesh.0 as usize;
+    let old = &self.geometry[i];
+    if old.vertices.len() == data.vertices.len() && old.indices == data.indices {
+      self.queue.write_buffer(&self.meshes[i].vertex_buffer, 0, bytemuck::cast_slice(&data.vertex_data()));
+      self.shapes[i].update(data);
+    } else {
+      self.meshes[i] = data.upload(&self.device);
+      self.shapes[i] = TriMesh::from_mesh(data);
+    }
+    self.sources[i] = None;
+    self.geometry[i] = data.clone();
+  }
+
+  pub fn add_object(&mut self, object:Object)->ObjectRef {
+    let handle = ObjectRef(u16::try_from(self.objects.len()).expect("more objects than an ObjectRef can address"));
+    self.objects.push(object);
+    handle
+  }
+
+  pub fn object(&self, object:ObjectRef)->&Object { &self.objects[object.0 as usize] }
+
+  pub fn object_mut(&mut self, object:ObjectRef)->&mut Object { &mut self.objects[object.0 as usize] }
+
+  pub fn objects(&self)->&[Object] { &self.objects }
+
+  /// Upload an image for use as a material's albedo, 8 bit images are treated as sRGB.
+  /// The full mip chain is generated on the GPU.
+  pub fn add_texture(&mut self, image:&Image, sampler:&SamplerConfig)->TextureRef {
+    self.push_texture(image, true, sampler)
+  }
+
+  fn push_texture(&mut self, image:&Image, srgb:bool, sampler:&SamplerConfig)->TextureRef {
+    let handle = self.next_texture();
+    let format = image.format(srgb);
+    let levels = if Mipmaps::supports(format) { Mipmaps::level_count(image.width, image.height) } else { 1 };
+    let texture = Texture::upload(&self.device, &self.queue, &self.texture_layout, image, srgb, sampler, levels);
+    if levels > 1 {
+      let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{label: Some("Mipmap Encoder")});
+      self.mipmaps.generate(&self.device, &mut encoder, &texture.texture, format, levels);
+      self.queue.submit(std::iter::once(encoder.finish()));
+    }
+    self.textures.push(texture);
+    self.texture_paths.push(None);
+    handle
+  }
+
+  fn next_texture(&self)->TextureRef {
+    TextureRef(u16::try_from(self.textures.len()).expect("more textures than a TextureRef can address"))
+  }
+
+  /// Upload a block compressed image with the levels it comes with. When the device can't sample
+  /// its format the first level is decoded on the CPU and the rest regenerated.
+  pub fn add_compressed_texture(&mut self, image:&CompressedImage, sampler:&SamplerConfig)->anyhow::Result<TextureRef> {
+    if self.device.features().contains(image.required_features()) { return Ok(self.push_compressed(image, sampler)) }
+    Ok(self.push_texture(&image.decompress()?, image.is_srgb(), sampler))
+  }
+
+  fn push_compressed(&mut self, image:&CompressedImage, sampler:&SamplerConfig)->TextureRef {
+    let handle = self.next_texture();
+    self.textures.push(Texture::upload_compressed(&self.device, &self.queue, &self.texture_layout, image, sampler));
+    self.texture_paths.push(None);
+    handle
+  }
+
+  /// Read a texture file and decode it if the device can't sample its format, without uploading it yet.
+  fn read_texture(&self, path:&std::path::Path)->anyhow::Result<TextureData> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    Ok(match extension.as_deref() {
+      Some("ktx2" | "dds") => {
+        let image = CompressedImage::load(path)?;
+        if self.device.features().contains(image.required_features()) { TextureData::Compressed(image) }
+        else { TextureData::Image(image.decompress()?, image.is_srgb()) }
+      }
+      _ => TextureData::Image(Image::load(path)?, true),
+    })
+  }
+
+  fn upload_texture(&mut self, data:&TextureData, sampler:&SamplerConfig)->TextureRef {
+    match data {
+      TextureData::Image(image, srgb) => self.push_texture(image, *srgb, sampler),
+      TextureData::Compressed(image) => self.push_compressed(image, sampler),
+    }
+  }
+
+  /// Load a PNG, JPEG, HDR, KTX2 or DDS file as a texture.
+  pub fn load_texture<P:AsRef<std::path::Path>>(&mut self, path:P, sampler:&SamplerConfig)->anyhow::Result<TextureRef> {
+    let path = path.as_ref();
+    let data = self.read_texture(path)?;
+    let texture = self.upload_texture(&data, sampler);
+    self.texture_paths[texture.0 as usize] = Some(file::absolute_path(std::path::Path::new(""), &path.to_string_lossy()));
+    Ok(texture)
+  }
+
+  pub fn texture(&self, texture:TextureRef)->&Texture { &self.textures[texture.0 as usize] }
+
+  /// Objects that are outlined and the one under the cursor.
+  pub fn selection(&self)->&Selection { &self.selection }
+
+  pub fn selection_mut(&mut self)->&mut Selection { &mut self.selection }
+
+  /// Hover the object under the cursor, or nothing when the cursor is outside the window.
+  pub fn update_hover(&mut self) {
+    if self.ui.wants_pointer() { self.selection.hovered = None; return }
+    self.selection.hovered = self.input.cursor_ndc().and_then(|ndc| self.pick_ray(ndc)).map(|hit| hit.object);
+  }
+
+  /// Nodes that objects, lights and the camera can be attached to.
+  pub fn graph(&self)->&Graph { &self.graph }
+
+  pub fn graph_mut(&mut self)->&mut Graph { &mut self.graph }
+
+  /// Move the objects and the camera attached to nodes to where their nodes are, done before every render.
+  pub fn update_graph(&mut self) {
+    for node in self.graph.iter().collect::<Vec<_>>() {
+      let (transform, scale) = self.graph.world(node);
+      for attachment in &self.graph.node(node).attachments {
+        if let Attachment::Object(object) = attachment {
+          if let Some(object) = self.objects.get_mut(object.0 as usize) {
+            object.transform = transform;
+            object.scale = glam::Vec3::splat(scale);
+          }
+        }
+      }
+    }
+    if let Some(node) = self.camera.node.filter(|&n| self.graph.get(n).is_some()) {
+      let (transform, _) = self.graph.world(node);
+      self.camera.follow(&transform);
+    }
+  }
+
+  /// Lights attached to nodes and where they are in the world, they shade the meshes.
+  pub fn lights(&self)->Vec<(g3::Point, Light)> {
+    let mut lights = vec![];
+    for node in self.graph.iter() {
+      let (transform, _) = self.graph.current_world(node);
+      for attachment in &self.graph.node(node).attachments {
+        if let Attachment::Light(light) = attachment { lights.push((transform(g3::point(0.0, 0.0, 0.0)), *light)) }
+      }
+    }
+    lights
+  }
+
+  pub fn gizmo(&self)->&Gizmo { &self.gizmo }
+
+  pub fn gizmo_mut(&mut self)->&mut Gizmo { &mut self.gizmo }
+
+  /// Center and axis length of the gizmo, which sits at the origin of the last selected object.
+  fn gizmo_frame(&self)->Option<(glam::Vec3, f32)> {
+    let object = self.objects.get(self.selection.iter().last()?.0 as usize)?;
+    let center:[f32;3] = (object.transform)(g3::point(0.0, 0.0, 0.0)).into();
+    let center = glam::Vec3::from(center);
+    Some((center, self.gizmo.scale(center, self.camera.eye.into())))
+  }
+
+  /// Grab the gizmo handle under the cursor, returns false if there is none.
+  pub fn begin_gizmo(&mut self)->bool {
+    let (center, scale) = match self.gizmo_frame() { Some(frame) => frame, None => return false };
+    let ray = match self.input.cursor_ndc() { Some(ndc) => self.camera.ray_from_eye(ndc), None => return false };
+    self.gizmo.begin(center, scale, &ray)
+  }
+
+  /// Drag the grabbed handle to the cursor and apply the change to the selection, or hover the handle under it.
+  pub fn update_gizmo(&mut self) {
+    let ray = match self.input.cursor_ndc() { Some(ndc) => self.camera.ray_from_eye(ndc), None => return };
+    if self.gizmo.active().is_some() {
+      if let Some(delta) = self.gizmo.drag(&ray) { self.apply(delta) }
+    } else if let Some((center, scale)) = self.gizmo_frame() {
+      self.gizmo.hover(center, scale, &ray);
+    }
+  }
+
+  pub fn end_gizmo(&mut self) { self.gizmo.end() }
+
+  /// Move, turn or scale every selected object. Objects attached to nodes move their node, once even when several
+  /// of its objects or the objects of nodes above it are selected.
+  pub fn apply(&mut self, delta:Delta) {
+    let (mut nodes, mut loose) = (vec![], vec![]);
+    for object in self.selection.iter() {
+      match self.graph.node_of(object) {
+        Some(node) => nodes.push(node),
+        None => loose.push(object),
+      }
+    }
+    for node in self.graph.topmost(&nodes) {
+      match delta {
+        Delta::Motor(m) => { let (world, _) = self.graph.world(node); self.graph.set_world(node, m * world) }
+        Delta::Scale(s) => {
+          let scale = self.graph.node(node).scale();
+          self.graph.set_scale(node, scale * s);
+        }
+      }
+    }
+    for object in loose {
+      let object = match self.objects.get_mut(object.0 as usize) { Some(object) => object, None => continue };
+      match delta {
+        Delta::Motor(m) => object.transform = m * object.transform,
+        Delta::Scale(s) => object.scale *= s,
+      }
+    }
+    self.update_graph();
+  }
+
+  /// Lines and points drawn with the scene until they are cleared.
+  pub fn lines(&self)->&Lines { &self.lines }
+
+  pub fn lines_mut(&mut self)->&mut Lines { &mut self.lines }
+
+  /// The reference grid, if it is shown.
+  pub fn grid(&self)->Option<&Grid> { self.grid.as_ref() }
+
+  pub fn grid_mut(&mut self)->Option<&mut Grid> { self.grid.as_mut() }
+
+  /// Show a reference grid, or hide it with `None`.
+  pub fn set_grid(&mut self, grid:Option<Grid>) { self.grid = grid }
+
+  /// The normal through the origin of the grid, colored like the world axis it follows.
+  fn grid_axis(&self)->Lines {
+    let mut lines = Lines::new();
+    if let Some(grid) = self.grid.filter(|g| g.axes) {
+      let (origin, _, _, normal) = grid.frame();
+      let style = LineStyle{color: axis_color(normal), width: 1.5, ..LineStyle::default()};
+      lines.line(origin - normal * grid.fade, origin + normal * grid.fade, &style);
+    }
+    lines
+  }
+
+  /// Settings of the immediate mode drawing, like the volume lines are clipped to.
+  pub fn debug(&self)->&DebugDraw { &self.debug }
+
+  pub fn debug_mut(&mut self)->&mut DebugDraw { &mut self.debug }
+
+  /// Draw a point until the next frame is rendered.
+  pub fn draw_point(&mut self, p:g3::Point, color:Color) { self.debug.point(p, color) }
+
+  /// Draw the part of a line inside [`DebugDraw::volume`] until the next frame is rendered.
+  pub fn draw_line(&mut self, l:g3::Line, color:Color) {
+    self.debug.view_from(&self.camera);
+    self.debug.line(l, color)
+  }
+
+  /// Draw a square of half size `extent` on a plane until the next frame is rendered, see [`DebugDraw::plane`].
+  pub fn draw_plane(&mut self, p:g3::Plane, color:Color, extent:f32) {
+    self.debug.view_from(&self.camera);
+    self.debug.plane(p, color, extent)
+  }
+
+  /// Draw the frame a motor moves the origin and axes to until the next frame is rendered.
+  pub fn draw_motor(&mut self, m:&g3::Motor) { self.debug.motor(m) }
+
+  /// Add a TrueType or OpenType font for [`TextStyle::font`], the first one added is the default.
+  pub fn add_font(&mut self, bytes:&[u8])->anyhow::Result<FontRef> {
+    let handle = self.next_font();
+    self.fonts.push(Font::from_bytes(bytes)?);
+    Ok(handle)
+  }
+
+  pub fn load_font<P:AsRef<std::path::Path>>(&mut self, path:P)->anyhow::Result<FontRef> {
+    let handle = self.next_font();
+    self.fonts.push(Font::load(path)?);
+    Ok(handle)
+  }
+
+  fn next_font(&self)->FontRef {
+    FontRef(u16::try_from(self.fonts.len()).expect("more fonts than a FontRef can address"))
+  }
+
+  /// Draw text at pixels from the top left of the screen until the next frame is rendered.
+  pub fn draw_text(&mut self, text:&str, position:[f32;2], style:&TextStyle) {
+    self.labels.push(Label{text: text.to_string(), anchor: Anchor::Screen(position), style: *style});
+  }
+
+  /// Draw text standing on a point in the world, facing the camera, until the next frame is rendered.
+  pub fn draw_label(&mut self, text:&str, p:g3::Point, style:&TextStyle) {
+    if p.w().abs() < 1e-6 { return }
+    self.labels.push(Label{text: text.to_string(), anchor: Anchor::World(<[f32;3]>::from(p).into()), style: *style});
+  }
+
+  pub fn ui(&self)->&Ui { &self.ui }
+
+  pub fn ui_mut(&mut self)->&mut Ui { &mut self.ui }
+
+  /// Run a frame of the debug UI with the built-in panels and whatever `f` adds, drawn over the next [`Cx::render`].
+  pub fn run_ui(&mut self, time:&mut Time, f:impl FnOnce(&mut Cx, &egui::Context)) {
+    if !self.ui.visible { self.ui.output = None; return }
+    let input = self.ui.begin(self.input.size(), time);
+    // The context is shared, so the panels can borrow the rest of the Cx.
+    let ctx = self.ui.context().clone();
+    let output = ctx.run(input, |ctx| {
+      crate::ui::panels(self, ctx, time);
+      f(self, ctx);
+    });
+    self.ui.output = Some((ctx.tessellate(output.shapes), output.textures_delta));
+  }
+
+  /// Keyboard, mouse and touch state, updated by the event loop of [`crate::run_app`].
+  pub fn input(&self)->&Input { &self.input }
+
+  pub fn input_mut(&mut self)->&mut Input { &mut self.input }
+
+  /// The line through a point on the screen in normalized device coordinates, see [`Input::cursor_ndc`].
+  pub fn ray(&self, ndc:[f32;2])->g3::Line {
+    self.camera.ray(ndc)
+  }
+
+  /// The nearest object under a point on the screen, found by casting a ray against the meshes on the CPU.
+  pub fn pick_ray(&self, ndc:[f32;2])->Option<Hit> {
+    crate::scene::pick(&self.objects, &self.shapes, &self.camera.ray_from_eye(ndc))
+  }
+
+  pub fn camera(&self)->&Camera { &self.camera }
+
+  pub fn camera_mut(&mut self)->&mut Camera { &mut self.camera }
+
+  /// The meshes, objects, nodes and camera of the scene, as they would be saved.
+  /// Textures that weren't loaded from files and sky backgrounds are left out. Paths are absolute until the file is saved.
+  pub fn to_scene_file(&self)->SceneFile {
+    let material = |m:&Material| MaterialData{color: m.color, albedo: m.albedo.and_then(|t| self.texture_paths[t.0 as usize].clone())};
+    let objects = self.objects.iter().map(|o| ObjectData{
+      mesh: o.mesh.0 as usize, material: material(&o.material), transform: file::motor_data(&o.transform), scale: o.scale.into()}).collect();
+    let order:Vec<NodeRef> = self.graph.iter().collect();
+    let index = |n:NodeRef| order.iter().position(|&m| m == n);
+    let nodes = order.iter().map(|&n| {
+      let node = self.graph.node(n);
+      let objects = node.attachments.iter().filter_map(|a| match a { Attachment::Object(o) => Some(o.0 as usize), _ => None }).collect();
+      let lights = node.attachments.iter().filter_map(|a| match a { Attachment::Light(l) => Some(*l), _ => None }).collect();
+      NodeData{name: node.name.clone(), parent: node.parent().and_then(index), transform: file::motor_data(&node.transform()), scale: node.scale(), objects, lights}
+    }).collect();
+    let background = match self.camera.background {
+      Background::Color(color) => BackgroundData::Color(color),
+      Background::Gradient{top, bottom} => BackgroundData::Gradient{top, bottom},
+      Background::Sky(_) => { log::warn!("sky backgrounds aren't saved"); BackgroundData::default() }
+    };
+    let c = &self.camera;
+    let camera = CameraData{eye: c.eye, target: c.target, up: c.up, fov_y: c.fov_y, node: c.node.and_then(index), background};
+    let meshes = self.sources.iter().zip(&self.geometry)
+      .map(|(source, mesh)| source.clone().unwrap_or_else(|| MeshSource::Inline(mesh.into()))).collect();
+    SceneFile{version: file::VERSION, meshes, objects, nodes, camera}
+  }
+
+  /// Replace the scene, its meshes, textures, objects, nodes and camera, with those of a scene file.
+  /// Files the scene refers to by relative paths are looked up from `base`. When one can't be read nothing is replaced.
+  pub fn load_scene_file(&mut self, scene:&SceneFile, base:&std::path::Path)->anyhow::Result<()> {
+    scene.validate()?;
+    let mut scene = scene.clone();
+    scene.resolve_paths(base);
+    let scene = &scene;
+    let meshes = scene.meshes.iter().map(|source| source.load(base)).collect::<anyhow::Result<Vec<_>>>()?;
+    let mut albedos:Vec<(&String, TextureData)> = vec![];
+    for path in scene.objects.iter().filter_map(|o| o.material.albedo.as_ref()) {
+      if !albedos.iter().any(|(p, _)| *p == path) { albedos.push((path, self.read_texture(&base.join(path))?)) }
+    }
+    let mut graph = Graph::new();
+    let mut nodes = vec![];
+    for n in &scene.nodes {
+      let node = graph.add(&n.name, n.parent.map(|p| nodes[p]));
+      graph.set_transform(node, file::motor_from_data(&n.transform));
+      graph.set_scale(node, n.scale);
+      for &o in &n.objects {
+        let object = u16::try_from(o).map_err(|_| anyhow::anyhow!("object {} is more than an ObjectRef can address", o))?;
+        graph.attach(node, Attachment::Object(ObjectRef(object)));
+      }
+      for &l in &n.lights { graph.attach(node, Attachment::Light(l)) }
+      nodes.push(node);
+    }
+
+    // Everything is read, the old scene goes.
+    self.meshes.clear();
+    self.sources.clear();
+    self.geometry.clear();
+    self.shapes.clear();
+    self.textures.clear();
+    self.texture_paths.clear();
+    let meshes:Vec<MeshRef> = meshes.iter().zip(&scene.meshes).map(|(mesh, source)| {
+      let handle = self.add_mesh(mesh);
+      self.set_mesh_source(handle, source.clone());
+      handle
+    }).collect();
+    let textures:Vec<(&String, TextureRef)> = albedos.iter().map(|(path, data)| {
+      let texture = self.upload_texture(data, &SamplerConfig::default());
+      self.texture_paths[texture.0 as usize] = Some(path.to_string());
+      (*path, texture)
+    }).collect();
+    self.objects = scene.objects.iter().map(|o| {
+      let albedo = o.material.albedo.as_ref().and_then(|path| textures.iter().find(|(p, _)| *p == path)).map(|&(_, texture)| texture);
+      Object{mesh: meshes[o.mesh], material: Material{color: o.material.color, albedo}, transform: file::motor_from_data(&o.transform), scale: o.scale.into()}
+    }).collect();
+    let c = &scene.camera;
+    let background = match c.background {
+      BackgroundData::Color(color) => Background::Color(color),
+      BackgroundData::Gradient{top, bottom} => Background::Gradient{top, bottom},
+    };
+    self.camera = Camera{eye: c.eye, target: c.target, up: c.up, fov_y: c.fov_y, node: c.node.map(|n| nodes[n]), background, aspect: self.camera.aspect, ..Camera::default()};
+    self.graph = graph;
+    self.selection.clear();
+    self.selection.hovered = None;
+    Ok(())
+  }
+
+  /// Read a scene file, see [`Cx::load_scene_file`].
+  pub fn load_scene<P:AsRef<std::path::Path>>(&mut self, path:P)->anyhow::Result<()> {
+    let path = path.as_ref();
+    let scene = SceneFile::load(path)?;
+    self.load_scene_file(&scene, path.parent().unwrap_or_else(|| std::path::Path::new("")))
+  }
+
+  /// Write the scene in the current version, mesh and texture paths are made relative to the file when they can be.
+  pub fn save_scene<P:AsRef<std::path::Path>>(&self, path:P)->anyhow::Result<()> {
+    self.to_scene_file().save(path)
+  }
+
+  /// Upload a cubemap for [`Background::Sky`], six square RGBA8 faces in the order +X, -X, +Y, -Y, +Z, -Z.
+  pub fn add_skybox(&mut self, size:u32, faces:[&[u8];6])->anyhow::Result<SkyRef> {
+    self.sky.add_cubemap(&self.device, &self.queue, size, faces)
+  }
+
+  /// Upload an equirectangular HDR environment for [`Background::Sky`], given as linear RGBA floats.
+  pub fn add_environment(&mut self, width:u32, height:u32, pixels:&[f32])->anyhow::Result<SkyRef> {
+    self.sky.add_equirect(&self.device, &self.queue, width, height, pixels)
+  }
+
   pub fn resize(&mut self, width: u32, height: u32) {
     let surface = match self.surface {
       Some(ref mut suf) => suf,
@@ -257,8 +850,51 @@ impl Cx {
     surface.config.width = width;
     surface.config.height = height;
     surface.raw.configure(&self.device, &surface.config);
+    surface.attachments = Attachments::new(&self.device, surface.config.format, surface.size(), self.sample_count);
+    self.camera.aspect = surface.aspect_ratio();
+  }
+
+  /// Sample counts supported for the format of the screen.
+  pub fn supported_sample_counts(&self)->Vec<u32> {
+    let format = self.surface.as_ref().map_or(wgpu::TextureFormat::Rgba8UnormSrgb, |s| s.config.format);
+    supported_sample_counts(&self.adapter, format)
+  }
+
+  pub fn sample_count(&self)->u32 { self.sample_count }
+
+  /// Request a number of MSAA samples, the largest supported count not exceeding `count` is used.
+  /// Attachments of the screen and all targets are recreated, pipelines follow on the next draw.
+  pub fn set_sample_count(&mut self, count:u32) {
+    let count = select_sample_count(&self.supported_sample_counts(), count);
+    if count == self.sample_count { return }
+    self.sample_count = count;
+    if let Some(ref mut surface) = self.surface {
+      surface.attachments = Attachments::new(&self.device, surface.config.format, surface.size(), count);
+    }
+    for target in self.targets.iter_mut() {
+      let count = select_sample_count(&supported_sample_counts(&self.adapter, target.format), count);
+      target.attachments = Attachments::new(&self.device, target.format, target.size, count);
+    }
   }
 
+  /// Create an offscreen target that can be rendered to with [`Cx::render_target`].
+  pub fn add_target(&mut self, width:u32, height:u32, format:wgpu::TextureFormat)->TargetRef {
+    let size = wgpu::Extent3d{width, height, depth_or_array_layers: 1};
+    let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Target"), size, mip_level_count: 1, sample_count: 1,
+      dimension: wgpu::TextureDimension::D2, format,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC});
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sample_count = select_sample_count(&supported_sample_counts(&self.adapter, format), self.sample_count);
+    let attachments = Attachments::new(&self.device, format, size, sample_count);
+    let handle = TargetRef(u8::try_from(self.targets.len()).expect("more targets than a TargetRef can address"));
+    self.targets.push(Target{view, format, size, texture, attachments});
+    handle
+  }
+
+  pub fn target(&self, target:TargetRef)->&Target {
+    &self.targets[target.0 as usize]
+  }
 
   //     fn inverse(&self) -> Self {
   //         let scale = 1.0 / self.scale;
@@ -273,40 +909,212 @@ impl Cx {
 
   pub fn render(&mut self) {
     // self.queue.write_buffer(&self.uniforms_buffer, 0, self.uniforms.as_bytes());
-    let surface = self.surface.as_mut().expect("No screen is configured!");
+    let info = self.surface.as_ref().expect("No screen is configured!").info();
+    self.update_graph();
+    self.phong.prepare(&self.device, &info);
+    self.sky.prepare(&self.device, &info);
+    let size = self.surface.as_ref().unwrap().size();
+    self.outline.prepare(&self.device, &self.queue, &info, size, &self.selection);
+    self.overlay.prepare(&self.device, &info);
+    self.line_pass.prepare(&self.device, &self.queue, &info, size, &[&self.lines, &self.debug.lines, &self.grid_axis()]);
+    if let Some(grid) = &self.grid { self.grid_pass.prepare(&self.device, &self.queue, &info, grid, &self.camera) }
+    self.text_pass.prepare(&self.device, &self.queue, &info, size, &self.fonts, &self.labels);
+    self.ui_pass.prepare(&self.device, &self.queue, &info, size, &self.ui);
+    self.reserve_locals();
+    let surface = self.surface.as_ref().unwrap();
     let frame = surface.raw.get_current_texture().unwrap();
     let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
     let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{label: Some("Render Encoder")});
+    self.draw(&mut encoder, &surface.attachments, &view, &info, size);
+    // The UI goes over everything, on the screen only.
+    if !self.ui_pass.is_empty() { self.ui_pass.draw(&mut encoder, &view, &self.global_bind_group, &self.textures) }
+    self.queue.submit(Some(encoder.finish()));
+    frame.present();
+    self.debug.clear();
+    self.labels.clear();
+  }
+
+  /// Render the scene into an offscreen target, multisampled targets are resolved into [`Target::view`].
+  pub fn render_target(&mut self, target:TargetRef) {
+    let info = self.target(target).info();
+    self.update_graph();
+    self.phong.prepare(&self.device, &info);
+    self.sky.prepare(&self.device, &info);
+    let size = self.target(target).size;
+    self.outline.prepare(&self.device, &self.queue, &info, size, &self.selection);
+    self.overlay.prepare(&self.device, &info);
+    self.line_pass.prepare(&self.device, &self.queue, &info, size, &[&self.lines, &self.debug.lines, &self.grid_axis()]);
+    if let Some(grid) = &self.grid { self.grid_pass.prepare(&self.device, &self.queue, &info, grid, &self.camera) }
+    self.text_pass.prepare(&self.device, &self.queue, &info, size, &self.fonts, &self.labels);
+    self.reserve_locals();
+    let target = &self.targets[target.0 as usize];
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{label: Some("Target Encoder")});
+    self.draw(&mut encoder, &target.attachments, &target.view, &info, size);
+    self.queue.submit(Some(encoder.finish()));
+  }
+
+  /// The object drawn at a pixel of the screen, counted from the top left, and the point of it seen there.
+  /// Only that pixel is rendered, with object ids instead of colors, and read back from the GPU. Unlike
+  /// [`Cx::pick_ray`] it sees exactly what is drawn, at the cost of waiting for the GPU.
+  pub async fn pick(&mut self, x:u32, y:u32)->Option<(ObjectRef, g3::Point)> {
+    let info = self.surface.as_ref()?.info();
+    let size = self.surface.as_ref()?.size();
+    if x >= size.width || y >= size.height { return None }
+    self.update_graph();
+    self.reserve_locals();
+    self.write_uniforms(&info);
+    let view_proj = self.camera.projection_matrix(info.aspect_ratio) * self.camera.view_matrix();
+    // The next render writes the globals again.
+    let globals = Globals{view_proj: Picking::view_proj(view_proj, x, y, size.width, size.height).to_cols_array_2d(), encode_srgb: 0, _padding: [0;3]};
+    self.queue.write_buffer(&self.global_buffer, 0, bytemuck::bytes_of(&globals));
+    let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor{label: Some("Picking Encoder")});
     {
-      let aspect = surface.aspect_ratio();
-      let m_proj = self.camera.projection_matrix(aspect);
-      let m_view = self.camera.view_matrix();
-      // let m_view_inv = nodes[camera.node].inverse_matrix();
-      // let m_final = m_proj * glam::Mat4::from(m_view_inv);
-      // let globals = Globals { view_proj: m_final.to_cols_array_2d() };
-      let m = m_proj * m_view;
-      let globals = Globals{view_proj: m.to_cols_array_2d()};
-      self.queue.write_buffer(&self.global_buffer, 0, bytemuck::bytes_of(&globals));
+      let mut pass = self.picking.pass(&mut encoder);
+      pass.set_bind_group(0, &self.global_bind_group, &[]);
+      for (i, object) in self.objects.iter().enumerate() {
+        let offset = (i as wgpu::BufferAddress * self.local_stride) as wgpu::DynamicOffset;
+        pass.set_bind_group(1, &self.local_bind_group, &[offset]);
+        let mesh = &self.meshes[object.mesh.0 as usize];
+        pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+      }
+    }
+    self.picking.copy(&mut encoder);
+    self.queue.submit(Some(encoder.finish()));
+    let (id, depth) = self.picking.read(&self.device).await?;
+    let index = (id as usize).checked_sub(1).filter(|&i| i < self.objects.len())?;
+    // Back from the pixel's center and depth to the world.
+    let ndc = glam::Vec3::new(2.0 * (x as f32 + 0.5) / size.width as f32 - 1.0, 1.0 - 2.0 * (y as f32 + 0.5) / size.height as f32, depth);
+    let p = view_proj.inverse().project_point3(ndc);
+    Some((ObjectRef(index as u16), g3::point(p.x, p.y, p.z)))
+  }
+
+  /// Grow the buffer of per object uniforms so every object has a slot.
+  fn reserve_locals(&mut self) {
+    if self.objects.len() <= self.local_capacity { return }
+    self.local_capacity = self.objects.len().next_power_of_two();
+    let (buffer, bind_group) = create_locals(&self.device, &self.local_bind_group_layout, self.local_stride, self.local_capacity);
+    self.local_buffer = buffer;
+    self.local_bind_group = bind_group;
+  }
+
+  /// Upload the camera and the per object uniforms for drawing into a target.
+  fn write_uniforms(&self, info:&TargetInfo) {
+    let m_proj = self.camera.projection_matrix(info.aspect_ratio);
+    let m_view = self.camera.view_matrix();
+    // let m_view_inv = nodes[camera.node].inverse_matrix();
+    // let m_final = m_proj * glam::Mat4::from(m_view_inv);
+    // let globals = Globals { view_proj: m_final.to_cols_array_2d() };
+    let m = m_proj * m_view;
+    let globals = Globals{view_proj: m.to_cols_array_2d(), encode_srgb: !info.format.describe().srgb as u32, _padding: [0;3]};
+    self.queue.write_buffer(&self.global_buffer, 0, bytemuck::bytes_of(&globals));
+    self.sky.update(&self.queue, &self.camera.background, m, info);
+    self.phong.set_lights(&self.queue, self.camera.eye(), &self.lights());
+
+    let mut locals = vec![0u8; self.local_stride as usize * self.objects.len()];
+    for (i, object) in self.objects.iter().enumerate() {
+      // Ids start at 1 so that 0 is left for the background when picking.
+      let local = Locals::new(object.matrix(), self.selection.tint(ObjectRef(i as u16), object.material.color.to_linear()), i as u32 + 1);
+      let offset = i * self.local_stride as usize;
+      locals[offset..offset + std::mem::size_of::<Locals>()].copy_from_slice(bytemuck::bytes_of(&local));
+    }
+    if !locals.is_empty() { self.queue.write_buffer(&self.local_buffer, 0, &locals); }
+
+    if let Some((center, scale)) = self.gizmo_frame() {
+      let model = glam::Mat4::from_scale_rotation_translation(glam::Vec3::splat(scale), glam::Quat::IDENTITY, center);
+      let handles = self.gizmo.mode.handles();
+      let mut locals = vec![0u8; self.local_stride as usize * handles.len()];
+      for (i, handle) in handles.iter().enumerate() {
+        let highlighted = self.gizmo.active().or_else(|| self.gizmo.hovered()) == Some(*handle);
+        let color = if highlighted { Color::YELLOW } else { handle.color() };
+        let local = Locals::new(model, color.to_linear(), 0);
+        let offset = i * self.local_stride as usize;
+        locals[offset..offset + std::mem::size_of::<Locals>()].copy_from_slice(bytemuck::bytes_of(&local));
+      }
+      self.queue.write_buffer(&self.overlay_buffer, 0, &locals);
     }
+  }
+
+  fn draw(&self, encoder:&mut wgpu::CommandEncoder, attachments:&Attachments, view:&wgpu::TextureView, info:&TargetInfo, size:wgpu::Extent3d) {
+    self.write_uniforms(info);
     {
+      let (view, resolve_target) = attachments.color(view);
       let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: Some("Render Pass"),
-        color_attachments: &[Some(wgpu::RenderPassColorAttachment{view: &view, resolve_target: None, ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color{r:0.1,g:0.2,b:0.3,a:1.0}),store: true}})],
-        depth_stencil_attachment: None});
-      pass.set_pipeline(&self.render_pipeline);
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target, ops: wgpu::Operations{load: wgpu::LoadOp::Clear(self.camera.background.clear_color(info.format)),store: true}})],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment{view: &attachments.depth, depth_ops: Some(wgpu::Operations{load: wgpu::LoadOp::Clear(1.0), store: true}), stencil_ops: None})});
+      self.sky.draw(&mut pass, &self.camera.background);
+
+      for (i, object) in self.objects.iter().enumerate() {
+        let Material{albedo, ..} = object.material;
+        match albedo {
+          Some(texture) => {
+            pass.set_pipeline(&self.phong.textured);
+            pass.set_bind_group(3, &self.textures[texture.0 as usize].bind_group, &[]);
+          }
+          None => pass.set_pipeline(&self.phong.pipeline),
+        }
+        pass.set_bind_group(0, &self.global_bind_group, &[]);
+        let offset = (i as wgpu::BufferAddress * self.local_stride) as wgpu::DynamicOffset;
+        pass.set_bind_group(1, &self.local_bind_group, &[offset]);
+        pass.set_bind_group(2, &self.phong.lights_bind_group, &[]);
+
+        let mesh = &self.meshes[object.mesh.0 as usize];
+        pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+      }
+      pass.set_bind_group(0, &self.global_bind_group, &[]);
+      if self.grid.is_some() { self.grid_pass.draw(&mut pass) }
+      self.line_pass.draw(&mut pass);
+    }
+    if !self.selection.is_empty() {
+      self.draw_outline(encoder, view);
+      self.draw_gizmo(encoder, view);
+    }
+    if matches!(self.grid, Some(g) if g.corner) {
+      let mut pass = self.grid_pass.draw_corner(encoder, view, size);
+      pass.set_bind_group(0, &self.global_bind_group, &[]);
+      pass.draw(0..3, 0..1);
+    }
+    if self.text_pass.glyph_count() > 0 {
+      let mut pass = self.text_pass.pass(encoder, view);
       pass.set_bind_group(0, &self.global_bind_group, &[]);
+      pass.draw(0..6, 0..self.text_pass.glyph_count());
+    }
+  }
 
-      let locals = Locals{color: Color::GREEN.into()};
-      self.queue.write_buffer(&self.local_buffer, 0, bytemuck::bytes_of(&locals));
-      let offset = 0;
-      pass.set_bind_group(1, &self.local_bind_group, &[offset]);
+  /// Draw the handles of the gizmo over the resolved image.
+  fn draw_gizmo(&self, encoder:&mut wgpu::CommandEncoder, view:&wgpu::TextureView) {
+    if self.gizmo_frame().is_none() { return }
+    let mut pass = self.overlay.pass(encoder, view);
+    pass.set_bind_group(0, &self.global_bind_group, &[]);
+    for (i, handle) in self.gizmo.mode.handles().iter().enumerate() {
+      let mesh = match self.handles.iter().find(|(m, h, _)| (*m, h) == (self.gizmo.mode, handle)) { Some((_, _, mesh)) => mesh, None => continue };
+      let offset = (i as wgpu::BufferAddress * self.local_stride) as wgpu::DynamicOffset;
+      pass.set_bind_group(1, &self.overlay_bind_group, &[offset]);
+      pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+      pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+      pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+    }
+  }
 
-      pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-      pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-      pass.draw_indexed(0..self.num_indices, 0, 0..1);
+  /// Outline the selected objects over the resolved image.
+  fn draw_outline(&self, encoder:&mut wgpu::CommandEncoder, view:&wgpu::TextureView) {
+    {
+      let mut pass = self.outline.seeds(encoder);
+      pass.set_bind_group(0, &self.global_bind_group, &[]);
+      for object in self.selection.iter().filter(|o| (o.0 as usize) < self.objects.len()) {
+        let offset = (object.0 as wgpu::BufferAddress * self.local_stride) as wgpu::DynamicOffset;
+        pass.set_bind_group(1, &self.local_bind_group, &[offset]);
+        let mesh = &self.meshes[self.objects[object.0 as usize].mesh.0 as usize];
+        pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+      }
     }
-    self.queue.submit(Some(encoder.finish()));
-    frame.present();
+    self.outline.draw(encoder, view);
   }
 }
 
@@ -317,17 +1125,19 @@ impl Cx {
 #[derive(Clone, Debug)]
 pub struct Camera {
   // pub projection: Projection,
-  /// Specify the depth range as seen by the camera.
-  /// `depth.start` maps to 0.0, and `depth.end` maps to 1.0.
-  /// Vertical field of view, in degrees.
-  /// Note: the horizontal FOV is computed based on the aspect.
   eye:[f32;3],
   target:[f32;3],
   up:[f32;3],
+  /// Vertical field of view, in degrees.
+  /// Note: the horizontal FOV is computed based on the aspect.
   fov_y: f32,
+  /// Distances from the eye to the near and far planes, both positive. `depth.start` maps to 0.0, and `depth.end` maps to 1.0.
   pub depth: std::ops::Range<f32>,
-  // pub node: super::NodeRef, // TODO
-  pub background: Color,
+  /// Width over height of the screen, kept up to date by [`Cx`] to turn points on it into rays.
+  pub aspect: f32,
+  /// Node the camera follows, looking down its -z axis with its y axis up.
+  pub node: Option<NodeRef>,
+  pub background: Background,
 }
 
 impl Default for Camera {
@@ -338,54 +1148,93 @@ impl Default for Camera {
       up: [0.0, 1.0, 0.0].into(),
       // Vertical field of view, in degrees...
       fov_y: 100.0,
-      depth: -0.1..100.0, // 0.0..1.0
-      // node: super::NodeRef::default(),
-      background: Color::BLACK,
+      depth: 0.1..100.0,
+      aspect: 1.0,
+      node: None,
+      background: Background::Color(Color::BLACK),
     }
   }
 }
 
 #[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct Locals {
-  color: [f32;4]
+  model: [[f32;4];4],
+  /// Inverse transpose of the model matrix, so normals stay perpendicular under non-uniform scale.
+  normal: [[f32;4];4],
+  color: [f32;4],
+  id: u32,
+  _padding: [u32;3],
+}
+
+impl Locals {
+  fn new(model:glam::Mat4, color:[f32;4], id:u32)->Self {
+    Locals{model: model.to_cols_array_2d(), normal: model.inverse().transpose().to_cols_array_2d(), color, id, _padding: [0;3]}
+  }
 }
 
 impl Camera {
+  /// Place the eye at the origin of a motor, looking down its -z axis with its y axis up.
+  pub fn follow(&mut self, transform:&g3::Motor) {
+    let at = |x, y, z| { let p:[f32;3] = transform(g3::point(x, y, z)).into(); glam::Vec3::from(p) };
+    let eye = at(0.0, 0.0, 0.0);
+    self.eye = eye.into();
+    self.target = at(0.0, 0.0, -1.0).into();
+    self.up = (at(0.0, 1.0, 0.0) - eye).into();
+  }
+
+  /// The line from the eye through a point on the screen in normalized device coordinates, oriented away from the eye.
+  pub fn ray(&self, ndc:[f32;2])->g3::Line { self.ray_from_eye(ndc).to_line() }
+
+  /// The ray from the eye through a point on the screen in normalized device coordinates, with a unit direction.
+  /// Things in front of the eye are at positive distances along it, unlike on a ray made from [`Camera::ray`].
+  pub fn ray_from_eye(&self, ndc:[f32;2])->Ray {
+    let eye = glam::Vec3::from(self.eye);
+    let forward = (glam::Vec3::from(self.target) - eye).normalize();
+    let right = forward.cross(self.up.into()).normalize();
+    let up = right.cross(forward);
+    let tan = (self.fov_y.to_radians() / 2.0).tan();
+    let direction = (forward + right * (ndc[0] * tan * self.aspect) + up * (ndc[1] * tan)).normalize();
+    Ray::new(eye, direction)
+  }
+
+  pub fn eye(&self)->glam::Vec3 { self.eye.into() }
+
+  pub fn target(&self)->glam::Vec3 { self.target.into() }
+
+  /// Move the eye and the point it looks at, keeping the up direction.
+  pub fn look_at(&mut self, eye:glam::Vec3, target:glam::Vec3) {
+    self.eye = eye.into();
+    self.target = target.into();
+  }
+
+  /// Vertical field of view in degrees.
+  pub fn fov_y(&self)->f32 { self.fov_y }
+
+  pub fn set_fov_y(&mut self, degrees:f32) { self.fov_y = degrees }
+
   pub fn view_matrix(&self) -> glam::Mat4 {
     glam::Mat4::look_at_rh(self.eye.into(), self.target.into(), self.up.into())
   }
+
+  /// Perspective projection to depths from 0 at the near plane to 1 at the far plane.
   pub fn projection_matrix(&self, aspect: f32) -> glam::Mat4 {
     let fov = self.fov_y.to_radians();
-    // let matrix = if self.depth.end == f32::INFINITY {
-    //       assert!(self.depth.start.is_finite());
-    //       glam::Mat4::perspective_infinite_rh(fov, aspect, self.depth.start)
-    //     } else if self.depth.start == f32::INFINITY {
-    //       glam::Mat4::perspective_infinite_reverse_rh(fov, aspect, self.depth.end)
-    //     } else {
-    //       glam::Mat4::perspective_rh(fov, aspect, self.depth.start, self.depth.end)
-    //     };
-    // matrix
-
-    // let near = self.depth.start; let far = self.depth.end;
-    // let f = (1.0/(fov / 2.0).tan()) * (PI/180); WRONG
-    //
-    // // https://www.scratchapixel.com/lessons/3d-basic-rendering/perspective-and-orthographic-projection-matrix/building-basic-perspective-projection-matrix
-    // let m = [[f, 0.0, 0.0, 0.0],
-    //          [0.0, f, 0.0, 0.0],
-    //          [f, 0.0, (far+near)/(near-far), -0.0],
-    //          [f, 0.0, (2.0*far*near)/(near-far), 0.0]];
-    glam::Mat4::perspective_rh(fov, aspect, -0.1, 1.0)
+    glam::Mat4::perspective_rh(fov, aspect, self.depth.start, self.depth.end)
   }
 }
 
+/// Group 0 of every scene pass, declared for the shaders in `pass/globals.wgsl`.
 #[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Globals {
-  view_proj:[[f32;4];4]
+  view_proj:[[f32;4];4],
+  // Non-zero when the target does not encode sRGB on write, colors are uploaded linear.
+  encode_srgb:u32,
+  _padding:[u32;3],
 }
 
 impl Globals {
   fn new() -> Self {
-    Self{view_proj: glam::Mat4::IDENTITY.to_cols_array_2d()}
+    Self{view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(), encode_srgb: 0, _padding: [0;3]}
   }
   fn update_view_proj(&mut self, camera: &Camera, aspect:f32) {
     self.view_proj = camera.projection_matrix(aspect).to_cols_array_2d();
@@ -413,6 +1262,60 @@ mod tests {
     println!("{:?}", p);
   }
 
+  #[test] fn sample_count() {
+    assert_eq!(select_sample_count(&[1, 4], 8), 4);
+    assert_eq!(select_sample_count(&[1, 4], 2), 1);
+    assert_eq!(select_sample_count(&[1, 2, 4, 8], 2), 2);
+    assert_eq!(select_sample_count(&[1], 4), 1);
+  }
+
+  #[test] fn ray() {
+    let camera = Camera{aspect: 1.5, ..Camera::default()};
+    // Through the center of the screen towards the target.
+    let ray = Ray::from_line(&camera.ray([0.0, 0.0]));
+    let to_target = (glam::Vec3::ZERO - glam::Vec3::from(camera.eye)).normalize();
+    assert!(ray.direction.abs_diff_eq(to_target, 1e-5));
+    assert!(ray.origin.cross(ray.direction).abs_diff_eq(glam::Vec3::from(camera.eye).cross(ray.direction), 1e-5));
+    // The top edge of the screen is half the field of view above it.
+    let top = Ray::from_line(&camera.ray([0.0, 1.0]));
+    assert!((top.direction.angle_between(ray.direction) - camera.fov_y.to_radians() / 2.0).abs() < 1e-4);
+  }
+
+  #[test] fn projection() {
+    let camera = Camera::default();
+    let m = camera.projection_matrix(1.5);
+    let depth = |z:f32| m.project_point3(glam::Vec3::new(0.0, 0.0, z)).z;
+    // Nearer points get smaller depths, the near and far planes map to 0 and 1.
+    assert!(depth(-2.0) < depth(-50.0));
+    assert!(depth(-camera.depth.start).abs() < 1e-5 && (depth(-camera.depth.end) - 1.0).abs() < 1e-4);
+    assert!(depth(-1.0) > 0.0 && depth(-0.05) < 0.0);
+  }
+
+  #[test] fn pick_from_eye() {
+    let camera = Camera::default();
+    let ray = camera.ray_from_eye([0.0, 0.0]);
+    assert!(ray.origin.abs_diff_eq(camera.eye(), 1e-6));
+    // A box halfway between the eye and the origin is in front of the eye, the one behind it is not.
+    let meshes = vec![TriMesh::from_mesh(&Mesh::cuboid([0.1; 3]))];
+    let mut objects = vec![Object::new(MeshRef(0), Color::RED.into()), Object::new(MeshRef(0), Color::BLUE.into())];
+    objects[0].transform = crate::scene::translation(camera.eye() * 0.5);
+    objects[1].transform = crate::scene::translation(camera.eye() * 1.5);
+    let hit = crate::scene::pick(&objects, &meshes, &ray).unwrap();
+    assert_eq!(hit.object, ObjectRef(0));
+    // It enters the box through its +z face, 0.45 of the way from the eye to the center along z.
+    assert!((hit.distance - 0.45 * camera.eye().length()).abs() < 1e-5);
+  }
+
+  #[test] fn follow() {
+    let mut camera = Camera::default();
+    let turned = g3::Motor::from(g3::rotor(std::f32::consts::FRAC_PI_2, 0.0, 1.0, 0.0));
+    camera.follow(&(crate::scene::translation(glam::Vec3::new(0.0, 1.0, 5.0)) * turned));
+    // A quarter turn about y looks down -x instead of -z.
+    assert!(glam::Vec3::from(camera.eye).abs_diff_eq(glam::Vec3::new(0.0, 1.0, 5.0), 1e-5));
+    assert!(glam::Vec3::from(camera.target).abs_diff_eq(glam::Vec3::new(-1.0, 1.0, 5.0), 1e-5));
+    assert!(glam::Vec3::from(camera.up).abs_diff_eq(glam::Vec3::Y, 1e-5));
+  }
+
   #[test] fn camera() {
     let mat = glam::Mat4::perspective_infinite_rh(160f32.to_radians(), 4.0/3.0, 0.0);
     println!("{}",mat);
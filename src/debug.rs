@@ -0,0 +1,134 @@
+//! Immediate mode drawing of PGA elements. Everything drawn is gone after the next frame.
+
+use glam::Vec3;
+use crate::Color;
+use crate::collision::{Aabb, Ray};
+use crate::pass::{Cap, LineStyle, Lines, PointShape};
+
+/// Points, lines, planes and motors drawn for one frame, see [`crate::Cx::draw_line`] and friends.
+#[derive(Clone, Debug)]
+pub struct DebugDraw {
+  pub(crate) lines: Lines,
+  /// Infinite lines are cut off where they leave this box, by default a box around the eye as far as the camera sees.
+  pub volume: Option<Aabb>,
+  /// The box around the eye, kept up to date by [`crate::Cx`].
+  pub(crate) view: Aabb,
+  /// Size of points in pixels.
+  pub point_size: f32,
+  /// Width of lines in pixels.
+  pub line_width: f32,
+  /// Length of the axes of motor frames.
+  pub frame_size: f32,
+}
+
+impl Default for DebugDraw {
+  fn default() -> Self {
+    DebugDraw{lines: Lines::new(), volume: None, view: Aabb::new(Vec3::splat(-100.0), Vec3::splat(100.0)), point_size: 8.0, line_width: 2.0, frame_size: 0.5}
+  }
+}
+
+fn position(p:g3::Point)->Vec3 { <[f32;3]>::from(p).into() }
+
+/// The part of a ray from its origin in both directions that is inside a box, as distances along it.
+fn clip(ray:&Ray, aabb:&Aabb)->Option<(f32, f32)> {
+  let (mut near, mut far) = (f32::NEG_INFINITY, f32::INFINITY);
+  for axis in 0..3 {
+    let (origin, direction) = (ray.origin[axis], ray.direction[axis]);
+    // Parallel to the slab, the ray is inside it everywhere or nowhere.
+    if direction == 0.0 {
+      if origin < aabb.min[axis] || origin > aabb.max[axis] { return None }
+      continue
+    }
+    let (t0, t1) = ((aabb.min[axis] - origin) / direction, (aabb.max[axis] - origin) / direction);
+    near = near.max(t0.min(t1));
+    far = far.min(t0.max(t1));
+  }
+  if near <= far { Some((near, far)) } else { None }
+}
+
+impl DebugDraw {
+  /// The box lines are clipped to.
+  pub fn volume(&self)->Aabb { self.volume.unwrap_or(self.view) }
+
+  /// Center the default volume on the eye, reaching as far as the far plane.
+  pub(crate) fn view_from(&mut self, camera:&crate::Camera) {
+    self.view = Aabb::new(camera.eye() - Vec3::splat(camera.depth.end), camera.eye() + Vec3::splat(camera.depth.end));
+  }
+
+  fn style(&self, color:Color)->LineStyle { LineStyle{color, width: self.line_width, cap: Cap::Round, ..LineStyle::default()} }
+
+  /// A round dot, points at infinity are skipped.
+  pub fn point(&mut self, p:g3::Point, color:Color) {
+    if p.w().abs() < 1e-6 { return }
+    self.lines.point(position(p), self.point_size, color, PointShape::Round);
+  }
+
+  /// The part of a line inside the volume.
+  pub fn line(&mut self, l:g3::Line, color:Color) {
+    if l.norm() < 1e-6 { return }
+    let ray = Ray::from_line(&l);
+    if let Some((near, far)) = clip(&ray, &self.volume()) {
+      let style = self.style(color);
+      self.lines.line(ray.at(near), ray.at(far), &style);
+    }
+  }
+
+  /// A filled square of half size `extent` on a plane, centered where the plane is closest to the middle of the
+  /// volume, with its outline and a short line along the normal.
+  pub fn plane(&mut self, p:g3::Plane, color:Color, extent:f32) {
+    let n = Vec3::new(p.x(), p.y(), p.z());
+    let length = n.length();
+    if length < 1e-6 { return }
+    let (n, d) = (n / length, p.d() / length);
+    let center = self.volume().center();
+    let center = center - n * (n.dot(center) + d);
+    let u = n.any_orthonormal_vector();
+    let (u, v) = (u * extent, n.cross(u) * extent);
+    let corners = [center - u - v, center + u - v, center + u + v, center - u + v];
+    self.lines.triangle(corners[0], corners[1], corners[2], color);
+    self.lines.triangle(corners[0], corners[2], corners[3], color);
+    let style = self.style(Color(color.0 | 0xFF));
+    self.lines.polyline(&corners, true, &style);
+    self.lines.line(center, center + n * extent * 0.5, &style);
+  }
+
+  /// The frame a motor moves the origin and axes to, x red, y green and z blue.
+  pub fn motor(&mut self, m:&g3::Motor) {
+    let origin = position(m(g3::point(0.0, 0.0, 0.0)));
+    for (axis, color) in Vec3::AXES.iter().zip([Color::RED, Color::GREEN, Color::BLUE]) {
+      let tip = *axis * self.frame_size;
+      let style = self.style(color);
+      self.lines.line(origin, position(m(g3::point(tip.x, tip.y, tip.z))), &style);
+    }
+  }
+
+  pub fn clear(&mut self) { self.lines.clear() }
+
+  pub fn is_empty(&self)->bool { self.lines.is_empty() }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn clipping() {
+    let volume = Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0));
+    // The line through (0, 0.5, 0) along x crosses the box from x = -1 to 1.
+    let ray = Ray::from_line(&Ray::new(Vec3::new(3.0, 0.5, 0.0), Vec3::X).to_line());
+    let (near, far) = clip(&ray, &volume).unwrap();
+    assert!(ray.at(near).abs_diff_eq(Vec3::new(-1.0, 0.5, 0.0), 1e-5) && ray.at(far).abs_diff_eq(Vec3::new(1.0, 0.5, 0.0), 1e-5));
+    assert!(clip(&Ray::new(Vec3::new(0.0, 2.0, 0.0), Vec3::X), &volume).is_none());
+    // Along a face of the box the whole width is inside.
+    let (near, far) = clip(&Ray::new(Vec3::new(0.0, 1.0, -1.0), Vec3::X), &volume).unwrap();
+    assert!(near == -1.0 && far == 1.0);
+    let mut debug = DebugDraw{volume: Some(volume), ..DebugDraw::default()};
+    debug.line(Ray::new(Vec3::new(0.0, 2.0, 0.0), Vec3::X).to_line(), Color::RED);
+    debug.point(g3::Point::new(1.0, 0.0, 0.0, 0.0), Color::RED);
+    assert!(debug.is_empty());
+    debug.plane(g3::plane(0.0, 1.0, 0.0, -0.5), Color::RED, 1.0);
+    debug.motor(&g3::translator(1.0, 0.0, 0.0, 1.0).into());
+    assert!(!debug.is_empty());
+    debug.clear();
+    assert!(debug.is_empty());
+  }
+}
@@ -0,0 +1,290 @@
+//! Scenes saved as RON. Every file says which version of the format it was written in, files of other versions
+//! are rejected. Paths in a file are relative to it, once read they are absolute.
+
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::{Color, Mesh};
+use crate::graph::Light;
+
+/// Version of the format written by [`SceneFile::to_string`].
+pub const VERSION:u32 = 1;
+
+/// Coefficients of a motor in the order of [`g3::Motor::new`], scalar, e23, e31, e12, e01, e02, e03, e0123.
+pub type MotorData = [f32;8];
+
+pub fn motor_data(m:&g3::Motor)->MotorData { [m.scalar(), m.e23(), m.e31(), m.e12(), m.e01(), m.e02(), m.e03(), m.e0123()] }
+
+pub fn motor_from_data(c:&MotorData)->g3::Motor { g3::Motor::new(c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]) }
+
+fn identity()->MotorData { [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0] }
+
+fn one()->f32 { 1.0 }
+
+fn ones()->[f32;3] { [1.0;3] }
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MeshData {
+  pub vertices: Vec<[f32;3]>,
+  pub indices: Vec<u32>,
+  #[serde(default)]
+  pub colors: Vec<Color>,
+  #[serde(default)]
+  pub uvs: Vec<[f32;2]>,
+}
+
+impl From<&Mesh> for MeshData {
+  fn from(mesh:&Mesh) -> Self { MeshData{vertices: mesh.vertices.clone(), indices: mesh.indices.clone(), colors: mesh.colors.clone(), uvs: mesh.uvs.clone()} }
+}
+
+impl From<MeshData> for Mesh {
+  fn from(data:MeshData) -> Self { Mesh{vertices: data.vertices, indices: data.indices, colors: data.colors, uvs: data.uvs} }
+}
+
+/// Where the geometry of a mesh comes from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+  /// A RON file holding [`MeshData`], relative to the scene file.
+  File(String),
+  Inline(MeshData),
+  Cuboid{half_extents:[f32;3]},
+  Grid{size:[f32;2], divisions:[u32;2]},
+}
+
+impl MeshSource {
+  /// Read the mesh, files are looked up relative to `base`.
+  pub fn load(&self, base:&Path)->anyhow::Result<Mesh> {
+    Ok(match self {
+      MeshSource::File(path) => {
+        let path = base.join(path);
+        let text = std::fs::read_to_string(&path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        ron::from_str::<MeshData>(&text)?.into()
+      }
+      MeshSource::Inline(data) => data.clone().into(),
+      MeshSource::Cuboid{half_extents} => Mesh::cuboid(*half_extents),
+      MeshSource::Grid{size, divisions} => Mesh::grid(*size, *divisions),
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaterialData {
+  pub color: Color,
+  /// Image file of the albedo map, relative to the scene file.
+  #[serde(default)]
+  pub albedo: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ObjectData {
+  /// Index into the meshes of the file.
+  pub mesh: usize,
+  pub material: MaterialData,
+  #[serde(default = "identity")]
+  pub transform: MotorData,
+  #[serde(default = "ones")]
+  pub scale: [f32;3],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeData {
+  pub name: String,
+  /// Index of an earlier node in the file.
+  #[serde(default)]
+  pub parent: Option<usize>,
+  #[serde(default = "identity")]
+  pub transform: MotorData,
+  #[serde(default = "one")]
+  pub scale: f32,
+  /// Indices into the objects of the file.
+  #[serde(default)]
+  pub objects: Vec<usize>,
+  #[serde(default)]
+  pub lights: Vec<Light>,
+}
+
+/// Backgrounds that don't depend on uploaded environments.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundData {
+  Color(Color),
+  Gradient{top:Color, bottom:Color},
+}
+
+impl Default for BackgroundData {
+  fn default() -> Self { BackgroundData::Color(Color::BLACK) }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraData {
+  pub eye: [f32;3],
+  pub target: [f32;3],
+  pub up: [f32;3],
+  /// Vertical field of view in degrees.
+  pub fov_y: f32,
+  /// Index of the node the camera follows.
+  #[serde(default)]
+  pub node: Option<usize>,
+  #[serde(default)]
+  pub background: BackgroundData,
+}
+
+/// Everything that is saved of a scene.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "SceneFile")]
+pub struct SceneFile {
+  pub version: u32,
+  #[serde(default)]
+  pub meshes: Vec<MeshSource>,
+  #[serde(default)]
+  pub objects: Vec<ObjectData>,
+  #[serde(default)]
+  pub nodes: Vec<NodeData>,
+  pub camera: CameraData,
+}
+
+/// Just enough of any version to tell which one it is.
+#[derive(Deserialize)]
+#[serde(rename = "SceneFile")]
+struct Header {
+  version: u32,
+}
+
+impl SceneFile {
+  /// Read a scene written in the current version.
+  pub fn parse(text:&str)->anyhow::Result<SceneFile> {
+    let header:Header = ron::from_str(text)?;
+    let file:SceneFile = match header.version {
+      VERSION => ron::from_str(text)?,
+      v if v > VERSION => anyhow::bail!("scene file version {} is newer than the supported version {}", v, VERSION),
+      v => anyhow::bail!("scene file version {} doesn't exist", v),
+    };
+    file.validate()?;
+    Ok(file)
+  }
+
+  /// Check that every index points at something that exists, nodes only at earlier nodes.
+  pub fn validate(&self)->anyhow::Result<()> {
+    let check = |what:&str, index:usize, len:usize| if index < len { Ok(()) } else { Err(anyhow::anyhow!("{} {} doesn't exist", what, index)) };
+    for o in &self.objects { check("mesh", o.mesh, self.meshes.len())? }
+    for (i, n) in self.nodes.iter().enumerate() {
+      if let Some(parent) = n.parent { check("parent node", parent, i)? }
+      for &o in &n.objects { check("object", o, self.objects.len())? }
+    }
+    if let Some(node) = self.camera.node { check("node", node, self.nodes.len())? }
+    Ok(())
+  }
+
+  pub fn to_string(&self)->anyhow::Result<String> {
+    Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new().depth_limit(4))?)
+  }
+
+  /// Read a scene file, the paths in it are made absolute.
+  pub fn load<P:AsRef<Path>>(path:P)->anyhow::Result<SceneFile> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let mut file = SceneFile::parse(&text)?;
+    file.resolve_paths(path.parent().unwrap_or_else(|| Path::new("")));
+    Ok(file)
+  }
+
+  /// Write a scene file, absolute paths are written relative to it where they can be.
+  pub fn save<P:AsRef<Path>>(&self, path:P)->anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut file = self.clone();
+    file.relative_paths(Path::new(&absolute_path(path.parent().unwrap_or_else(|| Path::new("")), ".")));
+    Ok(std::fs::write(path, file.to_string()?)?)
+  }
+
+  /// Make the mesh and texture paths absolute, reading relative ones from `base`.
+  pub fn resolve_paths(&mut self, base:&Path) { self.map_paths(|path| absolute_path(base, path)) }
+
+  /// Make the absolute mesh and texture paths relative to the directory `dir`.
+  pub fn relative_paths(&mut self, dir:&Path) { self.map_paths(|path| relative_path(path, dir)) }
+
+  fn map_paths(&mut self, f:impl Fn(&str)->String) {
+    for mesh in &mut self.meshes {
+      if let MeshSource::File(path) = mesh { *path = f(path) }
+    }
+    for albedo in self.objects.iter_mut().filter_map(|o| o.material.albedo.as_mut()) { *albedo = f(albedo) }
+  }
+}
+
+/// A path read from `base` as an absolute path, without `.`, `..` or links when the file exists.
+pub fn absolute_path(base:&Path, path:&str)->String {
+  let joined = base.join(path);
+  let absolute = joined.canonicalize().or_else(|_| std::env::current_dir().map(|dir| dir.join(&joined))).unwrap_or(joined);
+  absolute.to_string_lossy().into_owned()
+}
+
+/// An absolute path relative to the absolute directory `dir`, going up with `..` where they part. Relative paths
+/// and paths that share no root with `dir`, like ones on another drive, are kept as they are.
+pub fn relative_path(path:&str, dir:&Path)->String {
+  let path = Path::new(path);
+  let (to, from):(Vec<_>, Vec<_>) = (path.components().collect(), dir.components().collect());
+  let common = to.iter().zip(&from).take_while(|(a, b)| a == b).count();
+  if !path.is_absolute() || common == 0 { return path.to_string_lossy().into_owned() }
+  let relative:PathBuf = from[common..].iter().map(|_| Component::ParentDir).chain(to[common..].iter().copied()).collect();
+  relative.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn camera()->CameraData {
+    CameraData{eye: [0.0, 1.0, 2.0], target: [0.0; 3], up: [0.0, 1.0, 0.0], fov_y: 100.0, node: Some(0), background: BackgroundData::Gradient{top: Color::BLUE, bottom: Color::WHITE}}
+  }
+
+  #[test] fn round_trip() {
+    let turned = g3::Motor::from(g3::rotor(0.5, 0.0, 1.0, 0.0));
+    let file = SceneFile {
+      version: VERSION,
+      meshes: vec![MeshSource::Cuboid{half_extents: [0.5; 3]}, MeshSource::Inline((&Mesh::grid([1.0, 1.0], [1, 1])).into())],
+      objects: vec![ObjectData{mesh: 1, material: MaterialData{color: Color::RED, albedo: None}, transform: motor_data(&turned), scale: [1.0, 2.0, 1.0]}],
+      nodes: vec![
+        NodeData{name: "root".into(), parent: None, transform: identity(), scale: 2.0, objects: vec![0], lights: vec![Light::default()]},
+        NodeData{name: "child".into(), parent: Some(0), transform: identity(), scale: 1.0, objects: vec![], lights: vec![]}],
+      camera: camera(),
+    };
+    let text = file.to_string().unwrap();
+    assert!(text.contains("\"#ff0000ff\"") && text.contains("Cuboid"));
+    assert_eq!(SceneFile::parse(&text).unwrap(), file);
+    assert_eq!(motor_data(&motor_from_data(&motor_data(&turned))), motor_data(&turned));
+  }
+
+  #[test] fn paths() {
+    let root = std::env::temp_dir().join(format!("mirror-paths-{}", std::process::id()));
+    let (a, b) = (root.join("a"), root.join("b").join("c"));
+    std::fs::create_dir_all(a.join("textures")).unwrap();
+    std::fs::create_dir_all(&b).unwrap();
+    std::fs::write(a.join("mesh.ron"), ron::to_string(&MeshData::from(&Mesh::cuboid([0.5; 3]))).unwrap()).unwrap();
+    std::fs::write(a.join("textures").join("albedo.png"), []).unwrap();
+    let mut file = SceneFile{version: VERSION, meshes: vec![MeshSource::File("mesh.ron".into())], objects: vec![], nodes: vec![], camera: camera()};
+    file.camera.node = None;
+    file.objects.push(ObjectData{mesh: 0, material: MaterialData{color: Color::RED, albedo: Some("textures/albedo.png".into())}, transform: identity(), scale: ones()});
+    std::fs::write(a.join("scene.ron"), file.to_string().unwrap()).unwrap();
+    // Read, the paths are absolute and written back as they were.
+    let loaded = SceneFile::load(a.join("scene.ron")).unwrap();
+    let mesh = a.join("mesh.ron").canonicalize().unwrap();
+    assert_eq!(loaded.meshes[0], MeshSource::File(mesh.to_string_lossy().into_owned()));
+    loaded.save(a.join("again.ron")).unwrap();
+    assert_eq!(SceneFile::parse(&std::fs::read_to_string(a.join("again.ron")).unwrap()).unwrap(), file);
+    // Saved in another directory, they lead back to the same files.
+    loaded.save(b.join("scene.ron")).unwrap();
+    let text = std::fs::read_to_string(b.join("scene.ron")).unwrap();
+    assert!(text.contains(&Path::new("..").join("..").join("a").join("mesh.ron").to_string_lossy().into_owned()));
+    let moved = SceneFile::load(b.join("scene.ron")).unwrap();
+    assert_eq!(moved, loaded);
+    assert_eq!(moved.meshes[0].load(&b).unwrap().indices.len(), 36);
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test] fn invalid() {
+    assert!(SceneFile::parse("(version: 3, camera: ())").unwrap_err().to_string().contains("newer"));
+    assert!(SceneFile::parse("(version: 0, camera: ())").unwrap_err().to_string().contains("doesn't exist"));
+    let mut file = SceneFile{version: VERSION, meshes: vec![], objects: vec![], nodes: vec![], camera: camera()};
+    assert!(SceneFile::parse(&file.to_string().unwrap()).unwrap_err().to_string().contains("node 0"));
+    file.camera.node = None;
+    file.objects.push(ObjectData{mesh: 0, material: MaterialData{color: Color::RED, albedo: None}, transform: identity(), scale: ones()});
+    assert!(SceneFile::parse(&file.to_string().unwrap()).is_err());
+  }
+}
@@ -0,0 +1,372 @@
+//! Handles drawn over the selection that move, turn and scale it when dragged with the mouse.
+
+use glam::Vec3;
+use crate::{Color, Mesh};
+use crate::collision::Ray;
+use crate::scene::translation;
+
+/// Half the thickness of axes and rings, and how close the cursor ray has to pass to grab them, in gizmo units.
+const RADIUS:f32 = 0.1;
+/// Where the plane handles start and end along the other two axes.
+const PLANE:(f32, f32) = (0.3, 0.5);
+const RING:f32 = 0.9;
+/// Smallest factor a scale can be dragged to.
+const MIN_SCALE:f32 = 0.01;
+/// Closest to the center that a scale handle can be grabbed, in gizmo units, the factor is measured from there.
+const MIN_GRAB:f32 = 0.001;
+
+/// What dragging the gizmo does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GizmoMode {
+  Translate,
+  Rotate,
+  Scale,
+}
+
+/// A part of the gizmo that can be grabbed. Axes are numbered x, y, z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Handle {
+  /// Moves along an axis, or scales by how far it is dragged along it.
+  Axis(usize),
+  /// Moves in the plane with an axis as normal.
+  Plane(usize),
+  /// Turns about an axis.
+  Ring(usize),
+  /// Scales uniformly.
+  Center,
+}
+
+impl GizmoMode {
+  pub fn handles(&self)->&'static [Handle] {
+    match self {
+      GizmoMode::Translate => &[Handle::Axis(0), Handle::Axis(1), Handle::Axis(2), Handle::Plane(0), Handle::Plane(1), Handle::Plane(2)],
+      GizmoMode::Rotate => &[Handle::Ring(0), Handle::Ring(1), Handle::Ring(2)],
+      GizmoMode::Scale => &[Handle::Axis(0), Handle::Axis(1), Handle::Axis(2), Handle::Center],
+    }
+  }
+}
+
+impl Handle {
+  /// Red, green and blue for the x, y and z axes, plane handles are translucent.
+  pub fn color(&self)->Color {
+    match *self {
+      Handle::Axis(i) | Handle::Ring(i) => [Color::RED, Color::GREEN, Color::BLUE][i],
+      Handle::Plane(i) => Color([Color::RED, Color::GREEN, Color::BLUE][i].0 & 0xFFFFFF00 | 0x90),
+      Handle::Center => Color::WHITE,
+    }
+  }
+
+  /// Geometry of the handle around the origin, for a gizmo whose axes are one unit long.
+  pub fn mesh(&self, mode:GizmoMode)->Mesh {
+    let along = |i:usize, v:Vec3| -> [f32;3] { (Vec3::AXES[i] * v.x + Vec3::AXES[(i + 1) % 3] * v.y + Vec3::AXES[(i + 2) % 3] * v.z).into() };
+    match *self {
+      Handle::Axis(i) => {
+        // A shaft ending in a larger box, flatter for moving than for scaling.
+        let tip = if mode == GizmoMode::Scale { Vec3::splat(0.08) } else { Vec3::new(0.12, 0.06, 0.06) };
+        let mut mesh = boxes(&[(Vec3::new(0.45, 0.0, 0.0), Vec3::new(0.45, 0.015, 0.015)), (Vec3::new(1.0 - tip.x, 0.0, 0.0), tip)]);
+        for v in &mut mesh.vertices { *v = along(i, Vec3::from(*v)) }
+        mesh
+      }
+      Handle::Plane(i) => {
+        let (a, b) = PLANE;
+        let vertices = vec![along(i, Vec3::new(0.0, a, a)), along(i, Vec3::new(0.0, b, a)), along(i, Vec3::new(0.0, b, b)), along(i, Vec3::new(0.0, a, b))];
+        Mesh{vertices, indices: vec![0, 1, 2, 0, 2, 3], colors: vec![], uvs: vec![]}
+      }
+      Handle::Ring(i) => {
+        // A flat band, drawn from both sides.
+        let n = 64;
+        let (mut vertices, mut indices) = (vec![], vec![]);
+        for k in 0..n {
+          let (sin, cos) = (k as f32 / n as f32 * std::f32::consts::TAU).sin_cos();
+          for r in [RING - 0.02, RING + 0.02] { vertices.push(along(i, Vec3::new(0.0, cos * r, sin * r))) }
+          let (a, b, c, d) = (2 * k, 2 * k + 1, (2 * k + 3) % (2 * n), (2 * k + 2) % (2 * n));
+          indices.extend([a, b, c, a, c, d]);
+        }
+        Mesh{vertices, indices, colors: vec![], uvs: vec![]}
+      }
+      Handle::Center => boxes(&[(Vec3::ZERO, Vec3::splat(0.1))]),
+    }
+  }
+}
+
+/// Boxes given by center and half extents, as one mesh.
+fn boxes(parts:&[(Vec3, Vec3)])->Mesh {
+  let mut mesh = Mesh{vertices: vec![], indices: vec![], colors: vec![], uvs: vec![]};
+  for &(center, half) in parts {
+    let part = Mesh::cuboid(half.into());
+    let first = mesh.vertices.len() as u32;
+    mesh.vertices.extend(part.vertices.iter().map(|&v| <[f32;3]>::from(Vec3::from(v) + center)));
+    mesh.indices.extend(part.indices.iter().map(|i| first + i));
+  }
+  mesh
+}
+
+/// The change made by a drag since the previous one.
+#[derive(Clone, Copy, Debug)]
+pub enum Delta {
+  /// Applied after the transform of each selected object, `motor * transform`.
+  Motor(g3::Motor),
+  /// Multiplies the scale of each selected object. Scaling is uniform, the handles are along the world axes
+  /// while the objects can be turned.
+  Scale(f32),
+}
+
+/// Increments that drags snap to, nothing snaps by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Snap {
+  /// Distance in world units.
+  pub translate: Option<f32>,
+  /// Angle in degrees.
+  pub rotate: Option<f32>,
+  /// Factor of the scale at the start of the drag.
+  pub scale: Option<f32>,
+}
+
+fn snap(value:f32, step:Option<f32>)->f32 {
+  match step {
+    Some(step) if step > 0.0 => (value / step).round() * step,
+    _ => value,
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+  handle: Handle,
+  center: Vec3,
+  /// Normal of the plane the cursor is followed on by planes, rings and the center.
+  normal: Vec3,
+  /// Where the handle was grabbed.
+  grab: Vec3,
+  /// Angle turned so far without snapping and the angle of the cursor at the last drag, for following rings across the half turn.
+  turned: f32,
+  last: f32,
+  /// What was handed out so far: a translation, an angle in `x` or scale factors.
+  applied: Vec3,
+}
+
+/// Handles for the selected objects. Its axes follow the world axes and are `size` times the distance to the camera long,
+/// so the gizmo keeps its size on the screen.
+#[derive(Clone, Debug)]
+pub struct Gizmo {
+  pub mode: GizmoMode,
+  pub snap: Snap,
+  pub size: f32,
+  hovered: Option<Handle>,
+  drag: Option<Drag>,
+}
+
+impl Default for Gizmo {
+  fn default() -> Self { Gizmo::new(GizmoMode::Translate) }
+}
+
+impl Gizmo {
+  pub fn new(mode:GizmoMode)->Gizmo { Gizmo{mode, snap: Snap::default(), size: 0.15, hovered: None, drag: None} }
+
+  /// Length of the axes for a gizmo at `center` seen from `eye`.
+  pub fn scale(&self, center:Vec3, eye:Vec3)->f32 { self.size * center.distance(eye) }
+
+  /// The handle under the cursor, while nothing is dragged.
+  pub fn hovered(&self)->Option<Handle> { self.hovered }
+
+  /// The handle being dragged.
+  pub fn active(&self)->Option<Handle> { self.drag.map(|d| d.handle) }
+
+  /// The nearest handle a ray passes through, for a gizmo at `center` with axes `scale` long.
+  pub fn hit(&self, center:Vec3, scale:f32, ray:&Ray)->Option<Handle> {
+    let ray = Ray::new(ray.origin, ray.direction.normalize());
+    let mut nearest:Option<(Handle, f32)> = None;
+    for &handle in self.mode.handles() {
+      let t = match Self::intersect(handle, center, scale, &ray) { Some(t) => t, None => continue };
+      if !matches!(nearest, Some((_, n)) if n <= t) { nearest = Some((handle, t)) }
+    }
+    nearest.map(|(handle, _)| handle)
+  }
+
+  fn intersect(handle:Handle, center:Vec3, scale:f32, ray:&Ray)->Option<f32> {
+    match handle {
+      Handle::Axis(i) => {
+        let (s, t) = closest(center, Vec3::AXES[i], ray)?;
+        let gap = (center + Vec3::AXES[i] * s).distance(ray.at(t));
+        if t >= 0.0 && (0.0..=scale).contains(&s) && gap <= RADIUS * scale { Some(t) } else { None }
+      }
+      Handle::Plane(i) => {
+        let t = plane(center, Vec3::AXES[i], ray)?;
+        let p = ray.at(t) - center;
+        let inside = |axis:usize| (PLANE.0 * scale..=PLANE.1 * scale).contains(&p.dot(Vec3::AXES[axis]));
+        if inside((i + 1) % 3) && inside((i + 2) % 3) { Some(t) } else { None }
+      }
+      Handle::Ring(i) => {
+        let t = plane(center, Vec3::AXES[i], ray)?;
+        if (ray.at(t).distance(center) - RING * scale).abs() <= RADIUS * scale { Some(t) } else { None }
+      }
+      Handle::Center => {
+        let t = (center - ray.origin).dot(ray.direction);
+        if t >= 0.0 && ray.at(t).distance(center) <= 1.5 * RADIUS * scale { Some(t) } else { None }
+      }
+    }
+  }
+
+  /// Follow the cursor with the hovered handle, unless one is being dragged.
+  pub fn hover(&mut self, center:Vec3, scale:f32, ray:&Ray) {
+    if self.drag.is_none() { self.hovered = self.hit(center, scale, ray) }
+  }
+
+  /// Grab the handle a ray passes through, returns false if there is none.
+  pub fn begin(&mut self, center:Vec3, scale:f32, ray:&Ray)->bool {
+    let handle = match self.hit(center, scale, ray) { Some(handle) => handle, None => return false };
+    let ray = Ray::new(ray.origin, ray.direction.normalize());
+    let normal = match handle {
+      Handle::Plane(i) | Handle::Ring(i) => Vec3::AXES[i],
+      _ => -ray.direction,
+    };
+    let grab = match handle {
+      Handle::Axis(i) => center + Vec3::AXES[i] * closest(center, Vec3::AXES[i], &ray).map_or(0.0, |(s, _)| s),
+      _ => ray.at(plane(center, normal, &ray).unwrap_or(0.0)),
+    };
+    // Scales are ratios of distances from the center, there is none to start from at the center.
+    if self.mode == GizmoMode::Scale && grab.distance(center) < MIN_GRAB * scale { return false }
+    let mut drag = Drag{handle, center, normal, grab, turned: 0.0, last: 0.0, applied: Vec3::ZERO};
+    if let Handle::Ring(i) = handle { drag.last = angle(i, grab - center) }
+    if self.mode == GizmoMode::Scale { drag.applied = Vec3::ONE }
+    self.drag = Some(drag);
+    self.hovered = Some(handle);
+    true
+  }
+
+  /// Follow the cursor with the grabbed handle, returns the change since the last drag if there is any.
+  pub fn drag(&mut self, ray:&Ray)->Option<Delta> {
+    let (mode, step) = (self.mode, self.snap);
+    let drag = self.drag.as_mut()?;
+    let ray = Ray::new(ray.origin, ray.direction.normalize());
+    let c = drag.center;
+    match (mode, drag.handle) {
+      (GizmoMode::Translate, Handle::Axis(i)) => {
+        let (s, _) = closest(c, Vec3::AXES[i], &ray)?;
+        let total = Vec3::AXES[i] * snap(s - (drag.grab - c).dot(Vec3::AXES[i]), step.translate);
+        Some(Delta::Motor(translation(total - std::mem::replace(&mut drag.applied, total))))
+      }
+      (GizmoMode::Translate, Handle::Plane(_)) => {
+        let moved = ray.at(plane(c, drag.normal, &ray)?) - drag.grab;
+        let total = Vec3::new(snap(moved.x, step.translate), snap(moved.y, step.translate), snap(moved.z, step.translate));
+        Some(Delta::Motor(translation(total - std::mem::replace(&mut drag.applied, total))))
+      }
+      (GizmoMode::Rotate, Handle::Ring(i)) => {
+        let now = angle(i, ray.at(plane(c, drag.normal, &ray)?) - c);
+        let turn = (now - drag.last + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        drag.turned += turn;
+        drag.last = now;
+        let total = snap(drag.turned.to_degrees(), step.rotate).to_radians();
+        let delta = total - std::mem::replace(&mut drag.applied, Vec3::new(total, 0.0, 0.0)).x;
+        let axis = Vec3::AXES[i];
+        let rotor = g3::Motor::from(g3::rotor(delta, axis.x, axis.y, axis.z));
+        Some(Delta::Motor(translation(c) * rotor * translation(-c)))
+      }
+      (GizmoMode::Scale, handle) => {
+        let factor = match handle {
+          Handle::Axis(i) => {
+            let (s, _) = closest(c, Vec3::AXES[i], &ray)?;
+            s / (drag.grab - c).dot(Vec3::AXES[i])
+          }
+          _ => ray.at(plane(c, drag.normal, &ray)?).distance(c) / drag.grab.distance(c),
+        };
+        let factor = snap(factor, step.scale).max(MIN_SCALE);
+        Some(Delta::Scale(factor / std::mem::replace(&mut drag.applied, Vec3::splat(factor)).x))
+      }
+      _ => None,
+    }
+  }
+
+  /// Let go of the grabbed handle.
+  pub fn end(&mut self) { self.drag = None }
+}
+
+/// Parameters of the closest points of the line through `origin` along the unit `axis` and a ray with unit direction.
+fn closest(origin:Vec3, axis:Vec3, ray:&Ray)->Option<(f32, f32)> {
+  let w = origin - ray.origin;
+  let b = axis.dot(ray.direction);
+  let denominator = 1.0 - b * b;
+  if denominator < 1e-6 { return None }
+  let s = (b * ray.direction.dot(w) - axis.dot(w)) / denominator;
+  Some((s, ray.direction.dot(w) + b * s))
+}
+
+/// Distance along a ray to the plane through `origin` with `normal`, if it is in front.
+fn plane(origin:Vec3, normal:Vec3, ray:&Ray)->Option<f32> {
+  let d = ray.direction.dot(normal);
+  if d.abs() < 1e-6 { return None }
+  let t = (origin - ray.origin).dot(normal) / d;
+  if t >= 0.0 { Some(t) } else { None }
+}
+
+/// Angle of a vector about an axis, counterclockwise when looking down the axis.
+fn angle(axis:usize, v:Vec3)->f32 {
+  v.dot(Vec3::AXES[(axis + 2) % 3]).atan2(v.dot(Vec3::AXES[(axis + 1) % 3]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn apply(delta:Delta, p:Vec3)->Vec3 {
+    match delta {
+      Delta::Motor(m) => { let p:[f32;3] = m(g3::point(p.x, p.y, p.z)).into(); Vec3::from(p) }
+      Delta::Scale(s) => p * s,
+    }
+  }
+
+  #[test] fn hit() {
+    let gizmo = Gizmo::default();
+    let down = |x, z| Ray::new(Vec3::new(x, 5.0, z), -Vec3::Y);
+    assert_eq!(gizmo.hit(Vec3::ZERO, 1.0, &down(0.8, 0.05)), Some(Handle::Axis(0)));
+    assert_eq!(gizmo.hit(Vec3::ZERO, 1.0, &down(0.4, 0.4)), Some(Handle::Plane(1)));
+    assert_eq!(gizmo.hit(Vec3::ZERO, 1.0, &down(0.8, 0.8)), None);
+    assert_eq!(gizmo.hit(Vec3::ZERO, 2.0, &down(1.6, 0.1)), Some(Handle::Axis(0)));
+    let rotate = Gizmo::new(GizmoMode::Rotate);
+    assert_eq!(rotate.hit(Vec3::ZERO, 1.0, &down(0.0, 0.9)), Some(Handle::Ring(1)));
+  }
+
+  #[test] fn translate() {
+    let mut gizmo = Gizmo::default();
+    let center = Vec3::new(1.0, 0.0, 0.0);
+    assert!(gizmo.begin(center, 1.0, &Ray::new(Vec3::new(1.5, 0.0, 5.0), -Vec3::Z)));
+    assert_eq!(gizmo.active(), Some(Handle::Axis(0)));
+    // Only the part of the movement along the axis counts, and the deltas add up.
+    let first = gizmo.drag(&Ray::new(Vec3::new(2.0, 1.0, 5.0), -Vec3::Z)).unwrap();
+    assert!(apply(first, center).abs_diff_eq(Vec3::new(1.5, 0.0, 0.0), 1e-5));
+    let second = gizmo.drag(&Ray::new(Vec3::new(2.5, 0.0, 5.0), -Vec3::Z)).unwrap();
+    assert!(apply(second, Vec3::ZERO).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+    gizmo.end();
+    // Snapped to whole units.
+    gizmo.snap.translate = Some(1.0);
+    assert!(gizmo.begin(Vec3::ZERO, 1.0, &Ray::new(Vec3::new(0.4, 5.0, 0.4), -Vec3::Y)));
+    let delta = gizmo.drag(&Ray::new(Vec3::new(1.2, 5.0, 2.9), -Vec3::Y)).unwrap();
+    assert!(apply(delta, Vec3::ZERO).abs_diff_eq(Vec3::new(1.0, 0.0, 3.0), 1e-5));
+  }
+
+  #[test] fn rotate() {
+    let mut gizmo = Gizmo::new(GizmoMode::Rotate);
+    gizmo.snap.rotate = Some(15.0);
+    let center = Vec3::new(0.0, 0.0, 1.0);
+    // Grab the ring about z on its x side and drag it a little past a quarter turn.
+    assert!(gizmo.begin(center, 1.0, &Ray::new(center + Vec3::new(0.9, 0.0, 5.0), -Vec3::Z)));
+    let (sin, cos) = 93f32.to_radians().sin_cos();
+    let delta = gizmo.drag(&Ray::new(center + Vec3::new(cos, sin, 5.0), -Vec3::Z)).unwrap();
+    assert!(apply(delta, center + Vec3::X).abs_diff_eq(center + Vec3::Y, 1e-5));
+    assert!(apply(delta, center).abs_diff_eq(center, 1e-5));
+  }
+
+  #[test] fn scale() {
+    let mut gizmo = Gizmo::new(GizmoMode::Scale);
+    assert!(gizmo.begin(Vec3::ZERO, 1.0, &Ray::new(Vec3::new(0.0, 0.5, 5.0), -Vec3::Z)));
+    let first = gizmo.drag(&Ray::new(Vec3::new(0.0, 1.0, 5.0), -Vec3::Z)).unwrap();
+    let second = gizmo.drag(&Ray::new(Vec3::new(0.0, 1.5, 5.0), -Vec3::Z)).unwrap();
+    assert!(apply(first, Vec3::ONE).abs_diff_eq(Vec3::splat(2.0), 1e-5));
+    assert!(apply(second, Vec3::ONE).abs_diff_eq(Vec3::splat(1.5), 1e-5));
+    gizmo.end();
+    // The center handle can't be grabbed right at the center, there is nothing to scale from.
+    assert!(!gizmo.begin(Vec3::ZERO, 1.0, &Ray::new(Vec3::new(0.0, 0.0, 5.0), -Vec3::Z)));
+    assert!(gizmo.begin(Vec3::ZERO, 1.0, &Ray::new(Vec3::new(-0.1, -0.05, 5.0), -Vec3::Z)));
+    assert_eq!(gizmo.active(), Some(Handle::Center));
+    assert!(apply(gizmo.drag(&Ray::new(Vec3::new(-0.3, -0.15, 5.0), -Vec3::Z)).unwrap(), Vec3::ONE).abs_diff_eq(Vec3::splat(3.0), 1e-5));
+  }
+}
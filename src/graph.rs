@@ -0,0 +1,298 @@
+//! A hierarchy of nodes placed relative to their parents, that objects, lights and the camera can be attached to.
+
+use glam::Vec3;
+use crate::Color;
+use crate::scene::{identity, translation, ObjectRef};
+
+/// A node by its slot and the generation of the slot, so a reference to a removed node never finds the one that took its place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeRef(pub(crate) u16, pub(crate) u16);
+
+/// A point light, lighting up to `range` from the node it is attached to.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Light {
+  pub color: Color,
+  pub intensity: f32,
+  pub range: f32,
+}
+
+impl Default for Light {
+  fn default() -> Self { Light{color: Color::WHITE, intensity: 1.0, range: 10.0} }
+}
+
+/// What follows a node around.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attachment {
+  /// Placed and uniformly scaled like the node, its own transform is overwritten.
+  Object(ObjectRef),
+  Light(Light),
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+  pub name: String,
+  pub attachments: Vec<Attachment>,
+  parent: Option<NodeRef>,
+  children: Vec<NodeRef>,
+  transform: g3::Motor,
+  scale: f32,
+  /// Cached placement in the world, valid unless dirty.
+  world: (g3::Motor, f32),
+  dirty: bool,
+}
+
+impl Node {
+  pub fn parent(&self)->Option<NodeRef> { self.parent }
+
+  pub fn children(&self)->&[NodeRef] { &self.children }
+
+  /// Placement relative to the parent, applied after the scale.
+  pub fn transform(&self)->g3::Motor { self.transform }
+
+  pub fn scale(&self)->f32 { self.scale }
+}
+
+/// Nodes with a local motor and uniform scale each. World transforms are cached and only recomputed for nodes
+/// that moved, or whose ancestors moved, since they were last asked for.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+  nodes: Vec<Option<Node>>,
+  /// Bumped every time the node in a slot is removed.
+  generations: Vec<u16>,
+  free: Vec<u16>,
+  roots: Vec<NodeRef>,
+}
+
+fn transform_point(m:&g3::Motor, p:Vec3)->Vec3 { let p:[f32;3] = m(g3::point(p.x, p.y, p.z)).into(); p.into() }
+
+/// Placement of a node in the world from that of its parent.
+fn place(node:&Node, (parent, scale):(g3::Motor, f32))->(g3::Motor, f32) {
+  // The parent's scale stretches the offset of the child but not its rotation.
+  let offset = transform_point(&node.transform, Vec3::ZERO);
+  (parent * translation(offset * (scale - 1.0)) * node.transform, scale * node.scale)
+}
+
+impl Graph {
+  pub fn new()->Graph { Graph::default() }
+
+  /// Add a node without any attachments, at the origin of its parent or of the world.
+  pub fn add(&mut self, name:&str, parent:Option<NodeRef>)->NodeRef {
+    let node = Node{name: name.to_string(), attachments: vec![], parent, children: vec![], transform: identity(), scale: 1.0, world: (identity(), 1.0), dirty: true};
+    let index = match self.free.pop() {
+      Some(i) => { self.nodes[i as usize] = Some(node); NodeRef(i, self.generations[i as usize]) }
+      None => {
+        let i = u16::try_from(self.nodes.len()).expect("more nodes than a NodeRef can address");
+        self.nodes.push(Some(node));
+        self.generations.push(0);
+        NodeRef(i, 0)
+      }
+    };
+    match parent {
+      Some(parent) => self.node_mut(parent).children.push(index),
+      None => self.roots.push(index),
+    }
+    index
+  }
+
+  /// Remove a node and everything below it.
+  pub fn remove(&mut self, node:NodeRef) {
+    self.detach(node);
+    let removed = self.descendants(node);
+    for n in removed {
+      self.nodes[n.0 as usize] = None;
+      self.generations[n.0 as usize] = self.generations[n.0 as usize].wrapping_add(1);
+      self.free.push(n.0);
+    }
+  }
+
+  fn detach(&mut self, node:NodeRef) {
+    match self.node(node).parent {
+      Some(parent) => self.node_mut(parent).children.retain(|&c| c != node),
+      None => self.roots.retain(|&c| c != node),
+    }
+  }
+
+  /// Move a node under another parent or to the top, keeping its local transform.
+  /// Panics when the node would end up below itself.
+  pub fn set_parent(&mut self, node:NodeRef, parent:Option<NodeRef>) {
+    if let Some(p) = parent { assert!(!self.ancestors(p).any(|a| a == node) && p != node, "a node can't be its own ancestor") }
+    self.detach(node);
+    self.node_mut(node).parent = parent;
+    match parent {
+      Some(parent) => self.node_mut(parent).children.push(node),
+      None => self.roots.push(node),
+    }
+    self.invalidate(node);
+  }
+
+  pub fn get(&self, node:NodeRef)->Option<&Node> {
+    if self.generations.get(node.0 as usize) != Some(&node.1) { return None }
+    self.nodes[node.0 as usize].as_ref()
+  }
+
+  pub fn node(&self, node:NodeRef)->&Node { self.get(node).expect("node was removed") }
+
+  fn node_mut(&mut self, node:NodeRef)->&mut Node {
+    assert!(self.generations[node.0 as usize] == node.1, "node was removed");
+    self.nodes[node.0 as usize].as_mut().expect("node was removed")
+  }
+
+  /// Objects and lights of a node, changing them doesn't move anything.
+  pub fn attachments_mut(&mut self, node:NodeRef)->&mut Vec<Attachment> { &mut self.node_mut(node).attachments }
+
+  pub fn attach(&mut self, node:NodeRef, attachment:Attachment) { self.node_mut(node).attachments.push(attachment) }
+
+  pub fn set_transform(&mut self, node:NodeRef, transform:g3::Motor) {
+    self.node_mut(node).transform = transform;
+    self.invalidate(node);
+  }
+
+  pub fn set_scale(&mut self, node:NodeRef, scale:f32) {
+    self.node_mut(node).scale = scale;
+    self.invalidate(node);
+  }
+
+  /// Mark a node and everything below it for recomputing. Below a marked node everything is marked already.
+  fn invalidate(&mut self, node:NodeRef) {
+    self.node_mut(node).dirty = true;
+    let mut stack = self.node(node).children.clone();
+    while let Some(n) = stack.pop() {
+      let node = self.node_mut(n);
+      if node.dirty { continue }
+      node.dirty = true;
+      stack.extend(node.children.iter().copied());
+    }
+  }
+
+  /// Placement of a node in the world and its scale, a point `p` of the node is at `motor(scale * p)`.
+  pub fn world(&mut self, node:NodeRef)->(g3::Motor, f32) {
+    if !self.node(node).dirty { return self.node(node).world }
+    let parent = match self.node(node).parent {
+      Some(parent) => self.world(parent),
+      None => (identity(), 1.0),
+    };
+    let n = self.node_mut(node);
+    n.world = place(n, parent);
+    n.dirty = false;
+    n.world
+  }
+
+  /// Like [`Graph::world`] without updating the cache, for nodes that moved it is recomputed on every call.
+  pub fn current_world(&self, node:NodeRef)->(g3::Motor, f32) {
+    let n = self.node(node);
+    if !n.dirty { return n.world }
+    let parent = match n.parent {
+      Some(parent) => self.current_world(parent),
+      None => (identity(), 1.0),
+    };
+    place(n, parent)
+  }
+
+  /// Place a node in the world, by changing its local transform.
+  pub fn set_world(&mut self, node:NodeRef, world:g3::Motor) {
+    let (parent, scale) = match self.node(node).parent {
+      Some(parent) => self.world(parent),
+      None => (identity(), 1.0),
+    };
+    let local = parent.reverse() * world;
+    let offset = transform_point(&local, Vec3::ZERO);
+    self.set_transform(node, translation(offset / scale - offset) * local);
+  }
+
+  /// Bring the world transforms of all nodes up to date.
+  pub fn update(&mut self) {
+    for node in self.iter().collect::<Vec<_>>() { self.world(node); }
+  }
+
+  /// Nodes without a parent.
+  pub fn roots(&self)->&[NodeRef] { &self.roots }
+
+  /// All nodes, each parent before its children.
+  pub fn iter(&self)->impl Iterator<Item = NodeRef> + '_ {
+    self.roots.iter().flat_map(move |&root| self.descendants(root))
+  }
+
+  /// A node and everything below it, each parent before its children.
+  pub fn descendants(&self, node:NodeRef)->Vec<NodeRef> {
+    let (mut stack, mut nodes) = (vec![node], vec![]);
+    while let Some(n) = stack.pop() {
+      nodes.push(n);
+      stack.extend(self.node(n).children.iter().rev().copied());
+    }
+    nodes
+  }
+
+  /// The parent of a node, its parent and so on up to a root.
+  pub fn ancestors(&self, node:NodeRef)->impl Iterator<Item = NodeRef> + '_ {
+    std::iter::successors(self.node(node).parent, move |&n| self.node(n).parent)
+  }
+
+  /// Each of the nodes once, leaving out those below another one of them, so moving the ones left moves every node once.
+  pub fn topmost(&self, nodes:&[NodeRef])->Vec<NodeRef> {
+    let mut top:Vec<NodeRef> = vec![];
+    for &node in nodes {
+      if !top.contains(&node) && !self.ancestors(node).any(|a| nodes.contains(&a)) { top.push(node) }
+    }
+    top
+  }
+
+  /// The first node with a name.
+  pub fn find(&self, name:&str)->Option<NodeRef> { self.iter().find(|&n| self.node(n).name == name) }
+
+  /// The node an object is attached to.
+  pub fn node_of(&self, object:ObjectRef)->Option<NodeRef> {
+    self.iter().find(|&n| self.node(n).attachments.contains(&Attachment::Object(object)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn position(graph:&mut Graph, node:NodeRef)->Vec3 { transform_point(&graph.world(node).0, Vec3::ZERO) }
+
+  #[test] fn hierarchy() {
+    let mut graph = Graph::new();
+    let root = graph.add("root", None);
+    let arm = graph.add("arm", Some(root));
+    let hand = graph.add("hand", Some(arm));
+    graph.set_transform(arm, translation(Vec3::X));
+    graph.set_transform(hand, translation(Vec3::Y));
+    assert!(position(&mut graph, hand).abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-5));
+    // Moving the parent moves the children.
+    graph.set_transform(root, translation(Vec3::Z * 2.0));
+    assert!(position(&mut graph, hand).abs_diff_eq(Vec3::new(1.0, 1.0, 2.0), 1e-5));
+    // Turning it a quarter about z swings them around, scaling it stretches the offsets.
+    graph.set_transform(root, g3::rotor(std::f32::consts::FRAC_PI_2, 0.0, 0.0, 1.0).into());
+    graph.set_scale(root, 2.0);
+    assert!(position(&mut graph, hand).abs_diff_eq(Vec3::new(-2.0, 2.0, 0.0), 1e-4));
+    assert_eq!(graph.world(hand).1, 2.0);
+    assert_eq!(graph.iter().collect::<Vec<_>>(), vec![root, arm, hand]);
+    assert_eq!(graph.ancestors(hand).collect::<Vec<_>>(), vec![arm, root]);
+    let other = graph.add("other", None);
+    assert_eq!(graph.topmost(&[hand, other, arm, hand, other]), vec![other, arm]);
+    assert_eq!(graph.find("hand"), Some(hand));
+  }
+
+  #[test] fn reparent() {
+    let mut graph = Graph::new();
+    let (a, b) = (graph.add("a", None), graph.add("b", None));
+    let child = graph.add("child", Some(a));
+    graph.set_transform(b, translation(Vec3::X * 3.0));
+    graph.set_transform(child, translation(Vec3::Y));
+    assert!(position(&mut graph, child).abs_diff_eq(Vec3::Y, 1e-5));
+    graph.set_parent(child, Some(b));
+    assert!(position(&mut graph, child).abs_diff_eq(Vec3::new(3.0, 1.0, 0.0), 1e-5));
+    // Placing it in the world keeps it there under a scaled parent.
+    graph.set_scale(b, 2.0);
+    graph.set_world(child, translation(Vec3::new(1.0, 1.0, 1.0)));
+    assert!(position(&mut graph, child).abs_diff_eq(Vec3::new(1.0, 1.0, 1.0), 1e-5));
+    graph.remove(b);
+    assert!(graph.get(child).is_none() && graph.roots() == [a]);
+    let reused = graph.add("again", None);
+    assert!(graph.get(reused).is_some() && graph.node(a).children().is_empty());
+    // The new node took a removed node's slot but the old references don't find it.
+    assert!(reused.0 == b.0 || reused.0 == child.0);
+    assert!(graph.get(b).is_none() && graph.get(child).is_none());
+  }
+}
@@ -0,0 +1,281 @@
+//! Keyboard, mouse and touch input, translated from winit so apps don't depend on its event types.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+pub use winit::event::{ModifiersState, MouseButton, TouchPhase, VirtualKeyCode as Key};
+use winit::event::{ElementState, KeyboardInput, MouseScrollDelta, WindowEvent};
+
+/// Pixels per line of a wheel that scrolls by pixels, to report both kinds in lines.
+const PIXELS_PER_LINE:f32 = 20.0;
+
+/// Something the user did in the window. Positions are in physical pixels from the top left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+  Key{key:Key, pressed:bool},
+  /// Text typed, after the keyboard layout and modifiers are applied.
+  Character(char),
+  Modifiers(ModifiersState),
+  MouseButton{button:MouseButton, pressed:bool},
+  CursorMoved{position:[f32;2]},
+  CursorLeft,
+  /// Scrolled lines, positive `y` is away from the user.
+  Wheel{delta:[f32;2]},
+  Touch{id:u64, phase:TouchPhase, position:[f32;2]},
+  Resized{width:u32, height:u32},
+  Focused(bool),
+}
+
+impl InputEvent {
+  /// The input in a window event, if it has any.
+  pub fn from_window_event(event:&WindowEvent)->Option<InputEvent> {
+    let pressed = |state:&ElementState| *state == ElementState::Pressed;
+    Some(match event {
+      WindowEvent::KeyboardInput{input: KeyboardInput{state, virtual_keycode: Some(key), ..}, ..} => InputEvent::Key{key: *key, pressed: pressed(state)},
+      WindowEvent::ReceivedCharacter(c) => InputEvent::Character(*c),
+      WindowEvent::ModifiersChanged(modifiers) => InputEvent::Modifiers(*modifiers),
+      WindowEvent::MouseInput{state, button, ..} => InputEvent::MouseButton{button: *button, pressed: pressed(state)},
+      WindowEvent::CursorMoved{position, ..} => InputEvent::CursorMoved{position: [position.x as f32, position.y as f32]},
+      WindowEvent::CursorLeft{..} => InputEvent::CursorLeft,
+      WindowEvent::MouseWheel{delta, ..} => InputEvent::Wheel{delta: match delta {
+        MouseScrollDelta::LineDelta(x, y) => [*x, *y],
+        MouseScrollDelta::PixelDelta(p) => [p.x as f32 / PIXELS_PER_LINE, p.y as f32 / PIXELS_PER_LINE],
+      }},
+      WindowEvent::Touch(touch) => InputEvent::Touch{id: touch.id, phase: touch.phase, position: [touch.location.x as f32, touch.location.y as f32]},
+      WindowEvent::Resized(size) => InputEvent::Resized{width: size.width, height: size.height},
+      WindowEvent::ScaleFactorChanged{new_inner_size, ..} => InputEvent::Resized{width: new_inner_size.width, height: new_inner_size.height},
+      WindowEvent::Focused(focused) => InputEvent::Focused(*focused),
+      _ => return None,
+    })
+  }
+
+  /// The key or mouse button of the event and whether it went down.
+  pub fn button(&self)->Option<(Button, bool)> {
+    match *self {
+      InputEvent::Key{key, pressed} => Some((Button::Key(key), pressed)),
+      InputEvent::MouseButton{button, pressed} => Some((Button::Mouse(button), pressed)),
+      _ => None,
+    }
+  }
+}
+
+/// A key or mouse button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+  Key(Key),
+  Mouse(MouseButton),
+}
+
+impl From<Key> for Button {
+  fn from(key:Key) -> Self { Button::Key(key) }
+}
+
+impl From<MouseButton> for Button {
+  fn from(button:MouseButton) -> Self { Button::Mouse(button) }
+}
+
+/// A button pressed while holding modifiers, other modifiers may be held as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Combo {
+  pub button: Button,
+  pub modifiers: ModifiersState,
+}
+
+impl Combo {
+  pub fn new(button:impl Into<Button>)->Combo { Combo{button: button.into(), modifiers: ModifiersState::empty()} }
+
+  pub fn ctrl(mut self)->Combo { self.modifiers |= ModifiersState::CTRL; self }
+
+  pub fn shift(mut self)->Combo { self.modifiers |= ModifiersState::SHIFT; self }
+
+  pub fn alt(mut self)->Combo { self.modifiers |= ModifiersState::ALT; self }
+
+  /// The command key on macOS and the windows key elsewhere.
+  pub fn logo(mut self)->Combo { self.modifiers |= ModifiersState::LOGO; self }
+}
+
+impl<B:Into<Button>> From<B> for Combo {
+  fn from(button:B) -> Self { Combo::new(button) }
+}
+
+/// What is held down and what changed since the last frame, kept up to date from [`InputEvent`]s.
+/// The `just_` queries cover a whole frame, so ask them when rendering rather than in fixed updates.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+  down: HashSet<Button>,
+  pressed: HashSet<Button>,
+  released: HashSet<Button>,
+  modifiers: ModifiersState,
+  cursor: Option<[f32;2]>,
+  cursor_delta: [f32;2],
+  wheel: [f32;2],
+  touches: BTreeMap<u64, [f32;2]>,
+  size: [u32;2],
+  text: String,
+  bindings: HashMap<String, Vec<Combo>>,
+}
+
+impl Input {
+  pub fn new(width:u32, height:u32)->Input { Input{size: [width, height], ..Default::default()} }
+
+  pub fn handle(&mut self, event:&InputEvent) {
+    match *event {
+      InputEvent::Key{key, pressed} => self.set(Button::Key(key), pressed),
+      InputEvent::MouseButton{button, pressed} => self.set(Button::Mouse(button), pressed),
+      InputEvent::Character(c) => self.text.push(c),
+      InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+      InputEvent::CursorMoved{position} => {
+        if let Some(last) = self.cursor {
+          self.cursor_delta = [self.cursor_delta[0] + position[0] - last[0], self.cursor_delta[1] + position[1] - last[1]];
+        }
+        self.cursor = Some(position);
+      }
+      InputEvent::CursorLeft => self.cursor = None,
+      InputEvent::Wheel{delta} => self.wheel = [self.wheel[0] + delta[0], self.wheel[1] + delta[1]],
+      InputEvent::Touch{id, phase, position} => match phase {
+        TouchPhase::Started | TouchPhase::Moved => { self.touches.insert(id, position); }
+        TouchPhase::Ended | TouchPhase::Cancelled => { self.touches.remove(&id); }
+      }
+      InputEvent::Resized{width, height} => self.size = [width, height],
+      // Releases that happen in another window never arrive.
+      InputEvent::Focused(false) => {
+        self.released.extend(self.down.drain());
+        self.modifiers = ModifiersState::empty();
+      }
+      InputEvent::Focused(true) => {}
+    }
+  }
+
+  fn set(&mut self, button:Button, down:bool) {
+    if down {
+      // Key repeat sends more presses without releases.
+      if self.down.insert(button) { self.pressed.insert(button); }
+    } else if self.down.remove(&button) {
+      self.released.insert(button);
+    }
+  }
+
+  /// Forget what happened during the frame, call once a frame has been drawn.
+  pub fn end_frame(&mut self) {
+    self.pressed.clear();
+    self.released.clear();
+    self.cursor_delta = [0.0; 2];
+    self.wheel = [0.0; 2];
+    self.text.clear();
+  }
+
+  /// Whether a key or mouse button is held down.
+  pub fn down(&self, button:impl Into<Button>)->bool { self.down.contains(&button.into()) }
+
+  /// Whether a key or mouse button went down during this frame.
+  pub fn just_pressed(&self, button:impl Into<Button>)->bool { self.pressed.contains(&button.into()) }
+
+  /// Whether a key or mouse button was let go during this frame.
+  pub fn just_released(&self, button:impl Into<Button>)->bool { self.released.contains(&button.into()) }
+
+  pub fn modifiers(&self)->ModifiersState { self.modifiers }
+
+  /// Cursor position in physical pixels from the top left, while it is over the window.
+  pub fn cursor(&self)->Option<[f32;2]> { self.cursor }
+
+  /// Cursor position in normalized device coordinates, from -1 to 1 with y up.
+  pub fn cursor_ndc(&self)->Option<[f32;2]> {
+    let [w, h] = self.size.map(|s| s.max(1) as f32);
+    self.cursor.map(|[x, y]| [2.0 * x / w - 1.0, 1.0 - 2.0 * y / h])
+  }
+
+  /// How far the cursor moved during this frame, in physical pixels.
+  pub fn cursor_delta(&self)->[f32;2] { self.cursor_delta }
+
+  /// Lines scrolled during this frame.
+  pub fn wheel(&self)->[f32;2] { self.wheel }
+
+  /// Text typed during this frame.
+  pub fn text(&self)->&str { &self.text }
+
+  /// Positions of the fingers on the screen by touch id, in physical pixels.
+  pub fn touches(&self)->&BTreeMap<u64, [f32;2]> { &self.touches }
+
+  /// Window size in physical pixels.
+  pub fn size(&self)->[u32;2] { self.size }
+
+  /// Trigger a named action with a combo, in addition to those it already has.
+  pub fn bind(&mut self, action:&str, combo:impl Into<Combo>) {
+    self.bindings.entry(action.to_string()).or_default().push(combo.into());
+  }
+
+  /// Remove every combo of an action.
+  pub fn unbind(&mut self, action:&str) { self.bindings.remove(action); }
+
+  pub fn bindings(&self, action:&str)->&[Combo] { self.bindings.get(action).map_or(&[], |b| b.as_slice()) }
+
+  fn combo(&self, action:&str, test:impl Fn(Button)->bool)->bool {
+    self.bindings(action).iter().any(|c| test(c.button) && self.modifiers.contains(c.modifiers))
+  }
+
+  /// Whether any combo of an action is held down.
+  pub fn action_down(&self, action:&str)->bool { self.combo(action, |b| self.down(b)) }
+
+  /// Whether any combo of an action was completed during this frame.
+  pub fn action_pressed(&self, action:&str)->bool { self.combo(action, |b| self.just_pressed(b)) }
+
+  /// Whether the button of any combo of an action was let go during this frame.
+  pub fn action_released(&self, action:&str)->bool { self.bindings(action).iter().any(|c| self.just_released(c.button)) }
+
+  /// Whether an event, already handled, presses the button of a combo of an action while its modifiers are held.
+  pub fn pressed_by(&self, action:&str, event:&InputEvent)->bool {
+    match event.button() { Some((button, true)) => self.combo(action, |b| b == button), _ => false }
+  }
+
+  /// Whether an event lets go of the button of any combo of an action.
+  pub fn released_by(&self, action:&str, event:&InputEvent)->bool {
+    match event.button() { Some((button, false)) => self.bindings(action).iter().any(|c| c.button == button), _ => false }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn window_events() {
+    let resized = WindowEvent::Resized(winit::dpi::PhysicalSize::new(640, 480));
+    assert_eq!(InputEvent::from_window_event(&resized), Some(InputEvent::Resized{width: 640, height: 480}));
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::ReceivedCharacter('a')), Some(InputEvent::Character('a')));
+    assert_eq!(InputEvent::from_window_event(&WindowEvent::CloseRequested), None);
+  }
+
+  #[test] fn state() {
+    let mut input = Input::new(200, 100);
+    input.handle(&InputEvent::Key{key: Key::A, pressed: true});
+    input.handle(&InputEvent::Key{key: Key::A, pressed: true});
+    input.handle(&InputEvent::CursorMoved{position: [50.0, 25.0]});
+    input.handle(&InputEvent::CursorMoved{position: [150.0, 75.0]});
+    assert!(input.down(Key::A) && input.just_pressed(Key::A));
+    assert_eq!(input.cursor_ndc(), Some([0.5, -0.5]));
+    assert_eq!(input.cursor_delta(), [100.0, 50.0]);
+    input.end_frame();
+    assert!(input.down(Key::A) && !input.just_pressed(Key::A));
+    input.handle(&InputEvent::Key{key: Key::A, pressed: false});
+    input.handle(&InputEvent::MouseButton{button: MouseButton::Left, pressed: true});
+    input.handle(&InputEvent::Focused(false));
+    assert!(input.just_released(Key::A) && input.just_released(MouseButton::Left) && !input.down(MouseButton::Left));
+  }
+
+  #[test] fn bindings() {
+    let mut input = Input::default();
+    input.bind("save", Combo::new(Key::S).ctrl());
+    input.bind("jump", Key::Space);
+    input.bind("jump", MouseButton::Right);
+    input.handle(&InputEvent::Key{key: Key::S, pressed: true});
+    assert!(!input.action_pressed("save"));
+    input.handle(&InputEvent::Modifiers(ModifiersState::CTRL | ModifiersState::SHIFT));
+    assert!(input.action_pressed("save") && input.action_down("save"));
+    input.handle(&InputEvent::MouseButton{button: MouseButton::Right, pressed: true});
+    assert!(input.action_down("jump"));
+    input.unbind("jump");
+    assert!(!input.action_down("jump") && !input.action_down("unknown"));
+    let press = InputEvent::Key{key: Key::S, pressed: true};
+    assert!(input.pressed_by("save", &press) && !input.released_by("save", &press));
+    assert!(input.released_by("save", &InputEvent::Key{key: Key::S, pressed: false}));
+    input.handle(&InputEvent::Modifiers(ModifiersState::empty()));
+    assert!(!input.pressed_by("save", &press));
+  }
+}
@@ -7,15 +7,40 @@
 #[cfg(target_arch="wasm32")]
 use wasm_bindgen::prelude::*;
 
+mod app;
 mod pass;
 mod context;
 mod color;
 mod mesh;
-
-pub use color::Color;
-pub use context::{Window, Cx};
-
-use winit::{event::{Event,WindowEvent},event_loop::ControlFlow};
+mod texture;
+mod scene;
+mod time;
+mod input;
+mod gizmo;
+mod graph;
+mod file;
+mod debug;
+mod text;
+mod ui;
+pub mod physics;
+pub mod collision;
+
+pub use color::{Color, ColorSpace, Colormap, Gradient, Hsl, Hsv, Oklab, Oklch, ParseColorError, NAMED_COLORS};
+pub use context::{Window, Cx, Camera, Target, TargetInfo, TargetRef};
+pub use pass::{Background, Cap, Grid, Join, LineStyle, Lines, PointShape, SkyRef};
+pub use mesh::{Mesh, MeshRef, Vertex};
+pub use texture::{CompressedImage, Image, Pixels, SamplerConfig, Texture, TextureRef};
+pub use scene::{Hit, Material, Object, ObjectRef, Selection};
+pub use time::{Interpolated, Lerp, Time};
+pub use input::{Button, Combo, Input, InputEvent, Key, ModifiersState, MouseButton, TouchPhase};
+pub use app::{run_app, run_with, App};
+pub use gizmo::{Delta, Gizmo, GizmoMode, Handle, Snap};
+pub use graph::{Attachment, Graph, Light, Node, NodeRef};
+pub use debug::DebugDraw;
+pub use text::{Align, Anchor, Font, FontRef, Label, TextStyle};
+pub use ui::{Panels, Ui};
+pub use egui;
+pub use file::{BackgroundData, CameraData, MaterialData, MeshData, MeshSource, NodeData, ObjectData, SceneFile};
 
 fn logging() {
   cfg_if::cfg_if! {
@@ -28,37 +53,7 @@ fn logging() {
   }
 }
 
-struct App {
-  // pub event_loop: winit::event_loop::EventLoop<()>,
-  pub window:Window,
-  pub cx: Cx
-}
-
-impl App {
-  async fn new()->App {
-    logging();
-    let window = Window::new();
-    let cx = Cx::new(&window).await;
-    App{window,cx}
-  }
-}
-
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub async fn run() {
-  let App{window,mut cx} = App::new().await;
-  window.event_loop.run(move |e, _, control_flow| {
-    match e {
-      Event::RedrawRequested(window_id) if (window_id==window.window.id()) => { cx.render() }
-      Event::MainEventsCleared => { window.window.request_redraw() }
-      Event::WindowEvent{ref event,window_id} if (window_id==window.window.id()) => {
-        match event {
-          WindowEvent::CloseRequested => {*control_flow = ControlFlow::Exit},
-          WindowEvent::Resized(physical_size) => { cx.resize(physical_size.width, physical_size.height); }
-          WindowEvent::ScaleFactorChanged{new_inner_size, ..} => { cx.resize(new_inner_size.width, new_inner_size.height); }
-          _ => {}
-        }
-      }
-      _ => {}
-    }
-  });
+  run_with(|_, _| {}, |_, _| {}).await
 }
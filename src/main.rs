@@ -1,3 +1,32 @@
+use mirror::{App, Cx};
+
+/// Shows a scene file, `mirror --scene <path>`.
+struct Viewer {
+  scene: std::path::PathBuf,
+}
+
+impl App for Viewer {
+  fn init(&mut self, cx:&mut Cx) {
+    if let Err(e) = cx.load_scene(&self.scene) { eprintln!("couldn't load {}: {}", self.scene.display(), e) }
+  }
+}
+
+fn usage()->! {
+  eprintln!("usage: mirror [--scene <path>]");
+  std::process::exit(2)
+}
+
 fn main() {
-  pollster::block_on(mirror::run());
+  let mut args = std::env::args().skip(1);
+  let mut scene = None;
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--scene" => scene = Some(args.next().unwrap_or_else(|| usage())),
+      _ => usage(),
+    }
+  }
+  match scene {
+    Some(path) => mirror::run_app(Viewer{scene: path.into()}),
+    None => pollster::block_on(mirror::run()),
+  }
 }
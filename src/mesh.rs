@@ -1,24 +1,25 @@
 use g3::{Point,Plane,point,E2};
 use glam::{Vec3};
+use wgpu::util::DeviceExt;
+use crate::color::{Color, Colormap};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
   position:[f32;3],
+  normal:[f32;3],
+  uv:[f32;2],
+  /// Linear color multiplied with the material.
+  color:[f32;4],
 }
 
 impl Vertex {
   pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute;4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Float32x4];
     wgpu::VertexBufferLayout {
       array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
       step_mode: wgpu::VertexStepMode::Vertex,
-      attributes: &[
-        wgpu::VertexAttribute {offset: 0, shader_location: 0, format: wgpu::VertexFormat::Float32x3, },
-        wgpu::VertexAttribute {
-          offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-          shader_location: 1, format: wgpu::VertexFormat::Float32x3,
-        },
-      ],
+      attributes: &ATTRIBUTES,
     }
   }
 }
@@ -34,22 +35,107 @@ pub fn create_plane_mesh(p:Plane)-> Mesh {
   let vertices:Vec<[f32;3]> = vec!(a.into(), b.into(), c.into(), d.into());
   let indices = vec!(0u32, 2, 1, 0, 3, 2, 2, 3, 0, 1, 2, 0);
   // normals??
-  Mesh{vertices,indices}
+  let uvs = vec!([0.0,0.0], [0.0,1.0], [1.0,1.0], [1.0,0.0]);
+  Mesh{vertices,indices,colors:vec![],uvs}
 }
 
 pub fn demo_mesh()->Mesh {
   let vertices = vec!([-0.0868241, 0.49240386, 0.0], [-0.49513406, 0.06958647, 0.0], [-0.21918549, -0.44939706, 0.0], [0.35966998, -0.3473291, 0.0], [0.44147372, 0.2347359, 0.0]);
   let indices = vec!(0, 1, 4, 1, 2, 4, 2, 3, 4);
-  Mesh{vertices,indices}
+  Mesh{vertices,indices,colors:vec![],uvs:vec![]}
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Mesh {
   pub vertices:Vec<[f32;3]>,
-  pub indices:Vec<u32>
+  pub indices:Vec<u32>,
+  /// Optional color per vertex.
+  pub colors:Vec<Color>,
+  /// Optional texture coordinate per vertex.
+  pub uvs:Vec<[f32;2]>,
 }
 
 impl Mesh {
+  /// Normal of every vertex, averaged over the faces that share it and weighted by their area.
+  pub fn normals(&self)->Vec<[f32;3]> {
+    let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+    for t in self.indices.chunks_exact(3) {
+      let [a, b, c] = [t[0], t[1], t[2]].map(|i| Vec3::from(self.vertices[i as usize]));
+      let n = (b - a).cross(c - a);
+      for i in t { normals[*i as usize] += n; }
+    }
+    normals.iter().map(|n| n.normalize_or_zero().into()).collect()
+  }
+
+  /// Interleave the attributes for upload, missing colors are white and missing uvs zero.
+  pub fn vertex_data(&self)->Vec<Vertex> {
+    let normals = self.normals();
+    (0..self.vertices.len()).map(|i| Vertex {
+      position: self.vertices[i],
+      normal: normals[i],
+      uv: self.uvs.get(i).copied().unwrap_or([0.0, 0.0]),
+      color: self.colors.get(i).map_or([1.0;4], Color::to_linear),
+    }).collect()
+  }
+
+  pub fn upload(&self, device:&wgpu::Device)->MeshBuffers {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Vertex Buffer"),
+      contents: bytemuck::cast_slice(&self.vertex_data()),
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST});
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Index Buffer"),
+      contents: bytemuck::cast_slice(&self.indices),
+      usage: wgpu::BufferUsages::INDEX});
+    MeshBuffers{vertex_buffer, index_buffer, num_indices: self.indices.len() as u32}
+  }
+
+  /// Box centered on the origin, each face has its own vertices so that it is shaded flat.
+  pub fn cuboid(half_extents:[f32;3])->Mesh {
+    let h = Vec3::from(half_extents);
+    let (mut vertices, mut indices, mut uvs) = (vec![], vec![], vec![]);
+    for axis in 0..3 {
+      for sign in [1.0, -1.0] {
+        let n = Vec3::AXES[axis] * sign;
+        let (u, v) = (Vec3::AXES[(axis + 1) % 3], Vec3::AXES[(axis + 2) % 3] * sign);
+        let first = vertices.len() as u32;
+        for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+          vertices.push(((n + u * a + v * b) * h).into());
+          uvs.push([(a + 1.0) / 2.0, (1.0 - b) / 2.0]);
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+      }
+    }
+    Mesh{vertices, indices, colors: vec![], uvs}
+  }
+
+  /// Flat rectangle in the xz-plane facing up, split into quads that share their vertices, like a cloth.
+  pub fn grid(size:[f32;2], divisions:[u32;2])->Mesh {
+    let [nx, nz] = divisions.map(|d| d.max(1));
+    let (mut vertices, mut indices, mut uvs) = (vec![], vec![], vec![]);
+    for z in 0..=nz {
+      for x in 0..=nx {
+        let (u, v) = (x as f32 / nx as f32, z as f32 / nz as f32);
+        vertices.push([(u - 0.5) * size[0], 0.0, (v - 0.5) * size[1]]);
+        uvs.push([u, v]);
+      }
+    }
+    for z in 0..nz {
+      for x in 0..nx {
+        let a = x + (nx + 1) * z;
+        let (b, c, d) = (a + nx + 1, a + nx + 2, a + 1);
+        indices.extend([a, b, c, a, c, d]);
+      }
+    }
+    Mesh{vertices, indices, colors: vec![], uvs}
+  }
+
+  /// Color each vertex by mapping one scalar per vertex through a colormap.
+  pub fn color_by(&mut self, values:&[f32], map:&Colormap) {
+    assert_eq!(values.len(), self.vertices.len(), "expected one value per vertex");
+    self.colors = map.map_all(values);
+  }
+
   pub fn new(points:Vec<Point>)->MeshBuilder {
     MeshBuilder{positions:vec![]}
   }
@@ -66,6 +152,16 @@ impl MeshBuilder {
   // }
 }
 
+/// A mesh uploaded to the GPU.
+pub struct MeshBuffers {
+  pub vertex_buffer: wgpu::Buffer,
+  pub index_buffer: wgpu::Buffer,
+  pub num_indices: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshRef(pub(crate) u16);
+
 pub struct Geometry {
 
 }
@@ -0,0 +1,266 @@
+use std::num::NonZeroU32;
+use wgpu::util::DeviceExt;
+use crate::Color;
+use crate::context::{TargetInfo, DEPTH_FORMAT};
+use crate::texture::f32_to_f16;
+
+/// What is visible behind the geometry of a camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Background {
+  /// Clear to a single color.
+  Color(Color),
+  /// Blend from `bottom` to `top` depending on how far the view direction points up.
+  Gradient{top:Color, bottom:Color},
+  /// A cubemap or equirectangular environment added with [`crate::Cx::add_skybox`] or [`crate::Cx::add_environment`].
+  Sky(SkyRef),
+}
+
+impl Default for Background {
+  fn default() -> Self { Background::Color(Color::BLACK) }
+}
+
+impl From<Color> for Background {
+  fn from(color:Color) -> Self { Background::Color(color) }
+}
+
+impl Background {
+  /// Color a target of the given format is cleared to before the background pass runs.
+  pub fn clear_color(&self, format:wgpu::TextureFormat)->wgpu::Color {
+    match self {
+      Background::Color(color) => color.to_wgpu(format),
+      Background::Gradient{bottom, ..} => bottom.to_wgpu(format),
+      Background::Sky(_) => Color::BLACK.to_wgpu(format),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyRef(u16);
+
+/// Fail for textures larger than the device supports, or empty ones.
+fn check_size(device:&wgpu::Device, width:u32, height:u32)->anyhow::Result<()> {
+  let max = device.limits().max_texture_dimension_2d;
+  if width == 0 || height == 0 || width > max || height > max {
+    anyhow::bail!("A sky of {}x{} is outside the supported size of 1 to {}", width, height, max)
+  }
+  Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SkyKind { Cubemap, Equirect }
+
+struct SkyTexture {
+  kind: SkyKind,
+  bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+  inv_view_proj: [[f32;4];4],
+  top: [f32;4],
+  bottom: [f32;4],
+  // Non-zero when the target does not encode sRGB on write.
+  encode_srgb: u32,
+  _padding: [u32;3],
+}
+
+struct Pipelines {
+  gradient: wgpu::RenderPipeline,
+  cubemap: wgpu::RenderPipeline,
+  equirect: wgpu::RenderPipeline,
+}
+
+/// Draws gradients and environment maps behind the scene with a full screen triangle.
+pub struct Sky {
+  shader: wgpu::ShaderModule,
+  uniform_buffer: wgpu::Buffer,
+  uniform_bind_group: wgpu::BindGroup,
+  cubemap_layout: wgpu::BindGroupLayout,
+  equirect_layout: wgpu::BindGroupLayout,
+  gradient_pipeline_layout: wgpu::PipelineLayout,
+  cubemap_pipeline_layout: wgpu::PipelineLayout,
+  equirect_pipeline_layout: wgpu::PipelineLayout,
+  sampler: wgpu::Sampler,
+  textures: Vec<SkyTexture>,
+  format: wgpu::TextureFormat,
+  sample_count: u32,
+  pipelines: Pipelines,
+}
+
+fn texture_layout(device:&wgpu::Device, view_dimension:wgpu::TextureViewDimension)->wgpu::BindGroupLayout {
+  device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    label: Some("sky texture"),
+    entries: &[
+      wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+          sample_type: wgpu::TextureSampleType::Float{filterable: true},
+          view_dimension, multisampled: false}},
+      wgpu::BindGroupLayoutEntry {
+        binding: 1, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)}]})
+}
+
+impl Sky {
+  pub fn new(device:&wgpu::Device, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Background Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("background.wgsl")).into()),
+    });
+    let uniform = SkyUniform{inv_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(), top: Color::BLACK.to_linear(), bottom: Color::BLACK.to_linear(), encode_srgb: 0, _padding: [0;3]};
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Sky Buffer"),
+      contents: bytemuck::bytes_of(&uniform),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST});
+    let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("sky uniform"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None}}]});
+    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("sky uniform"), layout: &uniform_layout,
+      entries: &[wgpu::BindGroupEntry {binding: 0, resource: uniform_buffer.as_entire_binding()}]});
+    let cubemap_layout = texture_layout(device, wgpu::TextureViewDimension::Cube);
+    let equirect_layout = texture_layout(device, wgpu::TextureViewDimension::D2);
+    let pipeline_layout = |layouts:&[&wgpu::BindGroupLayout]| device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Background Pipeline Layout"), bind_group_layouts: layouts, push_constant_ranges: &[]});
+    let gradient_pipeline_layout = pipeline_layout(&[&uniform_layout]);
+    let cubemap_pipeline_layout = pipeline_layout(&[&uniform_layout, &cubemap_layout]);
+    let equirect_pipeline_layout = pipeline_layout(&[&uniform_layout, &equirect_layout]);
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("sky"),
+      address_mode_u: wgpu::AddressMode::Repeat,
+      address_mode_v: wgpu::AddressMode::ClampToEdge,
+      address_mode_w: wgpu::AddressMode::ClampToEdge,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()});
+    let pipelines = Pipelines {
+      gradient: create_pipeline(device, &gradient_pipeline_layout, &shader, "fs_gradient", info),
+      cubemap: create_pipeline(device, &cubemap_pipeline_layout, &shader, "fs_cubemap", info),
+      equirect: create_pipeline(device, &equirect_pipeline_layout, &shader, "fs_equirect", info),
+    };
+    Sky{shader, uniform_buffer, uniform_bind_group, cubemap_layout, equirect_layout,
+      gradient_pipeline_layout, cubemap_pipeline_layout, equirect_pipeline_layout, sampler,
+      textures: Vec::new(), format: info.format, sample_count: info.sample_count, pipelines}
+  }
+
+  /// Make sure the pipelines are compatible with the target that is about to be drawn.
+  pub fn prepare(&mut self, device:&wgpu::Device, info:&TargetInfo) {
+    if (self.format, self.sample_count) == (info.format, info.sample_count) { return }
+    self.pipelines = Pipelines {
+      gradient: create_pipeline(device, &self.gradient_pipeline_layout, &self.shader, "fs_gradient", info),
+      cubemap: create_pipeline(device, &self.cubemap_pipeline_layout, &self.shader, "fs_cubemap", info),
+      equirect: create_pipeline(device, &self.equirect_pipeline_layout, &self.shader, "fs_equirect", info),
+    };
+    self.format = info.format;
+    self.sample_count = info.sample_count;
+  }
+
+  /// Upload six square RGBA8 faces in the order +X, -X, +Y, -Y, +Z, -Z.
+  pub fn add_cubemap(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, size:u32, faces:[&[u8];6])->anyhow::Result<SkyRef> {
+    check_size(device, size, size)?;
+    if let Some(face) = faces.iter().find(|f| f.len() != (4 * size * size) as usize) {
+      anyhow::bail!("A cubemap face of {}x{} needs {} bytes, got {}", size, size, 4 * size * size, face.len())
+    }
+    let extent = wgpu::Extent3d{width: size, height: size, depth_or_array_layers: 6};
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Skybox"), size: extent, mip_level_count: 1, sample_count: 1,
+      dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Rgba8UnormSrgb,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST});
+    for (layer, face) in faces.iter().enumerate() {
+      queue.write_texture(
+        wgpu::ImageCopyTexture{texture: &texture, mip_level: 0, origin: wgpu::Origin3d{x: 0, y: 0, z: layer as u32}, aspect: wgpu::TextureAspect::All},
+        face,
+        wgpu::ImageDataLayout{offset: 0, bytes_per_row: NonZeroU32::new(4 * size), rows_per_image: NonZeroU32::new(size)},
+        wgpu::Extent3d{width: size, height: size, depth_or_array_layers: 1});
+    }
+    let view = texture.create_view(&wgpu::TextureViewDescriptor{dimension: Some(wgpu::TextureViewDimension::Cube), ..Default::default()});
+    self.add_texture(device, SkyKind::Cubemap, &view)
+  }
+
+  /// Upload an equirectangular high dynamic range environment given as linear RGBA floats.
+  pub fn add_equirect(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, width:u32, height:u32, pixels:&[f32])->anyhow::Result<SkyRef> {
+    check_size(device, width, height)?;
+    if pixels.len() != (4 * width * height) as usize {
+      anyhow::bail!("An environment of {}x{} needs {} floats, got {}", width, height, 4 * width * height, pixels.len())
+    }
+    let extent = wgpu::Extent3d{width, height, depth_or_array_layers: 1};
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Environment"), size: extent, mip_level_count: 1, sample_count: 1,
+      dimension: wgpu::TextureDimension::D2, format: wgpu::TextureFormat::Rgba16Float,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST});
+    let halfs:Vec<u16> = pixels.iter().map(|&f| f32_to_f16(f)).collect();
+    queue.write_texture(
+      texture.as_image_copy(),
+      bytemuck::cast_slice(&halfs),
+      wgpu::ImageDataLayout{offset: 0, bytes_per_row: NonZeroU32::new(8 * width), rows_per_image: None},
+      extent);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    self.add_texture(device, SkyKind::Equirect, &view)
+  }
+
+  fn add_texture(&mut self, device:&wgpu::Device, kind:SkyKind, view:&wgpu::TextureView)->anyhow::Result<SkyRef> {
+    let sky = u16::try_from(self.textures.len()).map_err(|_| anyhow::anyhow!("Too many skies"))?;
+    let layout = match kind { SkyKind::Cubemap => &self.cubemap_layout, SkyKind::Equirect => &self.equirect_layout };
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("sky texture"), layout,
+      entries: &[
+        wgpu::BindGroupEntry {binding: 0, resource: wgpu::BindingResource::TextureView(view)},
+        wgpu::BindGroupEntry {binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler)}]});
+    self.textures.push(SkyTexture{kind, bind_group});
+    Ok(SkyRef(sky))
+  }
+
+  /// Upload the camera and colors used by [`Sky::draw`].
+  pub fn update(&self, queue:&wgpu::Queue, background:&Background, view_proj:glam::Mat4, info:&TargetInfo) {
+    let (top, bottom) = match background {
+      Background::Gradient{top, bottom} => (top.to_linear(), bottom.to_linear()),
+      _ => (Color::BLACK.to_linear(), Color::BLACK.to_linear()),
+    };
+    let encode_srgb = !info.format.describe().srgb as u32;
+    let uniform = SkyUniform{inv_view_proj: view_proj.inverse().to_cols_array_2d(), top, bottom, encode_srgb, _padding: [0;3]};
+    queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+  }
+
+  /// Draw the background, a plain color is already taken care of by clearing the target.
+  pub fn draw<'a>(&'a self, pass:&mut wgpu::RenderPass<'a>, background:&Background) {
+    match background {
+      Background::Color(_) => return,
+      Background::Gradient{..} => pass.set_pipeline(&self.pipelines.gradient),
+      Background::Sky(sky) => {
+        let texture = &self.textures[sky.0 as usize];
+        pass.set_pipeline(match texture.kind { SkyKind::Cubemap => &self.pipelines.cubemap, SkyKind::Equirect => &self.pipelines.equirect });
+        pass.set_bind_group(1, &texture.bind_group, &[]);
+      }
+    }
+    pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+    pass.draw(0..3, 0..1);
+  }
+}
+
+fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, fragment:&str, info:&TargetInfo)->wgpu::RenderPipeline {
+  device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    label: Some("background"),
+    layout: Some(layout),
+    vertex: wgpu::VertexState {buffers: &[], module: shader, entry_point: "vs_main"},
+    primitive: wgpu::PrimitiveState::default(),
+    // The background is drawn first and never occludes anything.
+    depth_stencil: Some(wgpu::DepthStencilState {
+      format: DEPTH_FORMAT,
+      depth_write_enabled: false,
+      depth_compare: wgpu::CompareFunction::Always,
+      stencil: wgpu::StencilState::default(),
+      bias: wgpu::DepthBiasState::default()}),
+    multisample: wgpu::MultisampleState{count: info.sample_count, ..Default::default()},
+    fragment: Some(wgpu::FragmentState {
+      targets: &[Some(wgpu::ColorTargetState{format: info.format, blend: None, write_mask: wgpu::ColorWrites::ALL})],
+      module: shader, entry_point: fragment,
+    }),
+    multiview: None,
+  })
+}
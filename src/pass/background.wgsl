@@ -0,0 +1,60 @@
+// Full screen background, drawn before any geometry
+
+struct Sky {
+    inv_view_proj: mat4x4<f32>,
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+    encode_srgb: u32
+}
+
+@group(0) @binding(0) var<uniform> sky: Sky;
+
+@group(1) @binding(0) var cube_texture: texture_cube<f32>;
+@group(1) @binding(1) var cube_sampler: sampler;
+
+@group(1) @binding(0) var equirect_texture: texture_2d<f32>;
+@group(1) @binding(1) var equirect_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>
+}
+
+// A single triangle covering the whole screen
+@vertex fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index & 2u) * 2 - 1);
+    out.clip_position = vec4<f32>(x, y, 1.0, 1.0);
+    out.ndc = vec2<f32>(x, y);
+    return out;
+}
+
+// World space direction of the ray through a pixel
+fn direction(ndc: vec2<f32>) -> vec3<f32> {
+    let near = sky.inv_view_proj * vec4<f32>(ndc, 0.0, 1.0);
+    let far = sky.inv_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+// Colors are computed in linear space, targets that don't encode sRGB on write need it done here
+fn output(c: vec4<f32>) -> vec4<f32> {
+    if (sky.encode_srgb == 0u) { return c; }
+    return vec4<f32>(srgb_encode(c.rgb), c.a);
+}
+
+@fragment fn fs_gradient(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = direction(in.ndc).y * 0.5 + 0.5;
+    return output(mix(sky.bottom, sky.top, t));
+}
+
+@fragment fn fs_cubemap(in: VertexOutput) -> @location(0) vec4<f32> {
+    return output(textureSample(cube_texture, cube_sampler, direction(in.ndc)));
+}
+
+@fragment fn fs_equirect(in: VertexOutput) -> @location(0) vec4<f32> {
+    let d = direction(in.ndc);
+    let pi = 3.14159265359;
+    let uv = vec2<f32>(atan2(d.z, d.x) / (2.0 * pi) + 0.5, acos(clamp(d.y, -1.0, 1.0)) / pi);
+    return output(vec4<f32>(textureSample(equirect_texture, equirect_sampler, uv).rgb, 1.0));
+}
@@ -0,0 +1,16 @@
+// The camera and target uniforms bound to group 0 of every scene pass, matches `Globals` in context.rs
+
+struct Globals {
+    view_proj: mat4x4<f32>,
+    // Non-zero when the target does not encode sRGB on write
+    encode_srgb: u32
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+
+// Colors are computed in linear space, targets that don't encode sRGB on write need it done here
+fn encode(c: vec4<f32>) -> vec4<f32> {
+    if (globals.encode_srgb == 0u) { return c; }
+    return vec4<f32>(srgb_encode(c.rgb), c.a);
+}
+
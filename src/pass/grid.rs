@@ -0,0 +1,216 @@
+use glam::{Mat4, Vec3};
+use crate::Color;
+use crate::context::{Camera, TargetInfo, DEPTH_FORMAT};
+
+/// Size in pixels of the orientation gizmo in the top right corner.
+const CORNER_SIZE:u32 = 96;
+
+/// A reference grid on a plane that fades out with distance, see [`crate::Cx::set_grid`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+  pub plane: g3::Plane,
+  /// Distance between minor lines.
+  pub spacing: f32,
+  /// Every how many minor lines there is a major one.
+  pub major: u32,
+  pub minor_color: Color,
+  pub major_color: Color,
+  /// Distance from the eye where the grid has faded out.
+  pub fade: f32,
+  /// Color the lines through the origin of the grid and the normal through it like the world axes they follow.
+  pub axes: bool,
+  /// Show which way the world axes point in the top right corner.
+  pub corner: bool,
+}
+
+impl Default for Grid {
+  fn default() -> Self {
+    Grid{plane: g3::E2, spacing: 1.0, major: 10, minor_color: Color(0x80808060), major_color: Color(0xA0A0A0A0), fade: 50.0, axes: true, corner: true}
+  }
+}
+
+/// Color of the world axis a direction is closest to.
+pub(crate) fn axis_color(d:Vec3)->Color {
+  let d = d.abs();
+  if d.x >= d.y && d.x >= d.z { Color::RED } else if d.y >= d.z { Color::GREEN } else { Color::BLUE }
+}
+
+impl Grid {
+  /// The point of the plane closest to the world origin, two unit directions in the plane and its unit normal.
+  /// The first direction follows the world x axis unless the plane faces it, then it follows z.
+  pub fn frame(&self)->(Vec3, Vec3, Vec3, Vec3) {
+    let (p, n) = (self.plane, Vec3::new(self.plane.x(), self.plane.y(), self.plane.z()));
+    let length = n.length().max(f32::MIN_POSITIVE);
+    let (n, d) = (n / length, p.d() / length);
+    let along = if n.x.abs() < 0.9 { Vec3::X } else { Vec3::Z };
+    let u = (along - n * n.dot(along)).normalize();
+    (-n * d, u, n.cross(u), n)
+  }
+}
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+  /// Eye and the directions of the view scaled to the edges of the screen, for the ray through every pixel.
+  eye: [f32;4],
+  forward: [f32;4],
+  right: [f32;4],
+  up: [f32;4],
+  /// Frame of the grid, with the spacing, major count and fade distance in the last components.
+  origin: [f32;4],
+  u: [f32;4],
+  v: [f32;4],
+  normal: [f32;4],
+  minor: [f32;4],
+  major: [f32;4],
+  /// Colors of the lines along `u` and `v`, transparent without axes.
+  u_color: [f32;4],
+  v_color: [f32;4],
+  /// Directions of the world axes on the screen and their colors, the farthest first.
+  corner: [[f32;4];3],
+  corner_colors: [[f32;4];3],
+}
+
+/// A full screen pass that intersects the ray of every pixel with the plane of a [`Grid`], drawn with depth testing
+/// against the scene, and the orientation gizmo drawn over the resolved target.
+pub struct GridPass {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  sample_count: u32,
+  pipeline: wgpu::RenderPipeline,
+  corner: wgpu::RenderPipeline,
+  params: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+}
+
+impl GridPass {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Grid Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("grid.wgsl")).into()),
+    });
+    let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("grid"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None, visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer{ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None}}]});
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Grid Pipeline Layout"),
+      bind_group_layouts: &[globals, &params_layout], push_constant_ranges: &[]});
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Grid Params"),
+      size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("grid"), layout: &params_layout,
+      entries: &[wgpu::BindGroupEntry{binding: 0, resource: params.as_entire_binding()}]});
+    let (pipeline, corner) = Self::create_pipelines(device, &layout, &shader, info);
+    GridPass{shader, layout, format: info.format, sample_count: info.sample_count, pipeline, corner, params, bind_group}
+  }
+
+  fn create_pipelines(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, info:&TargetInfo)->(wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let create = |fragment:&str, depth_stencil:Option<wgpu::DepthStencilState>, count:u32| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("grid"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState{buffers: &[], module: shader, entry_point: "vs_screen"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil,
+      multisample: wgpu::MultisampleState{count, ..Default::default()},
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format: info.format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: fragment}),
+      multiview: None,
+    });
+    // The grid writes the depth of the plane but not into the depth buffer, so lines on it still show.
+    let depth = wgpu::DepthStencilState {
+      format: DEPTH_FORMAT,
+      depth_write_enabled: false,
+      depth_compare: wgpu::CompareFunction::Less,
+      stencil: wgpu::StencilState::default(),
+      bias: wgpu::DepthBiasState::default()};
+    (create("fs_grid", Some(depth), info.sample_count), create("fs_corner", None, 1))
+  }
+
+  /// Match the target that is about to be drawn and upload the grid as seen from the camera.
+  pub fn prepare(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, info:&TargetInfo, grid:&Grid, camera:&Camera) {
+    if (self.format, self.sample_count) != (info.format, info.sample_count) {
+      (self.pipeline, self.corner) = Self::create_pipelines(device, &self.layout, &self.shader, info);
+      self.format = info.format;
+      self.sample_count = info.sample_count;
+    }
+    let view = camera.view_matrix();
+    let world = view.inverse();
+    let tan = (camera.fov_y().to_radians() / 2.0).tan();
+    let (origin, u, v, normal) = grid.frame();
+    let color = |c:Color| c.to_linear();
+    let axis = |d:Vec3| if grid.axes { color(axis_color(d)) } else { [0.0;4] };
+    let w = |v:Vec3, w:f32| [v.x, v.y, v.z, w];
+    let (corner, corner_colors) = Self::corner(&view);
+    let params = Params {
+      eye: w(camera.eye(), 1.0),
+      forward: w(-world.z_axis.truncate(), 0.0),
+      right: w(world.x_axis.truncate() * tan * info.aspect_ratio, 0.0),
+      up: w(world.y_axis.truncate() * tan, 0.0),
+      origin: w(origin, grid.spacing),
+      u: w(u, grid.major.max(1) as f32),
+      v: w(v, grid.fade),
+      normal: w(normal, 0.0),
+      minor: color(grid.minor_color),
+      major: color(grid.major_color),
+      u_color: axis(u),
+      v_color: axis(v),
+      corner,
+      corner_colors: corner_colors.map(color),
+    };
+    queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+  }
+
+  /// Directions of the world axes on the screen, the farthest from the eye first, and their colors.
+  fn corner(view:&Mat4)->([[f32;4];3], [Color;3]) {
+    let mut axes = [(Vec3::X, Color::RED), (Vec3::Y, Color::GREEN), (Vec3::Z, Color::BLUE)].map(|(a, c)| (view.transform_vector3(a), c));
+    axes.sort_by(|a, b| a.0.z.partial_cmp(&b.0.z).unwrap_or(std::cmp::Ordering::Equal));
+    (axes.map(|(d, _)| [d.x, d.y, d.z, 0.0]), axes.map(|(_, c)| c))
+  }
+
+  /// Draw the grid into a pass over the scene, with the globals bound to group 0.
+  pub fn draw<'a>(&'a self, pass:&mut wgpu::RenderPass<'a>) {
+    pass.set_pipeline(&self.pipeline);
+    pass.set_bind_group(1, &self.bind_group, &[]);
+    pass.draw(0..3, 0..1);
+  }
+
+  /// Start a pass over the top right corner of `view`, which has to be single sampled, bind the globals and draw
+  /// the orientation gizmo with it.
+  pub fn draw_corner<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder, view:&'a wgpu::TextureView, size:wgpu::Extent3d)->wgpu::RenderPass<'a> {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Grid Corner"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Load, store: true}})],
+      depth_stencil_attachment: None});
+    let side = CORNER_SIZE.min(size.width).min(size.height);
+    pass.set_viewport((size.width - side) as f32, 0.0, side as f32, side as f32, 0.0, 1.0);
+    pass.set_pipeline(&self.corner);
+    pass.set_bind_group(1, &self.bind_group, &[]);
+    pass
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn frame() {
+    let (origin, u, v, n) = Grid::default().frame();
+    assert_eq!((origin, u, n), (Vec3::ZERO, Vec3::X, Vec3::Y));
+    assert_eq!((axis_color(u), axis_color(v), axis_color(n)), (Color::RED, Color::BLUE, Color::GREEN));
+    // The plane x = 2 is spanned by z and y.
+    let (origin, u, v, n) = Grid{plane: g3::plane(1.0, 0.0, 0.0, -2.0), ..Grid::default()}.frame();
+    assert!(origin.abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-6) && u.abs_diff_eq(Vec3::Z, 1e-6));
+    assert!(v.dot(u).abs() < 1e-6 && v.dot(n).abs() < 1e-6);
+    // Looking down the z axis, z points at the eye and is drawn last.
+    let (axes, colors) = GridPass::corner(&Mat4::look_at_rh(Vec3::Z, Vec3::ZERO, Vec3::Y));
+    assert_eq!(colors[2], Color::BLUE);
+    assert_eq!(axes[0][..2], [1.0, 0.0]);
+  }
+}
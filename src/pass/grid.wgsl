@@ -0,0 +1,108 @@
+// A grid on a plane, found by intersecting the ray of every pixel with it, and the world axes in a corner
+
+struct Params {
+    eye: vec4<f32>,
+    forward: vec4<f32>,
+    // Scaled to the edges of the screen
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // The spacing of minor lines in w
+    origin: vec4<f32>,
+    // Minor lines per major line in w
+    u: vec4<f32>,
+    // Distance where the grid has faded out in w
+    v: vec4<f32>,
+    normal: vec4<f32>,
+    minor: vec4<f32>,
+    major: vec4<f32>,
+    u_color: vec4<f32>,
+    v_color: vec4<f32>,
+    // Screen directions of the world axes, the farthest first
+    corner: array<vec4<f32>, 3>,
+    corner_colors: array<vec4<f32>, 3>
+}
+
+@group(1) @binding(0) var<uniform> params: Params;
+
+struct ScreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>
+}
+
+@vertex fn vs_screen(@builtin(vertex_index) index: u32) -> ScreenOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index & 2u) * 2 - 1);
+    return ScreenOutput(vec4<f32>(x, y, 0.0, 1.0), vec2<f32>(x, y));
+}
+
+fn over(below: vec4<f32>, above: vec4<f32>) -> vec4<f32> {
+    let a = above.a + below.a * (1.0 - above.a);
+    if (a <= 0.0) { return vec4<f32>(0.0); }
+    return vec4<f32>((above.rgb * above.a + below.rgb * below.a * (1.0 - above.a)) / a, a);
+}
+
+// How much of a pixel is covered by lines at whole coordinates, one pixel wide
+fn lines(coords: vec2<f32>, width: vec2<f32>) -> f32 {
+    let d = abs(fract(coords - 0.5) - 0.5) / width;
+    return 1.0 - min(min(d.x, d.y), 1.0);
+}
+
+struct GridOutput {
+    @builtin(frag_depth) depth: f32,
+    @location(0) color: vec4<f32>
+}
+
+@fragment fn fs_grid(in: ScreenOutput) -> GridOutput {
+    let eye = params.eye.xyz;
+    let direction = params.forward.xyz + params.right.xyz * in.ndc.x + params.up.xyz * in.ndc.y;
+    let normal = params.normal.xyz;
+    let t = dot(params.origin.xyz - eye, normal) / dot(direction, normal);
+    let hit = eye + direction * t;
+    let local = hit - params.origin.xyz;
+    let coords = vec2<f32>(dot(local, params.u.xyz), dot(local, params.v.xyz)) / params.origin.w;
+    // Derivatives are taken before anything is discarded
+    let width = fwidth(coords);
+    let major = coords / params.u.w;
+    let major_width = fwidth(major);
+
+    var out: GridOutput;
+    let clip = globals.view_proj * vec4<f32>(hit, 1.0);
+    out.depth = clip.z / clip.w;
+    if (t <= 0.0 || out.depth < 0.0 || out.depth > 1.0) { discard; }
+
+    // Minor lines fade out before they get too dense to tell apart
+    let density = max(width.x, width.y);
+    var color = params.minor * vec4<f32>(1.0, 1.0, 1.0, lines(coords, width) * (1.0 - smoothstep(0.3, 0.6, density)));
+    color = over(color, params.major * vec4<f32>(1.0, 1.0, 1.0, lines(major, major_width)));
+    // The line along u is where v is zero
+    let axis = 1.0 - min(abs(coords) / (width * 1.5), vec2<f32>(1.0));
+    color = over(color, params.u_color * vec4<f32>(1.0, 1.0, 1.0, axis.y));
+    color = over(color, params.v_color * vec4<f32>(1.0, 1.0, 1.0, axis.x));
+
+    let distance = length(hit - eye);
+    color.a = color.a * (1.0 - smoothstep(params.v.w * 0.5, params.v.w, distance));
+    if (color.a <= 0.0) { discard; }
+    out.color = encode(color);
+    return out;
+}
+
+// Distance from a point to the segment from the origin to d
+fn segment(p: vec2<f32>, d: vec2<f32>) -> f32 {
+    let t = clamp(dot(p, d) / max(dot(d, d), 0.000001), 0.0, 1.0);
+    return length(p - d * t);
+}
+
+@fragment fn fs_corner(in: ScreenOutput) -> @location(0) vec4<f32> {
+    let p = in.ndc;
+    let pixel = fwidth(p.x);
+    var color = vec4<f32>(0.0);
+    for (var i = 0; i < 3; i = i + 1) {
+        let d = params.corner[i].xy * 0.7;
+        let stroke = clamp((pixel * 1.5 - segment(p, d)) / pixel + 0.5, 0.0, 1.0);
+        let tip = clamp((pixel * 5.0 - length(p - d)) / pixel + 0.5, 0.0, 1.0);
+        let c = params.corner_colors[i];
+        color = over(color, vec4<f32>(c.rgb, c.a * max(stroke, tip)));
+    }
+    if (color.a <= 0.0) { discard; }
+    return encode(color);
+}
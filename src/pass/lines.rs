@@ -0,0 +1,305 @@
+use glam::Vec3;
+use crate::Color;
+use crate::context::{TargetInfo, DEPTH_FORMAT};
+
+/// How the free ends of lines and polylines are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cap {
+  /// Cut off at the end point.
+  Butt,
+  /// Extended by half the width.
+  Square,
+  Round,
+}
+
+/// How consecutive segments of a polyline meet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Join {
+  /// Extended to where the edges meet, up to four times the width.
+  Miter,
+  Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+  pub color: Color,
+  /// Width in pixels.
+  pub width: f32,
+  pub cap: Cap,
+  pub join: Join,
+}
+
+impl Default for LineStyle {
+  fn default() -> Self { LineStyle{color: Color::WHITE, width: 2.0, cap: Cap::Butt, join: Join::Miter} }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointShape {
+  Round,
+  Square,
+}
+
+/// What is drawn at either end of a segment, matches the constants in lines.wgsl.
+const BUTT:u32 = 0;
+const SQUARE:u32 = 1;
+const ROUND:u32 = 2;
+const MITER:u32 = 3;
+
+fn cap(cap:Cap)->u32 { match cap { Cap::Butt => BUTT, Cap::Square => SQUARE, Cap::Round => ROUND } }
+
+fn join(join:Join)->u32 { match join { Join::Miter => MITER, Join::Round => ROUND } }
+
+#[repr(C)] #[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct Segment {
+  a: [f32;3],
+  b: [f32;3],
+  /// Neighbours the ends are mitered against, only read for `MITER` ends.
+  prev: [f32;3],
+  next: [f32;3],
+  color: [f32;4],
+  width: f32,
+  start: u32,
+  end: u32,
+}
+
+#[repr(C)] #[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct Point {
+  position: [f32;3],
+  size: f32,
+  color: [f32;4],
+  round: u32,
+}
+
+/// Corner of a filled triangle.
+#[repr(C)] #[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct Corner {
+  position: [f32;3],
+  color: [f32;4],
+}
+
+impl Segment {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute;8] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Float32x3, 4 => Float32x4, 5 => Float32, 6 => Uint32, 7 => Uint32];
+    wgpu::VertexBufferLayout{array_stride: std::mem::size_of::<Segment>() as wgpu::BufferAddress, step_mode: wgpu::VertexStepMode::Instance, attributes: &ATTRIBUTES}
+  }
+}
+
+impl Point {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute;4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32, 2 => Float32x4, 3 => Uint32];
+    wgpu::VertexBufferLayout{array_stride: std::mem::size_of::<Point>() as wgpu::BufferAddress, step_mode: wgpu::VertexStepMode::Instance, attributes: &ATTRIBUTES}
+  }
+}
+
+impl Corner {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute;2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+    wgpu::VertexBufferLayout{array_stride: std::mem::size_of::<Corner>() as wgpu::BufferAddress, step_mode: wgpu::VertexStepMode::Vertex, attributes: &ATTRIBUTES}
+  }
+}
+
+/// Lines and points in world space with sizes in pixels, and unlit triangles, kept until cleared.
+#[derive(Clone, Debug, Default)]
+pub struct Lines {
+  segments: Vec<Segment>,
+  points: Vec<Point>,
+  corners: Vec<Corner>,
+}
+
+impl Lines {
+  pub fn new()->Lines { Lines::default() }
+
+  pub fn line(&mut self, a:Vec3, b:Vec3, style:&LineStyle) {
+    let end = cap(style.cap);
+    self.segments.push(Segment{a: a.into(), b: b.into(), prev: a.into(), next: b.into(), color: style.color.to_linear(), width: style.width, start: end, end});
+  }
+
+  /// Segments through consecutive points, the last joined back to the first when `closed`.
+  pub fn polyline(&mut self, points:&[Vec3], closed:bool, style:&LineStyle) {
+    let n = points.len();
+    if n < 2 { return }
+    let count = if closed && n > 2 { n } else { n - 1 };
+    let (free, joined) = (cap(style.cap), join(style.join));
+    for i in 0..count {
+      let (a, b) = (points[i], points[(i + 1) % n]);
+      let first = i == 0 && count == n - 1;
+      let last = i == count - 1 && count == n - 1;
+      let prev = if first { a } else { points[(i + n - 1) % n] };
+      let next = if last { b } else { points[(i + 2) % n] };
+      self.segments.push(Segment{a: a.into(), b: b.into(), prev: prev.into(), next: next.into(), color: style.color.to_linear(), width: style.width,
+        start: if first { free } else { joined }, end: if last { free } else { joined }});
+    }
+  }
+
+  /// A point `size` pixels across.
+  pub fn point(&mut self, position:Vec3, size:f32, color:Color, shape:PointShape) {
+    self.points.push(Point{position: position.into(), size, color: color.to_linear(), round: (shape == PointShape::Round) as u32});
+  }
+
+  /// A triangle seen from both sides, blended over the scene.
+  pub fn triangle(&mut self, a:Vec3, b:Vec3, c:Vec3, color:Color) {
+    let color = color.to_linear();
+    self.corners.extend([a, b, c].iter().map(|&p| Corner{position: p.into(), color}));
+  }
+
+  pub fn clear(&mut self) {
+    self.segments.clear();
+    self.points.clear();
+    self.corners.clear();
+  }
+
+  pub fn is_empty(&self)->bool { self.segments.is_empty() && self.points.is_empty() && self.corners.is_empty() }
+}
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+  viewport: [f32;2],
+  _padding: [f32;2],
+}
+
+/// Screen space lines and points as instanced quads and triangles as they are, depth tested against the scene.
+/// The pipelines are rebuilt when the format or sample count of the target changes.
+pub struct LinePass {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  sample_count: u32,
+  segment_pipeline: wgpu::RenderPipeline,
+  point_pipeline: wgpu::RenderPipeline,
+  triangle_pipeline: wgpu::RenderPipeline,
+  params: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// Instance buffers and their sizes in bytes, grown as needed.
+  segments: (wgpu::Buffer, usize),
+  points: (wgpu::Buffer, usize),
+  corners: (wgpu::Buffer, usize),
+  segment_count: u32,
+  point_count: u32,
+  corner_count: u32,
+}
+
+impl LinePass {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Lines Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("lines.wgsl")).into()),
+    });
+    let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("lines"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None, visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer{ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None}}]});
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Lines Pipeline Layout"),
+      bind_group_layouts: &[globals, &params_layout], push_constant_ranges: &[]});
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Lines Params"),
+      size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("lines"), layout: &params_layout,
+      entries: &[wgpu::BindGroupEntry{binding: 0, resource: params.as_entire_binding()}]});
+    let (segment_pipeline, point_pipeline, triangle_pipeline) = Self::create_pipelines(device, &layout, &shader, info);
+    LinePass{shader, layout, format: info.format, sample_count: info.sample_count, segment_pipeline, point_pipeline, triangle_pipeline, params, bind_group,
+      segments: Self::vertices(device, 0), points: Self::vertices(device, 0), corners: Self::vertices(device, 0), segment_count: 0, point_count: 0, corner_count: 0}
+  }
+
+  fn vertices(device:&wgpu::Device, size:usize)->(wgpu::Buffer, usize) {
+    let size = size.max(256);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Line Vertices"),
+      size: size as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    (buffer, size)
+  }
+
+  /// Write the items of all batches one after the other, growing the buffer when they don't fit, and count them.
+  fn upload<T:bytemuck::Pod>(device:&wgpu::Device, queue:&wgpu::Queue, buffer:&mut (wgpu::Buffer, usize), items:Vec<T>)->u32 {
+    let bytes:&[u8] = bytemuck::cast_slice(&items);
+    if bytes.len() > buffer.1 { *buffer = Self::vertices(device, bytes.len().next_power_of_two()) }
+    if !bytes.is_empty() { queue.write_buffer(&buffer.0, 0, bytes) }
+    items.len() as u32
+  }
+
+  fn create_pipelines(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, info:&TargetInfo)->(wgpu::RenderPipeline, wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let create = |buffer:wgpu::VertexBufferLayout, vertex:&str, fragment:&str| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("lines"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState{buffers: &[buffer], module: shader, entry_point: vertex},
+      primitive: wgpu::PrimitiveState::default(),
+      // Tested against the scene but not written, so lines don't hide each other.
+      depth_stencil: Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default()}),
+      multisample: wgpu::MultisampleState{count: info.sample_count, ..Default::default()},
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format: info.format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: fragment}),
+      multiview: None,
+    });
+    (create(Segment::desc(), "vs_segment", "fs_segment"), create(Point::desc(), "vs_point", "fs_point"), create(Corner::desc(), "vs_triangle", "fs_triangle"))
+  }
+
+  /// Match the target that is about to be drawn and upload the batches, which are drawn together.
+  pub fn prepare(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, info:&TargetInfo, size:wgpu::Extent3d, batches:&[&Lines]) {
+    if (self.format, self.sample_count) != (info.format, info.sample_count) {
+      (self.segment_pipeline, self.point_pipeline, self.triangle_pipeline) = Self::create_pipelines(device, &self.layout, &self.shader, info);
+      self.format = info.format;
+      self.sample_count = info.sample_count;
+    }
+    queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&Params{viewport: [size.width as f32, size.height as f32], _padding: [0.0;2]}));
+    self.segment_count = Self::upload(device, queue, &mut self.segments, batches.iter().flat_map(|b| b.segments.iter().copied()).collect());
+    self.point_count = Self::upload(device, queue, &mut self.points, batches.iter().flat_map(|b| b.points.iter().copied()).collect());
+    self.corner_count = Self::upload(device, queue, &mut self.corners, batches.iter().flat_map(|b| b.corners.iter().copied()).collect());
+  }
+
+  /// Draw into a pass over the scene, with the globals bound to group 0. Triangles go first so lines on them show.
+  pub fn draw<'a>(&'a self, pass:&mut wgpu::RenderPass<'a>) {
+    pass.set_bind_group(1, &self.bind_group, &[]);
+    if self.corner_count > 0 {
+      pass.set_pipeline(&self.triangle_pipeline);
+      pass.set_vertex_buffer(0, self.corners.0.slice(..));
+      pass.draw(0..self.corner_count, 0..1);
+    }
+    if self.segment_count > 0 {
+      pass.set_pipeline(&self.segment_pipeline);
+      pass.set_vertex_buffer(0, self.segments.0.slice(..));
+      pass.draw(0..6, 0..self.segment_count);
+    }
+    if self.point_count > 0 {
+      pass.set_pipeline(&self.point_pipeline);
+      pass.set_vertex_buffer(0, self.points.0.slice(..));
+      pass.draw(0..6, 0..self.point_count);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn polyline() {
+    let mut lines = Lines::new();
+    let square = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+    let style = LineStyle{cap: Cap::Round, ..LineStyle::default()};
+    lines.polyline(&square, false, &style);
+    let ends:Vec<_> = lines.segments.iter().map(|s| (s.start, s.end)).collect();
+    assert_eq!(ends, vec![(ROUND, MITER), (MITER, MITER), (MITER, ROUND)]);
+    assert_eq!((lines.segments[1].prev, lines.segments[1].next), ([0.0;3], [0.0, 1.0, 0.0]));
+    // Closed ones have no free ends and wrap around.
+    lines.clear();
+    lines.polyline(&square, true, &LineStyle{join: Join::Round, ..style});
+    assert_eq!(lines.segments.len(), 4);
+    assert!(lines.segments.iter().all(|s| (s.start, s.end) == (ROUND, ROUND)));
+    assert_eq!((lines.segments[0].prev, lines.segments[3].next), ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]));
+    lines.clear();
+    lines.polyline(&square[..1], false, &style);
+    lines.point(Vec3::ZERO, 4.0, Color::RED, PointShape::Square);
+    assert!(lines.segments.is_empty() && lines.points[0].round == 0);
+  }
+}
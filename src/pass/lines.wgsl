@@ -0,0 +1,187 @@
+// Lines and points of a size in pixels, drawn as instanced quads that are expanded in screen space,
+// and unlit triangles
+
+struct Params {
+    // Size of the target in pixels
+    viewport: vec2<f32>
+}
+
+@group(1) @binding(0) var<uniform> params: Params;
+
+// What is drawn at either end of a segment
+let BUTT: u32 = 0u;
+let SQUARE: u32 = 1u;
+let ROUND: u32 = 2u;
+let MITER: u32 = 3u;
+
+// Ends closer to the eye than this are clipped
+let NEAR: f32 = 0.001;
+
+struct Segment {
+    @location(0) a: vec3<f32>,
+    @location(1) b: vec3<f32>,
+    @location(2) prev: vec3<f32>,
+    @location(3) next: vec3<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) width: f32,
+    @location(6) start: u32,
+    @location(7) end: u32
+}
+
+struct SegmentOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Pixels along the segment from its start and across from its center
+    @location(1) @interpolate(linear) local: vec2<f32>,
+    // Length and width in pixels
+    @location(2) @interpolate(flat) shape: vec2<f32>,
+    @location(3) @interpolate(flat) ends: vec2<u32>
+}
+
+fn clip(p: vec3<f32>) -> vec4<f32> {
+    return globals.view_proj * vec4<f32>(p, 1.0);
+}
+
+fn screen(c: vec4<f32>) -> vec2<f32> {
+    return c.xy / c.w * params.viewport * 0.5;
+}
+
+fn unscreen(p: vec2<f32>, c: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(p / (params.viewport * 0.5) * c.w, c.z, c.w);
+}
+
+// Corners of a quad as two triangles, x from start to end and y from one side to the other
+fn corner(index: u32) -> vec2<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0));
+    return corners[index];
+}
+
+@vertex fn vs_segment(@builtin(vertex_index) index: u32, s: Segment) -> SegmentOutput {
+    var out: SegmentOutput;
+    var a = clip(s.a);
+    var b = clip(s.b);
+    var start = s.start;
+    var end = s.end;
+    if (a.w < NEAR && b.w < NEAR) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+    if (a.w < NEAR) { a = mix(a, b, (NEAR - a.w) / (b.w - a.w)); start = BUTT; }
+    if (b.w < NEAR) { b = mix(b, a, (NEAR - b.w) / (a.w - b.w)); end = BUTT; }
+    let sa = screen(a);
+    let sb = screen(b);
+    let len = distance(sa, sb);
+    var dir = vec2<f32>(1.0, 0.0);
+    if (len > 0.0001) { dir = (sb - sa) / len; }
+    let normal = vec2<f32>(-dir.y, dir.x);
+
+    let c = corner(index);
+    let at_end = c.x > 0.5;
+    let p = select(a, b, at_end);
+    let sp = select(sa, sb, at_end);
+    let kind = select(start, end, at_end);
+    // One more pixel all around for antialiasing
+    let margin = s.width * 0.5 + 1.0;
+    let outward = dir * select(-1.0, 1.0, at_end);
+    var offset = normal * c.y * margin;
+    if (kind == SQUARE || kind == ROUND) { offset = offset + outward * margin; }
+    if (kind == BUTT) { offset = offset + outward; }
+    if (kind == MITER) {
+        let n = clip(select(s.prev, s.next, at_end));
+        // The neighbouring segment, pointing the same way as this one
+        let other = select(sp - screen(n), screen(n) - sp, at_end);
+        let sum = dir + normalize(other);
+        if (n.w >= NEAR && length(other) > 0.0001 && length(sum) > 0.0001) {
+            let t = normalize(sum);
+            let miter = vec2<f32>(-t.y, t.x);
+            offset = miter * c.y * margin / max(dot(miter, normal), 0.25);
+        }
+    }
+    let q = sp + offset;
+    out.clip_position = unscreen(q, p);
+    out.color = s.color;
+    out.local = vec2<f32>(dot(q - sa, dir), dot(q - sa, normal));
+    out.shape = vec2<f32>(len, s.width);
+    out.ends = vec2<u32>(start, end);
+    return out;
+}
+
+// Distance that is compared to half the width, past an end of the given kind
+fn edge(kind: u32, beyond: f32, across: f32, half: f32) -> f32 {
+    if (beyond <= 0.0 || kind == MITER) { return across; }
+    if (kind == ROUND) { return length(vec2<f32>(beyond, across)); }
+    if (kind == SQUARE) { return max(across, beyond); }
+    return max(across, beyond + half);
+}
+
+@fragment fn fs_segment(in: SegmentOutput) -> @location(0) vec4<f32> {
+    let half = in.shape.y * 0.5;
+    let across = abs(in.local.y);
+    let d = max(edge(in.ends.x, -in.local.x, across, half), edge(in.ends.y, in.local.x - in.shape.x, across, half));
+    let alpha = clamp(half + 0.5 - d, 0.0, 1.0);
+    if (alpha <= 0.0) { discard; }
+    return encode(vec4<f32>(in.color.rgb, in.color.a * alpha));
+}
+
+struct Point {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    @location(2) color: vec4<f32>,
+    @location(3) round: u32
+}
+
+struct PointOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Pixels from the center
+    @location(1) @interpolate(linear) local: vec2<f32>,
+    @location(2) @interpolate(flat) size: f32,
+    @location(3) @interpolate(flat) round: u32
+}
+
+@vertex fn vs_point(@builtin(vertex_index) index: u32, p: Point) -> PointOutput {
+    var out: PointOutput;
+    let c = clip(p.position);
+    if (c.w < NEAR) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+    let k = corner(index);
+    let offset = vec2<f32>(k.x * 2.0 - 1.0, k.y) * (p.size * 0.5 + 1.0);
+    out.clip_position = unscreen(screen(c) + offset, c);
+    out.color = p.color;
+    out.local = offset;
+    out.size = p.size;
+    out.round = p.round;
+    return out;
+}
+
+@fragment fn fs_point(in: PointOutput) -> @location(0) vec4<f32> {
+    let d = select(max(abs(in.local.x), abs(in.local.y)), length(in.local), in.round == 1u);
+    let alpha = clamp(in.size * 0.5 + 0.5 - d, 0.0, 1.0);
+    if (alpha <= 0.0) { discard; }
+    return encode(vec4<f32>(in.color.rgb, in.color.a * alpha));
+}
+
+struct Corner {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>
+}
+
+struct TriangleOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>
+}
+
+@vertex fn vs_triangle(v: Corner) -> TriangleOutput {
+    var out: TriangleOutput;
+    out.clip_position = clip(v.position);
+    out.color = v.color;
+    return out;
+}
+
+@fragment fn fs_triangle(in: TriangleOutput) -> @location(0) vec4<f32> {
+    return encode(in.color);
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+/// Fills the mip chain of a texture by repeatedly blitting each level into the next one.
+/// Sampling an sRGB view decodes and rendering into one encodes, so averaging happens in linear space.
+pub struct Mipmaps {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::BindGroupLayout,
+  pipeline_layout: wgpu::PipelineLayout,
+  sampler: wgpu::Sampler,
+  pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl Mipmaps {
+  pub fn new(device:&wgpu::Device)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Mipmap Shader"),
+      source: wgpu::ShaderSource::Wgsl(include_str!("mipmap.wgsl").into()),
+    });
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("mipmap"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float{filterable: true},
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false}},
+        wgpu::BindGroupLayoutEntry {
+          binding: 1, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)}]});
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Mipmap Pipeline Layout"),
+      bind_group_layouts: &[&layout], push_constant_ranges: &[]});
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("mipmap"),
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()});
+    Mipmaps{shader, layout, pipeline_layout, sampler, pipelines: HashMap::new()}
+  }
+
+  /// Number of levels down to 1x1 of a texture of the given size.
+  pub fn level_count(width:u32, height:u32)->u32 {
+    32 - width.max(height).max(1).leading_zeros()
+  }
+
+  /// Whether the levels of this format can be generated, it has to be filterable and renderable.
+  pub fn supports(format:wgpu::TextureFormat)->bool {
+    let info = format.describe();
+    info.block_dimensions == (1, 1) && info.guaranteed_format_features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+      && info.guaranteed_format_features.flags.contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+  }
+
+  /// Generate levels 1.. of `texture` from level 0, it needs TEXTURE_BINDING and RENDER_ATTACHMENT usage.
+  pub fn generate(&mut self, device:&wgpu::Device, encoder:&mut wgpu::CommandEncoder, texture:&wgpu::Texture, format:wgpu::TextureFormat, level_count:u32) {
+    let (shader, pipeline_layout) = (&self.shader, &self.pipeline_layout);
+    let pipeline = self.pipelines.entry(format).or_insert_with(|| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("mipmap"),
+      layout: Some(pipeline_layout),
+      vertex: wgpu::VertexState {buffers: &[], module: shader, entry_point: "vs_main"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format, blend: None, write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: "fs_main"}),
+      multiview: None,
+    }));
+    let views:Vec<_> = (0..level_count).map(|level| texture.create_view(&wgpu::TextureViewDescriptor {
+      label: Some("mip"),
+      base_mip_level: level,
+      mip_level_count: std::num::NonZeroU32::new(1),
+      ..Default::default()})).collect();
+    for pair in views.windows(2) {
+      let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("mipmap"), layout: &self.layout,
+        entries: &[
+          wgpu::BindGroupEntry {binding: 0, resource: wgpu::BindingResource::TextureView(&pair[0])},
+          wgpu::BindGroupEntry {binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler)}]});
+      let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("mipmap"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+          view: &pair[1], resolve_target: None,
+          ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true}})],
+        depth_stencil_attachment: None,
+      });
+      pass.set_pipeline(pipeline);
+      pass.set_bind_group(0, &bind_group, &[]);
+      pass.draw(0..3, 0..1);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn level_count() {
+    assert_eq!(Mipmaps::level_count(1, 1), 1);
+    assert_eq!(Mipmaps::level_count(256, 256), 9);
+    assert_eq!(Mipmaps::level_count(300, 20), 9);
+    assert_eq!(Mipmaps::level_count(0, 0), 1);
+    assert!(Mipmaps::supports(wgpu::TextureFormat::Rgba8UnormSrgb));
+    assert!(!Mipmaps::supports(wgpu::TextureFormat::Bc1RgbaUnorm));
+  }
+}
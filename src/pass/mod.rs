@@ -1,3 +1,21 @@
 mod phong;
+mod background;
+mod mipmap;
+mod picking;
+mod outline;
+mod overlay;
+mod lines;
+mod grid;
+mod text;
+mod ui;
 
-pub use phong::*;
\ No newline at end of file
+pub use phong::*;
+pub use background::*;
+pub use mipmap::*;
+pub use picking::*;
+pub use outline::*;
+pub use overlay::*;
+pub use lines::*;
+pub use grid::*;
+pub use text::*;
+pub use ui::*;
@@ -0,0 +1,191 @@
+use crate::context::TargetInfo;
+use crate::mesh::Vertex;
+use crate::scene::Selection;
+
+/// Format of the seeds, the coordinates of the nearest covered pixel.
+const SEED_FORMAT:wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+/// Distance between the parameters of consecutive passes, as required for dynamic offsets.
+const STRIDE:wgpu::BufferAddress = 256;
+/// Flood passes are enough for outlines up to `2^MAX_PASSES` pixels wide.
+const MAX_PASSES:u32 = 12;
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+  color: [f32;4],
+  width: f32,
+  step: f32,
+  _padding: [f32;2],
+}
+
+/// Outlines around the silhouettes of selected objects. The covered pixels are drawn as seeds, a jump flood finds the
+/// nearest seed of every pixel, and the pixels close enough to one are blended over the target.
+pub struct Outline {
+  shader: wgpu::ShaderModule,
+  seed_pipeline: wgpu::RenderPipeline,
+  flood_pipeline: wgpu::RenderPipeline,
+  composite_layout: wgpu::PipelineLayout,
+  composite: wgpu::RenderPipeline,
+  format: wgpu::TextureFormat,
+  layout: wgpu::BindGroupLayout,
+  params: wgpu::Buffer,
+  passes: u32,
+  size: (u32, u32),
+  /// Ping pong textures of the flood and bind groups that read them.
+  views: [wgpu::TextureView;2],
+  bind_groups: [wgpu::BindGroup;2],
+}
+
+impl Outline {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, locals:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Outline Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("outline.wgsl")).into()),
+    });
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("outline"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float{filterable: false},
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false}},
+        wgpu::BindGroupLayoutEntry {
+          binding: 1, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: true,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<Params>() as wgpu::BufferAddress)}}]});
+    let seed_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Outline Seed Layout"),
+      bind_group_layouts: &[globals, locals], push_constant_ranges: &[]});
+    let composite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Outline Layout"),
+      bind_group_layouts: &[&layout], push_constant_ranges: &[]});
+    let seed_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("outline seeds"),
+      layout: Some(&seed_layout),
+      vertex: wgpu::VertexState {buffers: &[Vertex::desc()], module: &shader, entry_point: "vs_seed"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format: SEED_FORMAT, blend: None, write_mask: wgpu::ColorWrites::ALL})],
+        module: &shader, entry_point: "fs_seed"}),
+      multiview: None,
+    });
+    let flood_pipeline = Self::create_pipeline(device, &composite_layout, &shader, "fs_flood", SEED_FORMAT, None);
+    let composite = Self::create_pipeline(device, &composite_layout, &shader, "fs_composite", info.format, Some(wgpu::BlendState::ALPHA_BLENDING));
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Outline Params"),
+      size: STRIDE * (MAX_PASSES + 1) as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let (views, bind_groups) = Self::attachments(device, &layout, &params, 1, 1);
+    Outline{shader, seed_pipeline, flood_pipeline, composite_layout, composite, format: info.format, layout, params, passes: 0, size: (1, 1), views, bind_groups}
+  }
+
+  fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, fragment:&str, format:wgpu::TextureFormat, blend:Option<wgpu::BlendState>)->wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("outline"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState {buffers: &[], module: shader, entry_point: "vs_screen"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format, blend, write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: fragment}),
+      multiview: None,
+    })
+  }
+
+  fn attachments(device:&wgpu::Device, layout:&wgpu::BindGroupLayout, params:&wgpu::Buffer, width:u32, height:u32)->([wgpu::TextureView;2], [wgpu::BindGroup;2]) {
+    let views = [0, 1].map(|_| device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Outline Seeds"), size: wgpu::Extent3d{width, height, depth_or_array_layers: 1},
+      mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format: SEED_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING})
+      .create_view(&wgpu::TextureViewDescriptor::default()));
+    let bind_groups = [&views[0], &views[1]].map(|view| device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("outline"), layout,
+      entries: &[
+        wgpu::BindGroupEntry {binding: 0, resource: wgpu::BindingResource::TextureView(view)},
+        wgpu::BindGroupEntry {binding: 1, resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+          buffer: params, offset: 0, size: wgpu::BufferSize::new(std::mem::size_of::<Params>() as wgpu::BufferAddress)})}]}));
+    (views, bind_groups)
+  }
+
+  /// Number of flood passes for an outline, the first looks half the next power of two of the width away.
+  pub fn pass_count(width:f32)->u32 {
+    (32 - (width.ceil().max(1.0) as u32).leading_zeros()).min(MAX_PASSES)
+  }
+
+  /// Match the target that is about to be drawn and upload the look of the selection.
+  pub fn prepare(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, info:&TargetInfo, size:wgpu::Extent3d, selection:&Selection) {
+    if self.format != info.format {
+      self.composite = Self::create_pipeline(device, &self.composite_layout, &self.shader, "fs_composite", info.format, Some(wgpu::BlendState::ALPHA_BLENDING));
+      self.format = info.format;
+    }
+    if self.size != (size.width, size.height) {
+      (self.views, self.bind_groups) = Self::attachments(device, &self.layout, &self.params, size.width, size.height);
+      self.size = (size.width, size.height);
+    }
+    // Targets that don't encode sRGB on write take the encoded color.
+    let color = if info.format.describe().srgb { selection.color.to_linear() } else { selection.color.into() };
+    self.passes = Self::pass_count(selection.width);
+    let mut params = vec![0u8; STRIDE as usize * (self.passes + 1) as usize];
+    for i in 0..=self.passes {
+      let step = (1u32 << self.passes.saturating_sub(i + 1)) as f32;
+      let p = Params{color, width: selection.width, step, _padding: [0.0;2]};
+      let offset = i as usize * STRIDE as usize;
+      params[offset..offset + std::mem::size_of::<Params>()].copy_from_slice(bytemuck::bytes_of(&p));
+    }
+    queue.write_buffer(&self.params, 0, &params);
+  }
+
+  /// Start the pass that draws the selected objects as seeds, bind the globals and draw the objects with it.
+  pub fn seeds<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder)->wgpu::RenderPass<'a> {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Outline Seeds"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view: &self.views[0], resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color{r: -1.0, g: -1.0, b: 0.0, a: 0.0}), store: true}})],
+      depth_stencil_attachment: None});
+    pass.set_pipeline(&self.seed_pipeline);
+    pass
+  }
+
+  /// Spread the seeds and blend the outline over `view`, which has to be single sampled.
+  pub fn draw(&self, encoder:&mut wgpu::CommandEncoder, view:&wgpu::TextureView) {
+    let offset = |i:u32| (i as wgpu::BufferAddress * STRIDE) as wgpu::DynamicOffset;
+    for i in 0..self.passes {
+      let (source, target) = (i as usize % 2, (i as usize + 1) % 2);
+      let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Outline Flood"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment{view: &self.views[target], resolve_target: None,
+          ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true}})],
+        depth_stencil_attachment: None});
+      pass.set_pipeline(&self.flood_pipeline);
+      pass.set_bind_group(0, &self.bind_groups[source], &[offset(i)]);
+      pass.draw(0..3, 0..1);
+    }
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Outline"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Load, store: true}})],
+      depth_stencil_attachment: None});
+    pass.set_pipeline(&self.composite);
+    pass.set_bind_group(0, &self.bind_groups[self.passes as usize % 2], &[offset(self.passes)]);
+    pass.draw(0..3, 0..1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn pass_count() {
+    assert_eq!(Outline::pass_count(0.0), 1);
+    assert_eq!(Outline::pass_count(1.0), 1);
+    assert_eq!(Outline::pass_count(3.0), 2);
+    assert_eq!(Outline::pass_count(4.0), 3);
+    assert_eq!(Outline::pass_count(1e9), MAX_PASSES);
+  }
+}
@@ -0,0 +1,77 @@
+// Outlines around selected objects with a jump flood
+
+struct Locals {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    id: u32
+}
+
+@group(1) @binding(0) var<uniform> locals: Locals;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>
+}
+
+@vertex fn vs_seed(model: Vertex) -> @builtin(position) vec4<f32> {
+    return globals.view_proj * locals.model * vec4<f32>(model.position, 1.0);
+}
+
+// Every covered pixel is its own nearest seed
+@fragment fn fs_seed(@builtin(position) position: vec4<f32>) -> @location(0) vec2<f32> {
+    return position.xy;
+}
+
+struct Params {
+    color: vec4<f32>,
+    width: f32,
+    // Distance in pixels to the neighbours looked at by this flood pass
+    step: f32
+}
+
+@group(0) @binding(0) var seeds: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+// A single triangle covering the whole target
+@vertex fn vs_screen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index & 2u) * 2 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+// Seeds are pixel centers, pixels that haven't seen one yet hold negative coordinates
+fn seed(pixel: vec2<i32>) -> vec2<f32> {
+    let size = textureDimensions(seeds);
+    return textureLoad(seeds, clamp(pixel, vec2<i32>(0), size - vec2<i32>(1)), 0).xy;
+}
+
+@fragment fn fs_flood(@builtin(position) position: vec4<f32>) -> @location(0) vec2<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let step = i32(params.step);
+    var nearest = vec2<f32>(-1.0);
+    var distance = 1e20;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let s = seed(pixel + vec2<i32>(x, y) * step);
+            let d = length(s - position.xy);
+            if (s.x >= 0.0 && d < distance) {
+                nearest = s;
+                distance = d;
+            }
+        }
+    }
+    return nearest;
+}
+
+// Covers the pixels around the silhouettes up to the width, with a smooth outer edge
+@fragment fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let s = seed(vec2<i32>(position.xy));
+    if (s.x < 0.0) { discard; }
+    let d = length(s - position.xy);
+    if (d < 0.5) { discard; }
+    let coverage = clamp(params.width + 0.5 - d, 0.0, 1.0);
+    return vec4<f32>(params.color.rgb, params.color.a * coverage);
+}
@@ -0,0 +1,57 @@
+use crate::context::TargetInfo;
+use crate::mesh::Vertex;
+
+/// Unlit meshes blended over a resolved target without depth testing, for gizmos and other editor geometry.
+/// The pipeline is rebuilt when the format of the target changes.
+pub struct Overlay {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  pipeline: wgpu::RenderPipeline,
+}
+
+impl Overlay {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, locals:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Overlay Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("overlay.wgsl")).into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Overlay Pipeline Layout"),
+      bind_group_layouts: &[globals, locals], push_constant_ranges: &[]});
+    let pipeline = Self::create_pipeline(device, &layout, &shader, info.format);
+    Overlay{shader, layout, format: info.format, pipeline}
+  }
+
+  pub fn prepare(&mut self, device:&wgpu::Device, info:&TargetInfo) {
+    if self.format == info.format { return }
+    self.pipeline = Self::create_pipeline(device, &self.layout, &self.shader, info.format);
+    self.format = info.format;
+  }
+
+  fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, format:wgpu::TextureFormat)->wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("overlay"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState {buffers: &[Vertex::desc()], module: shader, entry_point: "vs_main"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: "fs_main"}),
+      multiview: None,
+    })
+  }
+
+  /// Start a pass over `view`, which has to be single sampled, bind the globals and draw the meshes with it.
+  pub fn pass<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder, view:&'a wgpu::TextureView)->wgpu::RenderPass<'a> {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Overlay"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Load, store: true}})],
+      depth_stencil_attachment: None});
+    pass.set_pipeline(&self.pipeline);
+    pass
+  }
+}
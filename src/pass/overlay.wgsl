@@ -0,0 +1,33 @@
+// Unlit geometry drawn over the scene, like gizmos
+
+struct Locals {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    id: u32
+}
+
+@group(1) @binding(0) var<uniform> locals: Locals;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>
+}
+
+@vertex fn vs_main(model: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = globals.view_proj * locals.model * vec4<f32>(model.position, 1.0);
+    out.color = model.color * locals.color;
+    return out;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return encode(in.color);
+}
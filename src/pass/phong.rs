@@ -1,11 +1,122 @@
+use wgpu::util::DeviceExt;
+use crate::context::{TargetInfo, DEPTH_FORMAT};
+use crate::graph::Light;
+use crate::mesh::Vertex;
+
+/// Lights that shade a frame, the ones after are left out.
+pub const MAX_LIGHTS:usize = 16;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct LightUniform {
   position: [f32; 3],
-  // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-  _padding: u32,
+  range: f32,
+  /// Linear color times intensity.
   color: [f32; 3],
-  // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-  _padding2: u32,
+  _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsUniform {
+  eye: [f32; 3],
+  count: u32,
+  lights: [LightUniform; MAX_LIGHTS],
+}
+
+/// Phong shaded meshes, the pipelines are rebuilt when the format or sample count of the target changes.
+/// Without lights meshes are drawn unlit, in their own color.
+pub struct Phong {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  textured_layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  sample_count: u32,
+  pub pipeline: wgpu::RenderPipeline,
+  /// Variant that multiplies the color with an albedo map bound to group 3.
+  pub textured: wgpu::RenderPipeline,
+  lights: wgpu::Buffer,
+  /// The lights, for group 2 of both pipelines.
+  pub lights_bind_group: wgpu::BindGroup,
 }
 
+impl Phong {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, locals:&wgpu::BindGroupLayout, texture:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Phong Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("phong.wgsl")).into()),
+    });
+    let lights_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("Lights Bind Group Layout"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None}}]});
+    let lights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("Lights Buffer"),
+      contents: bytemuck::bytes_of(&LightsUniform{eye: [0.0; 3], count: 0, lights: [LightUniform{position: [0.0; 3], range: 0.0, color: [0.0; 3], _padding: 0}; MAX_LIGHTS]}),
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST});
+    let lights_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("lights"), layout: &lights_layout,
+      entries: &[wgpu::BindGroupEntry{binding: 0, resource: lights.as_entire_binding()}]});
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Render Pipeline Layout"),
+      bind_group_layouts: &[globals, locals, &lights_layout], push_constant_ranges: &[]});
+    let textured_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Textured Pipeline Layout"),
+      bind_group_layouts: &[globals, locals, &lights_layout, texture], push_constant_ranges: &[]});
+    let pipeline = Self::create_pipeline(device, &layout, &shader, "fs_main", info);
+    let textured = Self::create_pipeline(device, &textured_layout, &shader, "fs_textured", info);
+    Phong{shader, layout, textured_layout, format: info.format, sample_count: info.sample_count, pipeline, textured, lights, lights_bind_group}
+  }
+
+  /// Upload the lights and where they are seen from, the first [`MAX_LIGHTS`] are used.
+  pub fn set_lights(&self, queue:&wgpu::Queue, eye:glam::Vec3, lights:&[(g3::Point, Light)]) {
+    let mut uniform = LightsUniform{eye: eye.into(), count: lights.len().min(MAX_LIGHTS) as u32, lights: [LightUniform{position: [0.0; 3], range: 0.0, color: [0.0; 3], _padding: 0}; MAX_LIGHTS]};
+    for (slot, (position, light)) in uniform.lights.iter_mut().zip(lights) {
+      let [r, g, b, _] = light.color.to_linear();
+      *slot = LightUniform{position: (*position).into(), range: light.range, color: [r * light.intensity, g * light.intensity, b * light.intensity], _padding: 0};
+    }
+    queue.write_buffer(&self.lights, 0, bytemuck::bytes_of(&uniform));
+  }
+
+  /// Make sure the pipelines are compatible with the target that is about to be drawn.
+  pub fn prepare(&mut self, device:&wgpu::Device, info:&TargetInfo) {
+    if (self.format, self.sample_count) == (info.format, info.sample_count) { return }
+    self.pipeline = Self::create_pipeline(device, &self.layout, &self.shader, "fs_main", info);
+    self.textured = Self::create_pipeline(device, &self.textured_layout, &self.shader, "fs_textured", info);
+    self.format = info.format;
+    self.sample_count = info.sample_count;
+  }
+
+  fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, fragment:&str, info:&TargetInfo)->wgpu::RenderPipeline {
+    let targets = &[Some(wgpu::ColorTargetState {
+      format: info.format,
+      blend: Some(wgpu::BlendState {
+        color: wgpu::BlendComponent::REPLACE,
+        alpha: wgpu::BlendComponent::REPLACE,
+      }),
+      write_mask: wgpu::ColorWrites::ALL})];
+    let primitive = wgpu::PrimitiveState{cull_mode:Some(wgpu::Face::Back),..Default::default()};
+    let multisample = wgpu::MultisampleState{count:info.sample_count, ..Default::default()};
+    let depth_stencil = Some(wgpu::DepthStencilState {
+      format: DEPTH_FORMAT,
+      depth_write_enabled: true,
+      depth_compare: wgpu::CompareFunction::Less,
+      stencil: wgpu::StencilState::default(),
+      bias: wgpu::DepthBiasState::default()});
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("phong"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState {buffers: &[Vertex::desc()], module: shader, entry_point: "vs_main"},
+      primitive,
+      depth_stencil,
+      multisample,
+      fragment: Some(wgpu::FragmentState {targets, module: shader, entry_point: fragment}),
+      multiview: None,
+    })
+  }
+}
@@ -1,32 +1,94 @@
 // Vertex shader
 
-struct Globals {
-    view_proj: mat4x4<f32>
+struct Locals {
+    model: mat4x4<f32>,
+    // Inverse transpose of the model matrix
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    id: u32
 }
 
-@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var<uniform> locals: Locals;
 
-struct Locals {
-    color: vec4<f32>
+struct Light {
+    position: vec3<f32>,
+    range: f32,
+    // Linear color times intensity
+    color: vec3<f32>
 }
 
-@group(1) @binding(0) var<uniform> locals: Locals;
+struct Lights {
+    eye: vec3<f32>,
+    count: u32,
+    lights: array<Light, 16>
+}
+
+@group(2) @binding(0) var<uniform> lights: Lights;
+
+@group(3) @binding(0) var albedo_texture: texture_2d<f32>;
+@group(3) @binding(1) var albedo_sampler: sampler;
+
+let AMBIENT: f32 = 0.1;
+let SHININESS: f32 = 32.0;
 
 struct Vertex {
-    @location(0) position: vec3<f32>
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>
 }
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) world: vec3<f32>
 }
 
 @vertex fn vs_main(model: Vertex) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = globals.view_proj * vec4<f32>(model.position, 1.0);
+    let world = locals.model * vec4<f32>(model.position, 1.0);
+    out.clip_position = globals.view_proj * world;
+    out.normal = (locals.normal * vec4<f32>(model.normal, 0.0)).xyz;
+    out.uv = model.uv;
+    out.color = model.color * locals.color;
+    out.world = world.xyz;
     return out;
 }
 
 // Fragment shader
+
+// Ambient, diffuse and specular light from every light in range, each fading out smoothly towards its range.
+// Without lights the color is left as it is.
+fn shade(color: vec4<f32>, in: VertexOutput) -> vec4<f32> {
+    if (lights.count == 0u) {
+        return color;
+    }
+    let normal = normalize(in.normal);
+    let view = normalize(lights.eye - in.world);
+    var diffuse = vec3<f32>(AMBIENT);
+    var specular = vec3<f32>(0.0);
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        let light = lights.lights[i];
+        let offset = light.position - in.world;
+        let distance = length(offset);
+        let direction = offset / max(distance, 0.0001);
+        let window = clamp(1.0 - pow(distance / max(light.range, 0.0001), 4.0), 0.0, 1.0);
+        let radiance = light.color * window * window / (1.0 + distance * distance);
+        let facing = max(dot(normal, direction), 0.0);
+        diffuse = diffuse + radiance * facing;
+        if (facing > 0.0) {
+            specular = specular + radiance * pow(max(dot(normal, normalize(direction + view)), 0.0), SHININESS);
+        }
+    }
+    return vec4<f32>(color.rgb * diffuse + specular, color.a);
+}
+
 @fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return locals.color;
+    return encode(shade(in.color, in));
+}
+
+@fragment fn fs_textured(in: VertexOutput) -> @location(0) vec4<f32> {
+    return encode(shade(in.color * textureSample(albedo_texture, albedo_sampler, in.uv), in));
 }
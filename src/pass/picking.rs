@@ -0,0 +1,131 @@
+use crate::context::DEPTH_FORMAT;
+use crate::mesh::Vertex;
+
+/// Distance between the id and the depth in the readback buffer, texture copies start at multiples of it.
+const ROW:wgpu::BufferAddress = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress;
+
+/// Renders one more than the index of the object covering a pixel and its depth, for picking on the GPU.
+/// The targets are a single pixel, the projection is offset with [`Picking::view_proj`] to fill it with the
+/// pixel that is asked for.
+pub struct Picking {
+  pipeline: wgpu::RenderPipeline,
+  ids: wgpu::Texture,
+  depths: wgpu::Texture,
+  views: [wgpu::TextureView;3],
+  readback: wgpu::Buffer,
+}
+
+impl Picking {
+  pub const ID_FORMAT:wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+  pub const DEPTH_FORMAT:wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, locals:&wgpu::BindGroupLayout)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Picking Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("picking.wgsl")).into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Picking Pipeline Layout"),
+      bind_group_layouts: &[globals, locals], push_constant_ranges: &[]});
+    let target = |format| Some(wgpu::ColorTargetState{format, blend: None, write_mask: wgpu::ColorWrites::ALL});
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("picking"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {buffers: &[Vertex::desc()], module: &shader, entry_point: "vs_main"},
+      primitive: wgpu::PrimitiveState{cull_mode: Some(wgpu::Face::Back), ..Default::default()},
+      depth_stencil: Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default()}),
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {targets: &[target(Self::ID_FORMAT), target(Self::DEPTH_FORMAT)], module: &shader, entry_point: "fs_main"}),
+      multiview: None,
+    });
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Picking Readback"), size: 2 * ROW,
+      usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let size = wgpu::Extent3d{width: 1, height: 1, depth_or_array_layers: 1};
+    let create = |label, format, usage| device.create_texture(&wgpu::TextureDescriptor {
+      label: Some(label), size, mip_level_count: 1, sample_count: 1, dimension: wgpu::TextureDimension::D2, format, usage});
+    let copy = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+    let ids = create("Picking Ids", Self::ID_FORMAT, copy);
+    let depths = create("Picking Depths", Self::DEPTH_FORMAT, copy);
+    let depth = create("Picking Depth", DEPTH_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT);
+    let views = [&ids, &depths, &depth].map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+    Picking{pipeline, ids, depths, views, readback}
+  }
+
+  /// Scale and move a projection so that the pixel at `x`, `y` of a `width` by `height` screen covers all of clip space.
+  pub fn view_proj(view_proj:glam::Mat4, x:u32, y:u32, width:u32, height:u32)->glam::Mat4 {
+    let (width, height) = (width as f32, height as f32);
+    let center = glam::Vec3::new(2.0 * (x as f32 + 0.5) / width - 1.0, 1.0 - 2.0 * (y as f32 + 0.5) / height, 0.0);
+    glam::Mat4::from_scale(glam::Vec3::new(width, height, 1.0)) * glam::Mat4::from_translation(-center) * view_proj
+  }
+
+  /// Start a pass into the single pixel, bind the globals with [`Picking::view_proj`] and draw the objects with it.
+  pub fn pass<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder)->wgpu::RenderPass<'a> {
+    let clear = |view| Some(wgpu::RenderPassColorAttachment{view, resolve_target: None, ops: wgpu::Operations{load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true}});
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Picking Pass"),
+      color_attachments: &[clear(&self.views[0]), clear(&self.views[1])],
+      depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment{view: &self.views[2], depth_ops: Some(wgpu::Operations{load: wgpu::LoadOp::Clear(1.0), store: true}), stencil_ops: None})});
+    pass.set_pipeline(&self.pipeline);
+    pass
+  }
+
+  /// Copy the pixel drawn by [`Picking::pass`] to where [`Picking::read`] finds it.
+  pub fn copy(&self, encoder:&mut wgpu::CommandEncoder) {
+    for (i, texture) in [&self.ids, &self.depths].into_iter().enumerate() {
+      encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture{texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All},
+        wgpu::ImageCopyBuffer{buffer: &self.readback, layout: wgpu::ImageDataLayout{offset: i as wgpu::BufferAddress * ROW, bytes_per_row: None, rows_per_image: None}},
+        wgpu::Extent3d{width: 1, height: 1, depth_or_array_layers: 1});
+    }
+  }
+
+  /// The id and depth of the copied pixel, once the GPU is done with it.
+  pub async fn read(&self, device:&wgpu::Device)->Option<(u32, f32)> {
+    let slice = self.readback.slice(..);
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| { let _ = sender.send(result); });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.await.ok()?.ok()?;
+    let data = slice.get_mapped_range();
+    let word = |offset:usize| [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+    let pixel = (u32::from_le_bytes(word(0)), f32::from_le_bytes(word(ROW as usize)));
+    drop(data);
+    self.readback.unmap();
+    Some(pixel)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn view_proj() {
+    let view_proj = glam::Mat4::perspective_rh(1.0, 2.0, 0.1, 10.0);
+    // Pixel 30, 10 of a 40 by 20 screen is centered on 0.525, -0.05.
+    let picked = Picking::view_proj(view_proj, 30, 10, 40, 20);
+    let point = view_proj.inverse().project_point3(glam::Vec3::new(0.525, -0.05, 0.5));
+    let clip = picked.project_point3(point);
+    assert!(clip.abs_diff_eq(glam::Vec3::new(0.0, 0.0, 0.5), 1e-4), "{:?}", clip);
+    // The edges of the pixel are the edges of clip space.
+    let corner = view_proj.inverse().project_point3(glam::Vec3::new(0.55, -0.1, 0.5));
+    assert!(picked.project_point3(corner).abs_diff_eq(glam::Vec3::new(1.0, -1.0, 0.5), 1e-4));
+  }
+
+  #[test] fn camera() {
+    // Through the camera's own projection, of two points in the middle pixel the nearer one is in front.
+    let camera = crate::Camera::default();
+    let view_proj = camera.projection_matrix(2.0) * camera.view_matrix();
+    let picked = Picking::view_proj(view_proj, 20, 10, 41, 21);
+    let ray = camera.ray_from_eye([0.0, 0.0]);
+    let (near, far) = (picked.project_point3(ray.at(1.0)), picked.project_point3(ray.at(3.0)));
+    assert!(near.truncate().abs_diff_eq(glam::Vec2::ZERO, 1e-4) && far.truncate().abs_diff_eq(glam::Vec2::ZERO, 1e-4));
+    assert!(0.0 < near.z && near.z < far.z && far.z < 1.0);
+  }
+}
@@ -0,0 +1,31 @@
+// Object ids and depth for picking
+
+struct Locals {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    // One more than the index of the object, zero is the background
+    id: u32
+}
+
+@group(1) @binding(0) var<uniform> locals: Locals;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>
+}
+
+@vertex fn vs_main(model: Vertex) -> @builtin(position) vec4<f32> {
+    return globals.view_proj * locals.model * vec4<f32>(model.position, 1.0);
+}
+
+struct Output {
+    @location(0) id: u32,
+    @location(1) depth: f32
+}
+
+@fragment fn fs_main(@builtin(position) position: vec4<f32>) -> Output {
+    return Output(locals.id, position.z);
+}
@@ -0,0 +1,186 @@
+use crate::context::TargetInfo;
+use crate::text::{Anchor, Atlas, Font, Label};
+
+/// Width and height of the glyph atlas.
+const ATLAS_SIZE:u32 = 1024;
+
+#[repr(C)] #[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct Glyph {
+  /// Pixels from the top left of the target, or a point in the world when `world` is set.
+  anchor: [f32;3],
+  world: u32,
+  /// Top left corner relative to the anchor and size, in pixels.
+  offset: [f32;2],
+  size: [f32;2],
+  uv: [f32;4],
+  color: [f32;4],
+}
+
+impl Glyph {
+  fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    const ATTRIBUTES: [wgpu::VertexAttribute;6] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32, 2 => Float32x2, 3 => Float32x2, 4 => Float32x4, 5 => Float32x4];
+    wgpu::VertexBufferLayout{array_stride: std::mem::size_of::<Glyph>() as wgpu::BufferAddress, step_mode: wgpu::VertexStepMode::Instance, attributes: &ATTRIBUTES}
+  }
+}
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+  viewport: [f32;2],
+  _padding: [f32;2],
+}
+
+/// Text as instanced quads sampling a glyph atlas, blended over a resolved target without depth testing.
+/// The pipeline is rebuilt when the format of the target changes.
+pub struct TextPass {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  pipeline: wgpu::RenderPipeline,
+  atlas: Atlas,
+  texture: wgpu::Texture,
+  params: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// Instance buffer and its size in bytes, grown as needed.
+  glyphs: (wgpu::Buffer, usize),
+  glyph_count: u32,
+}
+
+impl TextPass {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Text Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("text.wgsl")).into()),
+    });
+    let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("text"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0, count: None, visibility: wgpu::ShaderStages::VERTEX,
+          ty: wgpu::BindingType::Buffer{ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None}},
+        wgpu::BindGroupLayoutEntry {
+          binding: 1, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture{multisampled: false, view_dimension: wgpu::TextureViewDimension::D2, sample_type: wgpu::TextureSampleType::Float{filterable: true}}},
+        wgpu::BindGroupLayoutEntry {
+          binding: 2, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)}]});
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("Text Pipeline Layout"),
+      bind_group_layouts: &[globals, &params_layout], push_constant_ranges: &[]});
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Text Params"),
+      size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Glyph Atlas"),
+      size: wgpu::Extent3d{width: ATLAS_SIZE, height: ATLAS_SIZE, depth_or_array_layers: 1},
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: wgpu::TextureFormat::R8Unorm,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST});
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Glyphs are drawn at whole pixels at the size they were rasterized, linear filtering only smooths the edges.
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("Glyph Sampler"),
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()});
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("text"), layout: &params_layout,
+      entries: &[
+        wgpu::BindGroupEntry{binding: 0, resource: params.as_entire_binding()},
+        wgpu::BindGroupEntry{binding: 1, resource: wgpu::BindingResource::TextureView(&view)},
+        wgpu::BindGroupEntry{binding: 2, resource: wgpu::BindingResource::Sampler(&sampler)}]});
+    let pipeline = Self::create_pipeline(device, &layout, &shader, info.format);
+    TextPass{shader, layout, format: info.format, pipeline, atlas: Atlas::new(ATLAS_SIZE), texture, params, bind_group,
+      glyphs: Self::instances(device, 0), glyph_count: 0}
+  }
+
+  fn instances(device:&wgpu::Device, size:usize)->(wgpu::Buffer, usize) {
+    let size = size.max(256);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("Glyph Instances"),
+      size: size as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    (buffer, size)
+  }
+
+  fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, format:wgpu::TextureFormat)->wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("text"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState{buffers: &[Glyph::desc()], module: shader, entry_point: "vs_glyph"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format, blend: Some(wgpu::BlendState::ALPHA_BLENDING), write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: "fs_glyph"}),
+      multiview: None,
+    })
+  }
+
+  /// The glyphs of every label, or `None` when they don't all fit in the atlas.
+  fn place(&mut self, fonts:&[Font], labels:&[Label])->Option<Vec<Glyph>> {
+    let mut glyphs = vec![];
+    for label in labels {
+      let font = match fonts.get(label.style.font.0 as usize) { Some(font) => font, None => continue };
+      let (placed, height) = self.atlas.layout(label.style.font, font, &label.text, &label.style)?;
+      // World labels stand on their point, so the text is moved up by its height.
+      let (anchor, world, shift) = match label.anchor {
+        Anchor::Screen([x, y]) => ([x, y, 0.0], 0, 0.0),
+        Anchor::World(p) => (p.into(), 1, -height.round()),
+      };
+      let color = label.style.color.to_linear();
+      glyphs.extend(placed.iter().map(|p| Glyph{anchor, world, offset: [p.offset[0], p.offset[1] + shift], size: p.size, uv: p.uv, color}));
+    }
+    Some(glyphs)
+  }
+
+  /// Match the target that is about to be drawn, rasterize glyphs that are new and upload the labels.
+  /// When the atlas is full it is cleared and filled with only the glyphs of this frame.
+  pub fn prepare(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, info:&TargetInfo, size:wgpu::Extent3d, fonts:&[Font], labels:&[Label]) {
+    if self.format != info.format {
+      self.pipeline = Self::create_pipeline(device, &self.layout, &self.shader, info.format);
+      self.format = info.format;
+    }
+    let glyphs = match self.place(fonts, labels) {
+      Some(glyphs) => glyphs,
+      None => {
+        self.atlas.clear();
+        self.place(fonts, labels).unwrap_or_else(|| { log::warn!("Text does not fit in the glyph atlas"); vec![] })
+      }
+    };
+    if let Some((top, bottom)) = self.atlas.dirty.take() {
+      let start = (top * self.atlas.size) as usize;
+      queue.write_texture(
+        wgpu::ImageCopyTexture{texture: &self.texture, mip_level: 0, origin: wgpu::Origin3d{x: 0, y: top, z: 0}, aspect: wgpu::TextureAspect::All},
+        &self.atlas.pixels[start..(bottom * self.atlas.size) as usize],
+        wgpu::ImageDataLayout{offset: 0, bytes_per_row: std::num::NonZeroU32::new(self.atlas.size), rows_per_image: None},
+        wgpu::Extent3d{width: self.atlas.size, height: bottom - top, depth_or_array_layers: 1});
+    }
+    queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&Params{viewport: [size.width as f32, size.height as f32], _padding: [0.0;2]}));
+    let bytes:&[u8] = bytemuck::cast_slice(&glyphs);
+    if bytes.len() > self.glyphs.1 { self.glyphs = Self::instances(device, bytes.len().next_power_of_two()) }
+    if !bytes.is_empty() { queue.write_buffer(&self.glyphs.0, 0, bytes) }
+    self.glyph_count = glyphs.len() as u32;
+  }
+
+  /// Glyphs uploaded by the last [`TextPass::prepare`], each drawn as six vertices.
+  pub fn glyph_count(&self)->u32 { self.glyph_count }
+
+  /// Start a pass over `view`, which has to be single sampled, bind the globals and draw the text with it.
+  pub fn pass<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder, view:&'a wgpu::TextureView)->wgpu::RenderPass<'a> {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("Text"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Load, store: true}})],
+      depth_stencil_attachment: None});
+    pass.set_pipeline(&self.pipeline);
+    pass.set_bind_group(1, &self.bind_group, &[]);
+    pass.set_vertex_buffer(0, self.glyphs.0.slice(..));
+    pass
+  }
+}
@@ -0,0 +1,55 @@
+// Glyphs sampled from an atlas, placed in pixels from a point on the screen or a point in the world
+
+struct Params {
+    // Size of the target in pixels
+    viewport: vec2<f32>
+}
+
+@group(1) @binding(0) var<uniform> params: Params;
+@group(1) @binding(1) var atlas: texture_2d<f32>;
+@group(1) @binding(2) var atlas_sampler: sampler;
+
+struct Glyph {
+    @location(0) anchor: vec3<f32>,
+    @location(1) world: u32,
+    @location(2) offset: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    @location(4) uv: vec4<f32>,
+    @location(5) color: vec4<f32>
+}
+
+struct GlyphOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>
+}
+
+@vertex fn vs_glyph(@builtin(vertex_index) index: u32, glyph: Glyph) -> GlyphOutput {
+    var out: GlyphOutput;
+    // Two triangles over the corners (0, 0), (1, 0), (0, 1), (1, 1)
+    var corners = array<vec2<f32>, 6>(vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(0.0, 1.0), vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0));
+    let c = corners[index];
+    var anchor = glyph.anchor.xy;
+    if (glyph.world != 0u) {
+        let clip = globals.view_proj * vec4<f32>(glyph.anchor, 1.0);
+        // Behind the eye
+        if (clip.w <= 0.0) {
+            out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+            return out;
+        }
+        let ndc = clip.xy / clip.w;
+        anchor = vec2<f32>(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * params.viewport;
+    }
+    // Snapped to whole pixels so glyphs are drawn as sharp as they were rasterized
+    let pixel = floor(anchor + 0.5) + glyph.offset + glyph.size * c;
+    out.clip_position = vec4<f32>(pixel.x / params.viewport.x * 2.0 - 1.0, 1.0 - pixel.y / params.viewport.y * 2.0, 0.0, 1.0);
+    out.uv = mix(glyph.uv.xy, glyph.uv.zw, c);
+    out.color = glyph.color;
+    return out;
+}
+
+@fragment fn fs_glyph(in: GlyphOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    if (coverage <= 0.0) { discard; }
+    return encode(vec4<f32>(in.color.rgb, in.color.a * coverage));
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use crate::context::TargetInfo;
+use crate::texture::Texture;
+use crate::ui::Ui;
+
+#[repr(C)] #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+  /// Size of the target in points.
+  screen: [f32;2],
+  _padding: [f32;2],
+}
+
+fn vertex_desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+  const ATTRIBUTES: [wgpu::VertexAttribute;3] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4];
+  wgpu::VertexBufferLayout{array_stride: std::mem::size_of::<egui::epaint::Vertex>() as wgpu::BufferAddress, step_mode: wgpu::VertexStepMode::Vertex, attributes: &ATTRIBUTES}
+}
+
+/// A mesh of the UI, where its vertices and indices start in the buffers, and the pixels it is clipped to.
+struct Draw {
+  texture: egui::TextureId,
+  indices: std::ops::Range<u32>,
+  base_vertex: i32,
+  clip: [u32;4],
+}
+
+/// The triangles egui tessellated, blended over a resolved target without depth testing. Textures egui manages
+/// are kept here, [`egui::TextureId::User`] refers to a [`crate::TextureRef`] by its index.
+/// The pipeline is rebuilt when the format of the target changes.
+pub struct UiPass {
+  shader: wgpu::ShaderModule,
+  layout: wgpu::PipelineLayout,
+  format: wgpu::TextureFormat,
+  pipeline: wgpu::RenderPipeline,
+  texture_layout: wgpu::BindGroupLayout,
+  sampler: wgpu::Sampler,
+  textures: HashMap<u64, (wgpu::Texture, wgpu::BindGroup)>,
+  params: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// Vertex and index buffers and their sizes in bytes, grown as needed.
+  vertices: (wgpu::Buffer, usize),
+  indices: (wgpu::Buffer, usize),
+  draws: Vec<Draw>,
+}
+
+impl UiPass {
+  pub fn new(device:&wgpu::Device, globals:&wgpu::BindGroupLayout, textures:&wgpu::BindGroupLayout, info:&TargetInfo)->Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("UI Shader"),
+      source: wgpu::ShaderSource::Wgsl(concat!(include_str!("srgb.wgsl"), include_str!("globals.wgsl"), include_str!("ui.wgsl")).into()),
+    });
+    let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("ui"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0, count: None, visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer{ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None}}]});
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("UI Pipeline Layout"),
+      bind_group_layouts: &[globals, &params_layout, textures], push_constant_ranges: &[]});
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("UI Params"),
+      size: std::mem::size_of::<Params>() as wgpu::BufferAddress,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("ui"), layout: &params_layout,
+      entries: &[wgpu::BindGroupEntry{binding: 0, resource: params.as_entire_binding()}]});
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("UI Sampler"),
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      ..Default::default()});
+    let pipeline = Self::create_pipeline(device, &layout, &shader, info.format);
+    UiPass{shader, layout, format: info.format, pipeline, texture_layout: Texture::bind_group_layout(device), sampler, textures: HashMap::new(), params, bind_group,
+      vertices: Self::buffer(device, 0, wgpu::BufferUsages::VERTEX), indices: Self::buffer(device, 0, wgpu::BufferUsages::INDEX), draws: vec![]}
+  }
+
+  fn buffer(device:&wgpu::Device, size:usize, usage:wgpu::BufferUsages)->(wgpu::Buffer, usize) {
+    let size = size.max(256);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("UI Geometry"),
+      size: size as wgpu::BufferAddress,
+      usage: usage | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false});
+    (buffer, size)
+  }
+
+  fn create_pipeline(device:&wgpu::Device, layout:&wgpu::PipelineLayout, shader:&wgpu::ShaderModule, format:wgpu::TextureFormat)->wgpu::RenderPipeline {
+    // egui colors are premultiplied.
+    let blend = wgpu::BlendState {
+      color: wgpu::BlendComponent{src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha, operation: wgpu::BlendOperation::Add},
+      alpha: wgpu::BlendComponent{src_factor: wgpu::BlendFactor::OneMinusDstAlpha, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add}};
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("ui"),
+      layout: Some(layout),
+      vertex: wgpu::VertexState{buffers: &[vertex_desc()], module: shader, entry_point: "vs_main"},
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      fragment: Some(wgpu::FragmentState {
+        targets: &[Some(wgpu::ColorTargetState{format, blend: Some(blend), write_mask: wgpu::ColorWrites::ALL})],
+        module: shader, entry_point: "fs_main"}),
+      multiview: None,
+    })
+  }
+
+  /// Apply a change to a texture egui manages, creating it when the change covers all of it.
+  fn set_texture(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, id:u64, delta:&egui::epaint::ImageDelta) {
+    let (pixels, [width, height]):(Vec<egui::Color32>, _) = match &delta.image {
+      egui::ImageData::Color(image) => (image.pixels.clone(), image.size),
+      egui::ImageData::Font(image) => (image.srgba_pixels(1.0).collect(), image.size),
+    };
+    let size = wgpu::Extent3d{width: width as u32, height: height as u32, depth_or_array_layers: 1};
+    if delta.pos.is_none() {
+      let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("UI Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST});
+      let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+      let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("ui texture"), layout: &self.texture_layout,
+        entries: &[
+          wgpu::BindGroupEntry{binding: 0, resource: wgpu::BindingResource::TextureView(&view)},
+          wgpu::BindGroupEntry{binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler)}]});
+      self.textures.insert(id, (texture, bind_group));
+    }
+    let texture = match self.textures.get(&id) { Some((texture, _)) => texture, None => return };
+    let [x, y] = delta.pos.unwrap_or([0, 0]);
+    queue.write_texture(
+      wgpu::ImageCopyTexture{texture, mip_level: 0, origin: wgpu::Origin3d{x: x as u32, y: y as u32, z: 0}, aspect: wgpu::TextureAspect::All},
+      bytemuck::cast_slice(&pixels),
+      wgpu::ImageDataLayout{offset: 0, bytes_per_row: std::num::NonZeroU32::new(4 * size.width), rows_per_image: None},
+      size);
+  }
+
+  /// Match the target that is about to be drawn, apply the texture changes and upload the meshes of the last frame
+  /// of the UI, nothing is drawn when it didn't run.
+  pub fn prepare(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, info:&TargetInfo, size:wgpu::Extent3d, ui:&Ui) {
+    if self.format != info.format {
+      self.pipeline = Self::create_pipeline(device, &self.layout, &self.shader, info.format);
+      self.format = info.format;
+    }
+    self.draws.clear();
+    let (primitives, textures) = match &ui.output { Some(output) => output, None => return };
+    let pixels_per_point = ui.pixels_per_point;
+    for (id, delta) in &textures.set {
+      if let egui::TextureId::Managed(id) = id { self.set_texture(device, queue, *id, delta) }
+    }
+    queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&Params{screen: [size.width as f32 / pixels_per_point, size.height as f32 / pixels_per_point], _padding: [0.0;2]}));
+    let (mut vertices, mut indices) = (vec![], vec![]);
+    for egui::ClippedPrimitive{clip_rect, primitive} in primitives {
+      let mesh = match primitive { egui::epaint::Primitive::Mesh(mesh) => mesh, egui::epaint::Primitive::Callback(_) => continue };
+      // The clip rectangle in whole pixels inside the target, empty ones are skipped.
+      let (min, max) = (clip_rect.min.to_vec2() * pixels_per_point, clip_rect.max.to_vec2() * pixels_per_point);
+      let (x0, y0) = ((min.x.round().max(0.0) as u32).min(size.width), (min.y.round().max(0.0) as u32).min(size.height));
+      let (x1, y1) = ((max.x.round().max(0.0) as u32).min(size.width), (max.y.round().max(0.0) as u32).min(size.height));
+      if x1 <= x0 || y1 <= y0 || mesh.indices.is_empty() { continue }
+      let start = indices.len() as u32;
+      self.draws.push(Draw{texture: mesh.texture_id, indices: start..start + mesh.indices.len() as u32, base_vertex: vertices.len() as i32, clip: [x0, y0, x1 - x0, y1 - y0]});
+      vertices.extend_from_slice(&mesh.vertices);
+      indices.extend_from_slice(&mesh.indices);
+    }
+    for (buffer, bytes, usage) in [(&mut self.vertices, bytemuck::cast_slice::<_, u8>(&vertices), wgpu::BufferUsages::VERTEX), (&mut self.indices, bytemuck::cast_slice(&indices), wgpu::BufferUsages::INDEX)] {
+      if bytes.len() > buffer.1 { *buffer = Self::buffer(device, bytes.len().next_power_of_two(), usage) }
+      if !bytes.is_empty() { queue.write_buffer(&buffer.0, 0, bytes) }
+    }
+    for id in &textures.free {
+      if let egui::TextureId::Managed(id) = id { self.textures.remove(id); }
+    }
+  }
+
+  pub fn is_empty(&self)->bool { self.draws.is_empty() }
+
+  /// Start a pass over `view`, which has to be single sampled, and draw the UI with the globals bound to group 0.
+  pub fn draw<'a>(&'a self, encoder:&'a mut wgpu::CommandEncoder, view:&'a wgpu::TextureView, globals:&'a wgpu::BindGroup, textures:&'a [Texture]) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("UI"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment{view, resolve_target: None,
+        ops: wgpu::Operations{load: wgpu::LoadOp::Load, store: true}})],
+      depth_stencil_attachment: None});
+    pass.set_pipeline(&self.pipeline);
+    pass.set_bind_group(0, globals, &[]);
+    pass.set_bind_group(1, &self.bind_group, &[]);
+    pass.set_vertex_buffer(0, self.vertices.0.slice(..));
+    pass.set_index_buffer(self.indices.0.slice(..), wgpu::IndexFormat::Uint32);
+    for draw in &self.draws {
+      let bind_group = match draw.texture {
+        egui::TextureId::Managed(id) => self.textures.get(&id).map(|(_, b)| b),
+        egui::TextureId::User(id) => textures.get(id as usize).map(|t| &t.bind_group),
+      };
+      let bind_group = match bind_group { Some(b) => b, None => continue };
+      let [x, y, width, height] = draw.clip;
+      pass.set_scissor_rect(x, y, width, height);
+      pass.set_bind_group(2, bind_group, &[]);
+      pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+    }
+  }
+}
@@ -0,0 +1,40 @@
+// Triangles of an egui frame, in points from the top left with premultiplied sRGB vertex colors
+
+struct Params {
+    // Size of the target in points
+    screen: vec2<f32>
+}
+
+@group(1) @binding(0) var<uniform> params: Params;
+@group(2) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1) var s_diffuse: sampler;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>
+}
+
+@vertex fn vs_main(in: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position.x / params.screen.x * 2.0 - 1.0, 1.0 - in.position.y / params.screen.y * 2.0, 0.0, 1.0);
+    out.uv = in.uv;
+    // Decoded without the premultiplied alpha, which is put back after
+    let a = in.color.a;
+    let rgb = select(vec3<f32>(0.0), in.color.rgb / a, a > 0.0);
+    out.color = vec4<f32>(srgb_decode(rgb) * a, a);
+    return out;
+}
+
+@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(t_diffuse, s_diffuse, in.uv);
+    if (globals.encode_srgb == 0u || color.a <= 0.0) { return color; }
+    // Encoded without the premultiplied alpha, which is put back after
+    return vec4<f32>(srgb_encode(color.rgb / color.a) * color.a, color.a);
+}
@@ -0,0 +1,77 @@
+use glam::Vec3;
+use super::Particles;
+
+/// A condition on the positions of some particles, solved with XPBD. The compliance is the
+/// inverse stiffness, zero makes the constraint rigid independently of the time step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constraint {
+  /// Keep two particles `rest` apart.
+  Distance{a:usize, b:usize, rest:f32, compliance:f32},
+  /// Keep the vertices opposite a shared edge `rest` apart, which resists folding along the edge.
+  Bending{a:usize, b:usize, rest:f32, compliance:f32},
+  /// Keep the volume of a tetrahedron at `rest`.
+  Volume{tet:[usize;4], rest:f32, compliance:f32},
+}
+
+impl Constraint {
+  /// Move the particles towards satisfying the constraint, weighted by their inverse masses.
+  pub fn solve(&self, p:&mut Particles, h:f32) {
+    match *self {
+      Constraint::Distance{a, b, rest, compliance} | Constraint::Bending{a, b, rest, compliance} => {
+        let delta = p.positions[a] - p.positions[b];
+        let length = delta.length();
+        if length == 0.0 { return }
+        let n = delta / length;
+        apply(p, &[a, b], &[n, -n], length - rest, compliance, h);
+      }
+      Constraint::Volume{tet, rest, compliance} => {
+        // Each face gradient points away from the vertex opposite to it.
+        const FACES:[[usize;3];4] = [[1, 3, 2], [0, 2, 3], [0, 3, 1], [0, 1, 2]];
+        let x = tet.map(|i| p.positions[i]);
+        let gradients = FACES.map(|[i, j, k]| (x[j] - x[i]).cross(x[k] - x[i]) / 6.0);
+        let volume = tet_volume(&p.positions, tet);
+        apply(p, &tet, &gradients, volume - rest, compliance, h);
+      }
+    }
+  }
+}
+
+/// The XPBD update for a constraint with value `c` and the given gradients.
+fn apply(p:&mut Particles, indices:&[usize], gradients:&[Vec3], c:f32, compliance:f32, h:f32) {
+  let w:f32 = indices.iter().zip(gradients).map(|(&i, g)| p.inv_masses[i] * g.length_squared()).sum();
+  let alpha = compliance / (h * h);
+  if w + alpha == 0.0 { return }
+  let lambda = -c / (w + alpha);
+  for (&i, g) in indices.iter().zip(gradients) { p.positions[i] += *g * (lambda * p.inv_masses[i]); }
+}
+
+pub(crate) fn tet_volume(positions:&[Vec3], [a, b, c, d]:[usize;4])->f32 {
+  let x0 = positions[a];
+  (positions[b] - x0).cross(positions[c] - x0).dot(positions[d] - x0) / 6.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn volume() {
+    let mut p = Particles::default();
+    for v in [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z] { p.push(v, 1.0); }
+    let tet = [0, 1, 2, 3];
+    assert!((tet_volume(&p.positions, tet) - 1.0 / 6.0).abs() < 1e-6);
+    let constraint = Constraint::Volume{tet, rest: 1.0 / 6.0, compliance: 0.0};
+    for v in &mut p.positions { *v *= 0.5 }
+    for _ in 0..20 { constraint.solve(&mut p, 0.01) }
+    assert!((tet_volume(&p.positions, tet) - 1.0 / 6.0).abs() < 1e-4);
+  }
+
+  #[test] fn distance() {
+    let mut p = Particles::default();
+    p.push(Vec3::ZERO, 1.0);
+    p.push(Vec3::new(2.0, 0.0, 0.0), 3.0);
+    Constraint::Distance{a: 0, b: 1, rest: 1.0, compliance: 0.0}.solve(&mut p, 0.01);
+    // The lighter particle moves three times as far.
+    assert!((p.positions[0] - Vec3::new(0.75, 0.0, 0.0)).length() < 1e-6);
+    assert!((p.positions[1] - Vec3::new(1.75, 0.0, 0.0)).length() < 1e-6);
+  }
+}
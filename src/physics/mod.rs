@@ -0,0 +1,296 @@
+//! Position based dynamics in the style of Ten Minute Physics, independent of rendering.
+//! Particles and rigid bodies are moved by gravity, then projected onto their constraints in small substeps.
+//! Rigid bodies are posed by motors and move with a velocity bivector, see [`RigidBody`].
+//! Cloth and soft bodies are particles made from a [`Mesh`] or [`TetMesh`], copy them back with [`World::write_mesh`]
+//! and stream them to the GPU with [`crate::Cx::update_mesh`] every frame.
+
+mod constraint;
+mod rigid;
+mod soft;
+
+pub use constraint::Constraint;
+pub use rigid::{bivector, bivector_parts, Joint, MassProperties, RigidBody};
+pub use soft::{SelfCollision, TetMesh};
+
+use glam::Vec3;
+use crate::mesh::Mesh;
+
+/// Point masses, stored as parallel arrays. An inverse mass of zero pins a particle in place.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Particles {
+  pub positions: Vec<Vec3>,
+  pub previous: Vec<Vec3>,
+  pub velocities: Vec<Vec3>,
+  pub inv_masses: Vec<f32>,
+}
+
+impl Particles {
+  pub fn len(&self)->usize { self.positions.len() }
+
+  pub fn is_empty(&self)->bool { self.positions.is_empty() }
+
+  /// Add a particle and return its index, a mass of zero makes it immovable.
+  pub fn push(&mut self, position:Vec3, mass:f32)->usize {
+    self.positions.push(position);
+    self.previous.push(position);
+    self.velocities.push(Vec3::ZERO);
+    self.inv_masses.push(if mass > 0.0 { 1.0 / mass } else { 0.0 });
+    self.len() - 1
+  }
+}
+
+/// Particles, rigid bodies and the constraints between them, advanced with the substepped XPBD solver.
+#[derive(Clone, Debug)]
+pub struct World {
+  pub particles: Particles,
+  pub constraints: Vec<Constraint>,
+  pub bodies: Vec<RigidBody>,
+  pub joints: Vec<Joint>,
+  pub self_collisions: Vec<SelfCollision>,
+  pub gravity: Vec3,
+  /// Solver steps per [`World::step`], more substeps make stiff constraints converge.
+  pub substeps: u32,
+  /// Height of a ground plane facing up, particles that sink below it are pushed back with friction.
+  pub ground: Option<f32>,
+}
+
+impl Default for World {
+  fn default() -> Self {
+    World{particles: Particles::default(), constraints: Vec::new(), bodies: Vec::new(), joints: Vec::new(), self_collisions: Vec::new(), gravity: Vec3::new(0.0, -9.81, 0.0), substeps: 10, ground: Some(0.0)}
+  }
+}
+
+impl World {
+  pub fn new()->Self { Self::default() }
+
+  pub fn add_particle(&mut self, position:Vec3, mass:f32)->usize { self.particles.push(position, mass) }
+
+  /// Hold a particle where it is, like the corners of a hanging cloth.
+  pub fn pin(&mut self, particle:usize) {
+    self.particles.inv_masses[particle] = 0.0;
+    self.particles.velocities[particle] = Vec3::ZERO;
+  }
+
+  /// Let a pinned particle move again with the given mass.
+  pub fn unpin(&mut self, particle:usize, mass:f32) {
+    self.particles.inv_masses[particle] = if mass > 0.0 { 1.0 / mass } else { 0.0 };
+  }
+
+  /// Keep two particles at their current distance, a compliance of zero is perfectly stiff.
+  pub fn add_distance(&mut self, a:usize, b:usize, compliance:f32) {
+    let rest = self.particles.positions[a].distance(self.particles.positions[b]);
+    self.constraints.push(Constraint::Distance{a, b, rest, compliance});
+  }
+
+  /// Resist folding along the edge between two triangles by holding their opposite vertices `a` and `b` apart.
+  pub fn add_bending(&mut self, a:usize, b:usize, compliance:f32) {
+    let rest = self.particles.positions[a].distance(self.particles.positions[b]);
+    self.constraints.push(Constraint::Bending{a, b, rest, compliance});
+  }
+
+  /// Keep the current volume of a tetrahedron, the vertices are ordered so the volume is positive.
+  pub fn add_volume(&mut self, tet:[usize;4], compliance:f32) {
+    let rest = constraint::tet_volume(&self.particles.positions, tet);
+    self.constraints.push(Constraint::Volume{tet, rest, compliance});
+  }
+
+  /// Add a particle per vertex of a mesh, held together by its edges and folds.
+  /// Returns the index of the first particle, the rest follow in vertex order.
+  pub fn add_mesh(&mut self, mesh:&Mesh, mass:f32, stretch_compliance:f32, bend_compliance:f32)->usize {
+    let first = self.particles.len();
+    for &v in &mesh.vertices { self.add_particle(Vec3::from(v), mass); }
+    // Each edge is visited from both of its triangles in a closed mesh, the opposite vertices of
+    // the pair are the ones to keep apart for bending.
+    let mut edges:std::collections::BTreeMap<(u32, u32), u32> = std::collections::BTreeMap::new();
+    for t in mesh.indices.chunks_exact(3) {
+      for k in 0..3 {
+        let (a, b, opposite) = (t[k], t[(k + 1) % 3], t[(k + 2) % 3]);
+        match edges.insert((a.min(b), a.max(b)), opposite) {
+          None => self.add_distance(first + a as usize, first + b as usize, stretch_compliance),
+          Some(other) => self.add_bending(first + other as usize, first + opposite as usize, bend_compliance),
+        }
+      }
+    }
+    first
+  }
+
+  /// Keep `count` particles starting at `first` a `thickness` apart, so a cloth can't pass through itself.
+  pub fn add_self_collision(&mut self, first:usize, count:usize, thickness:f32) {
+    self.self_collisions.push(SelfCollision::new(&self.particles, first, count, thickness));
+  }
+
+  /// Add a particle per vertex of a tetrahedral mesh, held together by the edges and volumes of its tetrahedra.
+  /// The mass of each tetrahedron is spread over its vertices. Returns the index of the first particle.
+  pub fn add_tet_mesh(&mut self, mesh:&TetMesh, density:f32, edge_compliance:f32, volume_compliance:f32)->usize {
+    let first = self.particles.len();
+    let mut masses = vec![0.0; mesh.vertices.len()];
+    for &tet in &mesh.tets {
+      for i in tet { masses[i as usize] += density * mesh.volume(tet).abs() / 4.0 }
+    }
+    for (&v, &mass) in mesh.vertices.iter().zip(&masses) { self.add_particle(v, mass); }
+    let mut edges = std::collections::BTreeSet::new();
+    for &tet in &mesh.tets {
+      for (k, &a) in tet.iter().enumerate() {
+        for &b in &tet[k + 1..] {
+          if edges.insert((a.min(b), a.max(b))) { self.add_distance(first + a as usize, first + b as usize, edge_compliance) }
+        }
+      }
+      self.add_volume(tet.map(|i| first + i as usize), volume_compliance);
+    }
+    first
+  }
+
+  pub fn add_body(&mut self, body:RigidBody)->usize {
+    self.bodies.push(body);
+    self.bodies.len() - 1
+  }
+
+  /// Pin two bodies together at a world space point, where they keep turning freely.
+  pub fn add_ball(&mut self, a:usize, b:usize, anchor:Vec3, compliance:f32) {
+    let anchors = self.anchors(a, b, anchor);
+    self.joints.push(Joint::Ball{a, b, anchors, compliance});
+  }
+
+  /// Join two bodies so they only turn relative to each other about a world space axis through `anchor`.
+  pub fn add_hinge(&mut self, a:usize, b:usize, anchor:Vec3, axis:Vec3, compliance:f32) {
+    let anchors = self.anchors(a, b, anchor);
+    let axes = [self.bodies[a].direction_to_body(axis), self.bodies[b].direction_to_body(axis)];
+    self.joints.push(Joint::Hinge{a, b, anchors, axes, compliance});
+  }
+
+  /// Weld two bodies together in their current relative pose.
+  pub fn add_fixed(&mut self, a:usize, b:usize, anchor:Vec3, compliance:f32) {
+    let anchors = self.anchors(a, b, anchor);
+    let axes = [Vec3::X, Vec3::Y].map(|axis| [self.bodies[a].direction_to_body(axis), self.bodies[b].direction_to_body(axis)]);
+    self.joints.push(Joint::Fixed{a, b, anchors, axes, compliance});
+  }
+
+  fn anchors(&self, a:usize, b:usize, anchor:Vec3)->[Vec3;2] { [self.bodies[a].to_body(anchor), self.bodies[b].to_body(anchor)] }
+
+  /// Advance the simulation by `dt` seconds.
+  pub fn step(&mut self, dt:f32) {
+    if self.substeps == 0 || dt <= 0.0 { return }
+    let h = dt / self.substeps as f32;
+    for _ in 0..self.substeps { self.substep(h) }
+  }
+
+  fn substep(&mut self, h:f32) {
+    for body in &mut self.bodies { body.integrate(h, self.gravity) }
+    for joint in &self.joints { joint.solve(&mut self.bodies, h) }
+    for body in &mut self.bodies { body.update_velocity(h) }
+    let p = &mut self.particles;
+    for i in 0..p.len() {
+      if p.inv_masses[i] == 0.0 { continue }
+      p.velocities[i] += self.gravity * h;
+      p.previous[i] = p.positions[i];
+      p.positions[i] += p.velocities[i] * h;
+    }
+    for constraint in &self.constraints { constraint.solve(p, h) }
+    for collision in &self.self_collisions { collision.solve(p) }
+    if let Some(ground) = self.ground {
+      for i in 0..p.len() {
+        if p.inv_masses[i] == 0.0 || p.positions[i].y >= ground { continue }
+        // Sticking to where the substep started acts as static friction.
+        p.positions[i] = p.previous[i];
+        p.positions[i].y = ground;
+      }
+    }
+    for i in 0..p.len() {
+      if p.inv_masses[i] == 0.0 { continue }
+      p.velocities[i] = (p.positions[i] - p.previous[i]) / h;
+    }
+  }
+
+  /// Copy the positions of the particles starting at `first` into the vertices of a mesh.
+  pub fn write_mesh(&self, first:usize, mesh:&mut Mesh) {
+    let positions = &self.particles.positions[first..first + mesh.vertices.len()];
+    for (v, p) in mesh.vertices.iter_mut().zip(positions) { *v = (*p).into(); }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn free_fall() {
+    let mut world = World{ground: None, substeps: 100, ..World::new()};
+    let p = world.add_particle(Vec3::ZERO, 1.0);
+    world.step(1.0);
+    // Semi implicit Euler lands slightly below the exact -g/2.
+    assert!((world.particles.positions[p].y + 9.81 / 2.0).abs() < 0.1);
+    assert!((world.particles.velocities[p].y + 9.81).abs() < 1e-3);
+  }
+
+  #[test] fn ground() {
+    let mut world = World::new();
+    let p = world.add_particle(Vec3::new(0.0, 1.0, 0.0), 1.0);
+    for _ in 0..120 { world.step(1.0 / 60.0) }
+    assert_eq!(world.particles.positions[p].y, 0.0);
+    assert!(world.particles.velocities[p].length() < 1e-3);
+  }
+
+  #[test] fn pendulum() {
+    let mut world = World{ground: None, ..World::new()};
+    let pivot = world.add_particle(Vec3::ZERO, 0.0);
+    let bob = world.add_particle(Vec3::new(1.0, 0.0, 0.0), 1.0);
+    world.add_distance(pivot, bob, 0.0);
+    // Close to the bottom of the swing after half a second.
+    for _ in 0..30 { world.step(1.0 / 60.0) }
+    assert_eq!(world.particles.positions[pivot], Vec3::ZERO);
+    assert!((world.particles.positions[bob].length() - 1.0).abs() < 1e-3);
+    assert!(world.particles.positions[bob].y < -0.5);
+  }
+
+  #[test] fn deterministic() {
+    let run = || {
+      let mut world = World::new();
+      let mut mesh = crate::mesh::demo_mesh();
+      for v in &mut mesh.vertices { v[1] += 1.0 }
+      let first = world.add_mesh(&mesh, 1.0, 0.0, 0.01);
+      for _ in 0..30 { world.step(1.0 / 60.0) }
+      world.write_mesh(first, &mut mesh);
+      mesh.vertices
+    };
+    let vertices = run();
+    assert_eq!(vertices, run());
+    assert!(vertices.iter().all(|v| v[1] < 1.0 && v[1] >= 0.0));
+  }
+
+  #[test] fn cloth() {
+    let mut world = World{ground: None, ..World::new()};
+    let mut mesh = Mesh::grid([1.0, 1.0], [8, 8]);
+    for v in &mut mesh.vertices { *v = [v[0], -v[2], 0.0] }
+    let first = world.add_mesh(&mesh, 0.01, 0.0, 0.1);
+    world.add_self_collision(first, mesh.vertices.len(), 0.02);
+    // Hang it from the two top corners.
+    let top:Vec<usize> = (0..mesh.vertices.len()).filter(|&i| mesh.vertices[i][1] == 0.5 && mesh.vertices[i][0].abs() == 0.5).collect();
+    for &i in &top { world.pin(first + i) }
+    for _ in 0..60 { world.step(1.0 / 60.0) }
+    world.write_mesh(first, &mut mesh);
+    assert_eq!(top.len(), 2);
+    for &i in &top { assert_eq!(mesh.vertices[i][1], 0.5) }
+    assert!(mesh.vertices.iter().all(|v| v[1] <= 0.5 + 1e-4 && v[1] > -1.5));
+  }
+
+  #[test] fn soft_body() {
+    let mut world = World::new();
+    let mut tets = TetMesh::cuboid([0.5; 3], [2; 3]);
+    for v in &mut tets.vertices { v.y += 1.0 }
+    let first = world.add_tet_mesh(&tets, 100.0, 0.0, 0.0);
+    let mass:f32 = world.particles.inv_masses.iter().map(|w| 1.0 / w).sum();
+    assert!((mass - 100.0).abs() < 1e-3);
+    for _ in 0..120 { world.step(1.0 / 60.0) }
+    // Resting on the ground with its volume kept.
+    let volume:f32 = tets.tets.iter().map(|t| constraint::tet_volume(&world.particles.positions, t.map(|i| first + i as usize))).sum();
+    assert!((volume - 1.0).abs() < 0.01, "{}", volume);
+    assert!(world.particles.positions.iter().all(|p| p.y >= 0.0 && p.y < 1.01));
+  }
+
+  #[test] fn add_mesh() {
+    let mut world = World::new();
+    world.add_mesh(&crate::mesh::demo_mesh(), 1.0, 0.0, 0.0);
+    let count = |bending:bool| world.constraints.iter().filter(|c| matches!(c, Constraint::Bending{..}) == bending).count();
+    // Three triangles in a fan share two edges.
+    assert_eq!((count(false), count(true)), (7, 2));
+  }
+}
@@ -0,0 +1,423 @@
+use g3::{Line, Motor};
+use glam::{Mat3, Quat, Vec3};
+use crate::mesh::Mesh;
+use crate::scene::identity;
+
+/// Mass distribution of a solid, with the inertia diagonalized along its principal axes.
+#[derive(Clone, Copy, Debug)]
+pub struct MassProperties {
+  pub mass: f32,
+  pub center: Vec3,
+  /// Moments of inertia about the principal axes through the center of mass.
+  pub inertia: Vec3,
+  /// Rotation from the principal axes to the axes the geometry is given in.
+  pub axes: Motor,
+}
+
+impl MassProperties {
+  /// Integrate over the volume enclosed by a closed mesh with outward facing triangles.
+  pub fn from_mesh(mesh:&Mesh, density:f32)->MassProperties {
+    // Sum signed tetrahedra between the origin and every triangle.
+    let canonical = Mat3::from_cols_array(&[2.0, 1.0, 1.0, 1.0, 2.0, 1.0, 1.0, 1.0, 2.0]);
+    let (mut volume, mut moment, mut covariance) = (0.0, Vec3::ZERO, Mat3::ZERO);
+    for t in mesh.indices.chunks_exact(3) {
+      let [a, b, c] = [t[0], t[1], t[2]].map(|i| Vec3::from(mesh.vertices[i as usize]));
+      let det = a.dot(b.cross(c));
+      let m = Mat3::from_cols(a, b, c);
+      volume += det / 6.0;
+      moment += (a + b + c) * det / 24.0;
+      covariance += m * canonical * m.transpose() * (det / 120.0);
+    }
+    let mass = density * volume;
+    let center = if volume != 0.0 { moment / volume } else { Vec3::ZERO };
+    let covariance = (covariance - outer(center, center) * volume) * density;
+    let tensor = Mat3::from_diagonal(Vec3::splat(trace(covariance))) - covariance;
+    let (inertia, axes) = principal_axes(tensor);
+    MassProperties{mass, center, inertia, axes: rotation_motor(axes)}
+  }
+
+  pub fn sphere(mass:f32, radius:f32)->MassProperties {
+    MassProperties{mass, center: Vec3::ZERO, inertia: Vec3::splat(0.4 * mass * radius * radius), axes: identity()}
+  }
+
+  pub fn cuboid(mass:f32, half_extents:Vec3)->MassProperties {
+    let s = half_extents * half_extents;
+    let inertia = Vec3::new(s.y + s.z, s.x + s.z, s.x + s.y) * (mass / 3.0);
+    MassProperties{mass, center: Vec3::ZERO, inertia, axes: identity()}
+  }
+}
+
+fn outer(a:Vec3, b:Vec3)->Mat3 { Mat3::from_cols(a * b.x, a * b.y, a * b.z) }
+
+fn trace(m:Mat3)->f32 { m.x_axis.x + m.y_axis.y + m.z_axis.z }
+
+/// Eigenvalues and eigenvectors of a symmetric matrix with cyclic Jacobi rotations, the
+/// eigenvectors are the columns of a rotation matrix.
+fn principal_axes(m:Mat3)->(Vec3, Mat3) {
+  let mut a = m.to_cols_array_2d();
+  let mut v = Mat3::IDENTITY.to_cols_array_2d();
+  for _ in 0..32 {
+    let off = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+    if off < 1e-12 * (trace(m) * trace(m)).max(1e-30) { break }
+    for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+      if a[p][q].abs() < 1e-20 { continue }
+      let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+      let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+      let c = 1.0 / (t * t + 1.0).sqrt();
+      let s = t * c;
+      for row in &mut a {
+        let (ap, aq) = (row[p], row[q]);
+        row[p] = c * ap - s * aq;
+        row[q] = s * ap + c * aq;
+      }
+      let (ap, aq) = (Vec3::from(a[p]), Vec3::from(a[q]));
+      a[p] = (ap * c - aq * s).into();
+      a[q] = (ap * s + aq * c).into();
+      for row in &mut v {
+        let (vp, vq) = (row[p], row[q]);
+        row[p] = c * vp - s * vq;
+        row[q] = s * vp + c * vq;
+      }
+    }
+  }
+  // Rows of the column array are the eigenvectors, flip one if needed to get a proper rotation.
+  let mut axes = Mat3::from_cols_array_2d(&v).transpose();
+  if axes.determinant() < 0.0 { axes.z_axis = -axes.z_axis }
+  (Vec3::new(a[0][0], a[1][1], a[2][2]), axes)
+}
+
+/// Motor rotating like a rotation matrix.
+fn rotation_motor(m:Mat3)->Motor {
+  let (axis, angle) = Quat::from_mat3(&m).normalize().to_axis_angle();
+  if angle.abs() < 1e-6 { return identity() }
+  g3::rotor(angle, axis.x, axis.y, axis.z).into()
+}
+
+/// A screw with angular part `angular` and linear part `linear`.
+pub fn bivector(angular:Vec3, linear:Vec3)->Line {
+  g3::line(linear.x, linear.y, linear.z, angular.x, angular.y, angular.z)
+}
+
+/// The angular and linear part of a screw.
+pub fn bivector_parts(b:&Line)->(Vec3, Vec3) {
+  (Vec3::new(b.e23(), b.e31(), b.e12()), Vec3::new(b.e01(), b.e02(), b.e03()))
+}
+
+/// The motor that moves along a screw for unit time, `exp(-B/2)`.
+fn screw(angular:Vec3, linear:Vec3)->Motor { (bivector(angular, linear) * -0.5).exp() }
+
+fn transform_point(m:&Motor, p:Vec3)->Vec3 { let p:[f32;3] = m(g3::point(p.x, p.y, p.z)).into(); p.into() }
+
+fn transform_direction(m:&Motor, d:Vec3)->Vec3 { transform_point(m, d) - transform_point(m, Vec3::ZERO) }
+
+/// A solid whose pose is a motor from its body frame, centered on the center of mass along its
+/// principal axes, to the world.
+#[derive(Clone, Copy, Debug)]
+pub struct RigidBody {
+  pub pose: Motor,
+  pub(crate) previous: Motor,
+  /// Velocity in the body frame, the angular velocity as the rotation part and the velocity
+  /// of the center of mass as the translation part.
+  pub velocity: Line,
+  pub inv_mass: f32,
+  /// Inverse principal moments of inertia.
+  pub inv_inertia: Vec3,
+  /// Motor from the coordinates of the geometry the body was made from to the body frame.
+  pub local: Motor,
+}
+
+impl RigidBody {
+  /// A body in the pose where its geometry appears untransformed.
+  pub fn new(properties:&MassProperties)->RigidBody {
+    let inv = |x:f32| if x > 0.0 { 1.0 / x } else { 0.0 };
+    let c = properties.center;
+    let pose = screw(Vec3::ZERO, c) * properties.axes;
+    RigidBody {
+      pose, previous: pose,
+      velocity: bivector(Vec3::ZERO, Vec3::ZERO),
+      inv_mass: inv(properties.mass),
+      inv_inertia: properties.inertia.to_array().map(inv).into(),
+      local: pose.reverse(),
+    }
+  }
+
+  /// A closed mesh of uniform density.
+  pub fn from_mesh(mesh:&Mesh, density:f32)->RigidBody { Self::new(&MassProperties::from_mesh(mesh, density)) }
+
+  /// An immovable body, for example to attach joints to the world.
+  pub fn fixed(pose:Motor)->RigidBody {
+    RigidBody{pose, previous: pose, velocity: bivector(Vec3::ZERO, Vec3::ZERO), inv_mass: 0.0, inv_inertia: Vec3::ZERO, local: identity()}
+  }
+
+  pub fn is_static(&self)->bool { self.inv_mass == 0.0 && self.inv_inertia == Vec3::ZERO }
+
+  /// Motor placing the original geometry in the world, for [`crate::Object::transform`].
+  pub fn transform(&self)->Motor { self.pose * self.local }
+
+  pub fn center(&self)->Vec3 { transform_point(&self.pose, Vec3::ZERO) }
+
+  pub fn to_world(&self, p:Vec3)->Vec3 { transform_point(&self.pose, p) }
+
+  pub fn to_body(&self, p:Vec3)->Vec3 { transform_point(&self.pose.reverse(), p) }
+
+  pub fn direction_to_world(&self, d:Vec3)->Vec3 { transform_direction(&self.pose, d) }
+
+  pub fn direction_to_body(&self, d:Vec3)->Vec3 { transform_direction(&self.pose.reverse(), d) }
+
+  /// World space velocity of the center of mass and angular velocity.
+  pub fn world_velocity(&self)->(Vec3, Vec3) {
+    let (angular, linear) = bivector_parts(&self.velocity);
+    (self.direction_to_world(angular), self.direction_to_world(linear))
+  }
+
+  /// Set the velocity from world space vectors, the linear one at the center of mass.
+  pub fn set_world_velocity(&mut self, angular:Vec3, linear:Vec3) {
+    self.velocity = bivector(self.direction_to_body(angular), self.direction_to_body(linear));
+  }
+
+  pub fn kinetic_energy(&self)->f32 {
+    let (w, v) = bivector_parts(&self.velocity);
+    let mass = if self.inv_mass > 0.0 { 1.0 / self.inv_mass } else { 0.0 };
+    0.5 * (mass * v.length_squared() + w.dot(self.inertia() * w))
+  }
+
+  /// Angular momentum about the center of mass in world space.
+  pub fn angular_momentum(&self)->Vec3 {
+    let (w, _) = bivector_parts(&self.velocity);
+    self.direction_to_world(self.inertia() * w)
+  }
+
+  fn inertia(&self)->Vec3 { self.inv_inertia.to_array().map(|x| if x > 0.0 { 1.0 / x } else { 0.0 }).into() }
+
+  /// Symplectic Euler, velocities are updated before the pose.
+  pub(crate) fn integrate(&mut self, h:f32, gravity:Vec3) {
+    self.previous = self.pose;
+    if self.is_static() { return }
+    let (mut w, mut v) = bivector_parts(&self.velocity);
+    if self.inv_mass > 0.0 { v += self.direction_to_body(gravity) * h }
+    if self.inv_inertia != Vec3::ZERO { w = self.gyroscopic(w, h) }
+    self.velocity = bivector(w, v);
+    // Move in a straight line and then turn about the center, like a free body does.
+    self.pose = (self.pose * screw(Vec3::ZERO, v * h) * screw(w * h, Vec3::ZERO)).normalized();
+  }
+
+  /// One Newton step of the implicit Euler equations `I dw/dt + w × Iw = 0`, which unlike
+  /// explicit integration doesn't gain energy while tumbling.
+  fn gyroscopic(&self, w:Vec3, h:f32)->Vec3 {
+    let inertia = Mat3::from_diagonal(self.inertia());
+    let iw = inertia * w;
+    let f = w.cross(iw) * h;
+    let jacobian = inertia + (skew(w) * inertia - skew(iw)) * h;
+    if jacobian.determinant().abs() < 1e-12 { return w }
+    w - jacobian.inverse() * f
+  }
+
+  /// Derive the velocity from how far the pose moved during a substep.
+  pub(crate) fn update_velocity(&mut self, h:f32) {
+    if self.is_static() { return }
+    // The rotation part of the change is cos(θ/2) - sin(θ/2) ω/|ω|.
+    let delta = self.previous.reverse() * self.pose;
+    let sin = Vec3::new(delta.e23(), delta.e31(), delta.e12()) * -delta.scalar().signum();
+    let angle = 2.0 * sin.length().atan2(delta.scalar().abs());
+    let w = sin.normalize_or_zero() * (angle / h);
+    let v = self.direction_to_body(self.center() - transform_point(&self.previous, Vec3::ZERO)) / h;
+    self.velocity = bivector(w, v);
+  }
+
+  /// Inverse mass felt when pushing at the body point `r` along the body direction `n`.
+  fn positional_weight(&self, r:Vec3, n:Vec3)->f32 {
+    let rn = r.cross(n);
+    self.inv_mass + rn.dot(self.inv_inertia * rn)
+  }
+
+  /// Inverse inertia felt when turning about the body direction `n`.
+  fn angular_weight(&self, n:Vec3)->f32 { n.dot(self.inv_inertia * n) }
+
+  /// Move the body as if the world space impulse `p` was applied at the body point `r`.
+  fn apply_positional(&mut self, r:Vec3, p:Vec3) {
+    let p = self.direction_to_body(p);
+    self.pose = self.pose * screw(self.inv_inertia * r.cross(p), p * self.inv_mass);
+  }
+
+  /// Turn the body as if the world space angular impulse `p` was applied.
+  fn apply_angular(&mut self, p:Vec3) {
+    let p = self.direction_to_body(p);
+    self.pose = self.pose * screw(self.inv_inertia * p, Vec3::ZERO);
+  }
+}
+
+fn skew(v:Vec3)->Mat3 { Mat3::from_cols(Vec3::new(0.0, v.z, -v.y), Vec3::new(-v.z, 0.0, v.x), Vec3::new(v.y, -v.x, 0.0)) }
+
+/// A constraint between two rigid bodies, anchors and axes are given in each body's frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Joint {
+  /// The anchors coincide, rotation is free.
+  Ball{a:usize, b:usize, anchors:[Vec3;2], compliance:f32},
+  /// A ball joint that also keeps the axes aligned, leaving one rotational degree of freedom.
+  Hinge{a:usize, b:usize, anchors:[Vec3;2], axes:[Vec3;2], compliance:f32},
+  /// A ball joint that keeps two pairs of axes aligned, the bodies move as one.
+  Fixed{a:usize, b:usize, anchors:[Vec3;2], axes:[[Vec3;2];2], compliance:f32},
+}
+
+impl Joint {
+  pub(crate) fn solve(&self, bodies:&mut [RigidBody], h:f32) {
+    let alpha = |compliance:f32| compliance / (h * h);
+    match *self {
+      Joint::Ball{a, b, anchors, compliance} => solve_anchor(bodies, a, b, anchors, alpha(compliance)),
+      Joint::Hinge{a, b, anchors, axes, compliance} => {
+        solve_axes(bodies, a, b, axes, alpha(compliance));
+        solve_anchor(bodies, a, b, anchors, alpha(compliance));
+      }
+      Joint::Fixed{a, b, anchors, axes, compliance} => {
+        for axes in axes { solve_axes(bodies, a, b, axes, alpha(compliance)) }
+        solve_anchor(bodies, a, b, anchors, alpha(compliance));
+      }
+    }
+  }
+}
+
+fn pair(bodies:&mut [RigidBody], a:usize, b:usize)->(&mut RigidBody, &mut RigidBody) {
+  assert_ne!(a, b, "a joint needs two different bodies");
+  if a < b { let (l, r) = bodies.split_at_mut(b); (&mut l[a], &mut r[0]) }
+  else { let (l, r) = bodies.split_at_mut(a); (&mut r[0], &mut l[b]) }
+}
+
+/// Pull the anchors together along the line between them.
+fn solve_anchor(bodies:&mut [RigidBody], a:usize, b:usize, anchors:[Vec3;2], alpha:f32) {
+  let (a, b) = pair(bodies, a, b);
+  let d = a.to_world(anchors[0]) - b.to_world(anchors[1]);
+  let c = d.length();
+  if c < 1e-9 { return }
+  let n = d / c;
+  let w = a.positional_weight(anchors[0], a.direction_to_body(n)) + b.positional_weight(anchors[1], b.direction_to_body(n));
+  if w + alpha == 0.0 { return }
+  let p = n * (-c / (w + alpha));
+  a.apply_positional(anchors[0], p);
+  b.apply_positional(anchors[1], -p);
+}
+
+/// Turn the bodies about the axis perpendicular to both axes until they line up.
+fn solve_axes(bodies:&mut [RigidBody], a:usize, b:usize, axes:[Vec3;2], alpha:f32) {
+  let (a, b) = pair(bodies, a, b);
+  let error = a.direction_to_world(axes[0]).cross(b.direction_to_world(axes[1]));
+  let c = error.length();
+  if c < 1e-9 { return }
+  let n = error / c;
+  let w = a.angular_weight(a.direction_to_body(n)) + b.angular_weight(b.direction_to_body(n));
+  if w + alpha == 0.0 { return }
+  let p = n * (c.asin() / (w + alpha));
+  a.apply_angular(p);
+  b.apply_angular(-p);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::file;
+  use crate::physics::World;
+
+  #[test] fn screws() {
+    let b = bivector(Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0));
+    assert_eq!([b.e23(), b.e31(), b.e12(), b.e01(), b.e02(), b.e03()], [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(bivector_parts(&b), (Vec3::new(1.0, 2.0, 3.0), Vec3::new(4.0, 5.0, 6.0)));
+    // A linear part of 2 along x is exp(-B/2) = 1 - e01, moving points 2 along x.
+    let m = screw(Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0));
+    assert!((m.scalar() - 1.0).abs() < 1e-6 && (m.e01() + 1.0).abs() < 1e-6, "{:?}", file::motor_data(&m));
+    assert!(transform_point(&m, Vec3::ZERO).abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-5));
+    // An angular part of a quarter turn about z is cos(pi/4) - sin(pi/4) e12, turning x into y.
+    let m = screw(Vec3::new(0.0, 0.0, std::f32::consts::FRAC_PI_2), Vec3::ZERO);
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    assert!((m.scalar() - half).abs() < 1e-6 && (m.e12() + half).abs() < 1e-6, "{:?}", file::motor_data(&m));
+    assert!(transform_direction(&m, Vec3::X).abs_diff_eq(Vec3::Y, 1e-5));
+  }
+
+  #[test] fn cuboid() {
+    let mesh = Mesh::cuboid([1.0, 0.5, 0.25]);
+    let properties = MassProperties::from_mesh(&mesh, 2.0);
+    let expected = MassProperties::cuboid(2.0, Vec3::new(1.0, 0.5, 0.25));
+    assert!((properties.mass - 2.0).abs() < 1e-5);
+    assert!(properties.center.length() < 1e-6);
+    let (mut got, mut want) = (properties.inertia.to_array(), expected.inertia.to_array());
+    got.sort_by(f32::total_cmp);
+    want.sort_by(f32::total_cmp);
+    for (g, w) in got.iter().zip(want) { assert!((g - w).abs() < 1e-4, "{:?} {:?}", got, want) }
+  }
+
+  #[test] fn offset_mesh() {
+    let mut mesh = Mesh::cuboid([0.5, 0.5, 0.5]);
+    for v in &mut mesh.vertices { v[0] += 3.0 }
+    let body = RigidBody::from_mesh(&mesh, 1.0);
+    assert!((body.center() - Vec3::new(3.0, 0.0, 0.0)).length() < 1e-5);
+    // The geometry stays where it was.
+    let m = crate::scene::motor_to_mat4(&body.transform());
+    assert!(m.abs_diff_eq(glam::Mat4::IDENTITY, 1e-5));
+  }
+
+  #[test] fn free_spin() {
+    // Spinning about the axis of largest inertia is stable, a small wobble stays small.
+    let mut world = World{gravity: Vec3::ZERO, ground: None, ..World::new()};
+    let body = world.add_body(RigidBody::new(&MassProperties::cuboid(1.0, Vec3::new(1.0, 0.5, 0.1))));
+    world.bodies[body].velocity = bivector(Vec3::new(0.01, 0.0, 5.0), Vec3::new(1.0, 0.0, 0.0));
+    let (momentum, energy) = (world.bodies[body].angular_momentum(), world.bodies[body].kinetic_energy());
+    for _ in 0..600 { world.step(1.0 / 60.0) }
+    let b = &world.bodies[body];
+    assert!((b.angular_momentum() - momentum).length() < 0.02 * momentum.length());
+    assert!(b.kinetic_energy() <= energy * 1.001 && b.kinetic_energy() > energy * 0.95);
+    // The center moved in a straight line at constant speed.
+    assert!((b.center() - Vec3::new(10.0, 0.0, 0.0)).length() < 0.05, "{:?}", b.center());
+  }
+
+  #[test] fn pendulum_period() {
+    // A rod hanging from a ball joint at its top end, a physical pendulum.
+    let mut world = World{ground: None, ..World::new()};
+    let pivot = world.add_body(RigidBody::fixed(identity()));
+    let properties = MassProperties::cuboid(1.0, Vec3::new(0.05, 0.5, 0.05));
+    let mut rod = RigidBody::new(&properties);
+    rod.pose = screw(Vec3::new(0.0, 0.0, 0.1), Vec3::ZERO) * screw(Vec3::ZERO, Vec3::new(0.0, -0.5, 0.0));
+    let rod = world.add_body(rod);
+    world.add_ball(pivot, rod, Vec3::ZERO, 0.0);
+    // Time between the first and third time the rod passes the bottom.
+    let (dt, mut t, mut crossings) = (1.0 / 60.0, 0.0, vec![]);
+    let mut x = world.bodies[rod].center().x;
+    while crossings.len() < 3 && t < 10.0 {
+      world.step(dt);
+      t += dt;
+      let next = world.bodies[rod].center().x;
+      if x.signum() != next.signum() { crossings.push(t - dt * next / (next - x)) }
+      x = next;
+    }
+    let inertia = properties.inertia.z + 0.5 * 0.5;
+    let expected = 2.0 * std::f32::consts::PI * (inertia / (9.81 * 0.5)).sqrt();
+    let period = crossings[2] - crossings[0];
+    assert!((period - expected).abs() < 0.01 * expected, "{} {}", period, expected);
+    assert!((world.bodies[rod].center().length() - 0.5).abs() < 1e-3);
+  }
+
+  #[test] fn hinge() {
+    let mut world = World{ground: None, ..World::new()};
+    let pivot = world.add_body(RigidBody::fixed(identity()));
+    let mut door = RigidBody::new(&MassProperties::cuboid(1.0, Vec3::new(0.5, 1.0, 0.05)));
+    door.pose = screw(Vec3::ZERO, Vec3::new(0.5, 0.0, 0.0));
+    let door = world.add_body(door);
+    world.add_hinge(pivot, door, Vec3::ZERO, Vec3::Z, 0.0);
+    for _ in 0..60 { world.step(1.0 / 60.0) }
+    let b = &world.bodies[door];
+    // Swings down in the plane of the hinge without twisting out of it.
+    assert!(b.direction_to_world(Vec3::Z).abs_diff_eq(Vec3::Z, 1e-3));
+    assert!(b.center().z.abs() < 1e-3 && b.center().y < -0.1);
+    assert!((b.center().length() - 0.5).abs() < 1e-3);
+  }
+
+  #[test] fn fixed() {
+    let mut world = World{ground: None, ..World::new()};
+    let wall = world.add_body(RigidBody::fixed(identity()));
+    let mut beam = RigidBody::new(&MassProperties::cuboid(1.0, Vec3::new(1.0, 0.1, 0.1)));
+    beam.pose = screw(Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0));
+    let beam = world.add_body(beam);
+    world.add_fixed(wall, beam, Vec3::ZERO, 0.0);
+    for _ in 0..60 { world.step(1.0 / 60.0) }
+    let b = &world.bodies[beam];
+    assert!((b.center() - Vec3::new(1.0, 0.0, 0.0)).length() < 0.01, "{:?}", b.center());
+    assert!(b.direction_to_world(Vec3::X).abs_diff_eq(Vec3::X, 0.01));
+  }
+}
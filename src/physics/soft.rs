@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use glam::Vec3;
+use crate::mesh::Mesh;
+use super::Particles;
+
+/// Tetrahedra over shared vertices, the volume of a soft body.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TetMesh {
+  pub vertices: Vec<Vec3>,
+  /// Vertex indices of each tetrahedron, ordered so its volume is positive.
+  pub tets: Vec<[u32;4]>,
+}
+
+impl TetMesh {
+  /// Box centered on the origin, each of its cells split into six tetrahedra along the same diagonal.
+  pub fn cuboid(half_extents:[f32;3], divisions:[u32;3])->TetMesh {
+    let h = Vec3::from(half_extents);
+    let n = divisions.map(|d| d.max(1));
+    let index = |x:u32, y:u32, z:u32| x + (n[0] + 1) * (y + (n[1] + 1) * z);
+    let mut vertices = Vec::new();
+    for z in 0..=n[2] { for y in 0..=n[1] { for x in 0..=n[0] {
+      let t = Vec3::new(x as f32 / n[0] as f32, y as f32 / n[1] as f32, z as f32 / n[2] as f32);
+      vertices.push(-h + t * h * 2.0);
+    }}}
+    let mut tets = Vec::new();
+    for z in 0..n[2] { for y in 0..n[1] { for x in 0..n[0] {
+      // Every path along the edges from one corner of the cell to the opposite one is a tetrahedron.
+      for order in [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+        let mut corner = [x, y, z];
+        let mut tet = [index(x, y, z); 4];
+        for (k, &axis) in order.iter().enumerate() {
+          corner[axis] += 1;
+          tet[k + 1] = index(corner[0], corner[1], corner[2]);
+        }
+        tets.push(tet);
+      }
+    }}}
+    let mut mesh = TetMesh{vertices, tets};
+    mesh.orient();
+    mesh
+  }
+
+  pub fn volume(&self, tet:[u32;4])->f32 {
+    let [a, b, c, d] = tet.map(|i| self.vertices[i as usize]);
+    (b - a).cross(c - a).dot(d - a) / 6.0
+  }
+
+  /// Swap two vertices of every tetrahedron with a negative volume.
+  pub fn orient(&mut self) {
+    for i in 0..self.tets.len() {
+      if self.volume(self.tets[i]) < 0.0 { self.tets[i].swap(2, 3) }
+    }
+  }
+
+  /// The faces that belong to a single tetrahedron, facing out.
+  pub fn surface(&self)->Vec<u32> {
+    const FACES:[[usize;3];4] = [[0, 2, 1], [0, 1, 3], [1, 2, 3], [0, 3, 2]];
+    let mut count:HashMap<[u32;3], usize> = HashMap::new();
+    let faces:Vec<[u32;3]> = self.tets.iter().flat_map(|t| FACES.map(|f| f.map(|i| t[i]))).collect();
+    let key = |f:&[u32;3]| { let mut k = *f; k.sort_unstable(); k };
+    for f in &faces { *count.entry(key(f)).or_insert(0) += 1 }
+    faces.iter().filter(|f| count[&key(f)] == 1).flatten().copied().collect()
+  }
+
+  /// The surface as a mesh with one vertex per tetrahedron vertex, so the particles of
+  /// [`super::World::add_tet_mesh`] can be copied into it with [`super::World::write_mesh`].
+  pub fn to_mesh(&self)->Mesh {
+    Mesh{vertices: self.vertices.iter().map(|&v| v.into()).collect(), indices: self.surface(), colors: vec![], uvs: vec![]}
+  }
+}
+
+/// Keeps particles of a cloth at least `thickness` apart so it doesn't pass through itself.
+/// Particles that started closer than that, like neighbours in a fine mesh, only keep their rest distance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfCollision {
+  pub first: usize,
+  pub thickness: f32,
+  rest: Vec<Vec3>,
+}
+
+impl SelfCollision {
+  pub fn new(particles:&Particles, first:usize, count:usize, thickness:f32)->Self {
+    SelfCollision{first, thickness, rest: particles.positions[first..first + count].to_vec()}
+  }
+
+  pub fn len(&self)->usize { self.rest.len() }
+
+  pub fn is_empty(&self)->bool { self.rest.is_empty() }
+
+  /// Push apart particles that came too close, found through a hash grid with cells as big as the thickness.
+  pub fn solve(&self, p:&mut Particles) {
+    let cell = |x:Vec3| (x / self.thickness).floor().as_ivec3().to_array();
+    let mut grid:HashMap<[i32;3], Vec<usize>> = HashMap::new();
+    for i in 0..self.len() { grid.entry(cell(p.positions[self.first + i])).or_default().push(i) }
+    for i in 0..self.len() {
+      let [x, y, z] = cell(p.positions[self.first + i]);
+      for dz in -1..=1 { for dy in -1..=1 { for dx in -1..=1 {
+        let neighbours = match grid.get(&[x + dx, y + dy, z + dz]) { Some(n) => n, None => continue };
+        for &j in neighbours {
+          if j <= i { continue }
+          let (a, b) = (self.first + i, self.first + j);
+          let (wa, wb) = (p.inv_masses[a], p.inv_masses[b]);
+          if wa + wb == 0.0 { continue }
+          let delta = p.positions[b] - p.positions[a];
+          let distance = delta.length();
+          let min = self.thickness.min(self.rest[i].distance(self.rest[j]));
+          if distance >= min || distance == 0.0 { continue }
+          let correction = delta * ((min - distance) / (distance * (wa + wb)));
+          p.positions[a] -= correction * wa;
+          p.positions[b] += correction * wb;
+        }
+      }}}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn cuboid() {
+    let mesh = TetMesh::cuboid([1.0, 0.5, 0.5], [2, 1, 1]);
+    assert_eq!((mesh.vertices.len(), mesh.tets.len()), (12, 12));
+    let volume:f32 = mesh.tets.iter().map(|&t| mesh.volume(t)).sum();
+    assert!((volume - 2.0).abs() < 1e-5);
+    assert!(mesh.tets.iter().all(|&t| mesh.volume(t) > 0.0));
+    // Ten unit squares of two triangles on the outside.
+    let surface = mesh.to_mesh();
+    assert_eq!(surface.indices.len(), 3 * 2 * 10);
+    for (n, v) in surface.normals().iter().zip(&surface.vertices) {
+      assert!(Vec3::from(*n).dot(Vec3::from(*v)) > 0.0);
+    }
+  }
+
+  #[test] fn self_collision() {
+    let mut p = Particles::default();
+    for x in [0.0, 0.05, 1.0] { p.push(Vec3::new(x, 0.0, 0.0), 1.0); }
+    let collision = SelfCollision::new(&p, 0, 3, 0.1);
+    // The first pair started closer than the thickness and only keeps its rest distance.
+    p.positions[1].x = 0.01;
+    collision.solve(&mut p);
+    assert!((p.positions[1].x - p.positions[0].x - 0.05).abs() < 1e-5);
+    // Particles that were far apart are kept a full thickness apart.
+    p.positions[2] = Vec3::new(0.03, 1.0, 0.0);
+    p.positions[1] = Vec3::new(0.03, 0.98, 0.0);
+    collision.solve(&mut p);
+    assert!((p.positions[2].y - p.positions[1].y - 0.1).abs() < 1e-5);
+  }
+}
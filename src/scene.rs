@@ -0,0 +1,196 @@
+use crate::Color;
+use crate::collision::{Ray, TriMesh};
+use crate::mesh::MeshRef;
+use crate::texture::TextureRef;
+
+/// Surface appearance of an object, the albedo map is multiplied with the color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+  pub color: Color,
+  pub albedo: Option<TextureRef>,
+}
+
+impl Default for Material {
+  fn default() -> Self { Material{color: Color::WHITE, albedo: None} }
+}
+
+impl From<Color> for Material {
+  fn from(color:Color) -> Self { Material{color, albedo: None} }
+}
+
+/// A mesh placed in the world by a motor, after scaling it along its own axes.
+#[derive(Clone, Copy, Debug)]
+pub struct Object {
+  pub mesh: MeshRef,
+  pub material: Material,
+  pub transform: g3::Motor,
+  pub scale: glam::Vec3,
+}
+
+impl Object {
+  pub fn new(mesh:MeshRef, material:Material)->Self {
+    Object{mesh, material, transform: identity(), scale: glam::Vec3::ONE}
+  }
+
+  /// Matrix from the mesh to the world.
+  pub fn matrix(&self)->glam::Mat4 { motor_to_mat4(&self.transform) * glam::Mat4::from_scale(self.scale) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectRef(pub(crate) u16);
+
+/// The motor that leaves everything in place.
+pub fn identity()->g3::Motor {
+  g3::Motor::new(1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+}
+
+/// The motor that moves everything by `v`.
+pub(crate) fn translation(v:glam::Vec3)->g3::Motor { g3::Motor::new(1.0, 0.0, 0.0, 0.0, -v.x / 2.0, -v.y / 2.0, -v.z / 2.0, 0.0) }
+
+/// Matrix that transforms points the same way as the motor, for use in shaders.
+pub fn motor_to_mat4(m:&g3::Motor)->glam::Mat4 {
+  let o:[f32;3] = m(g3::point(0.0, 0.0, 0.0)).into();
+  let column = |x, y, z| { let p:[f32;3] = m(g3::point(x, y, z)).into(); glam::Vec3::from(p) - glam::Vec3::from(o) };
+  glam::Mat4::from_cols(
+    column(1.0, 0.0, 0.0).extend(0.0),
+    column(0.0, 1.0, 0.0).extend(0.0),
+    column(0.0, 0.0, 1.0).extend(0.0),
+    glam::Vec3::from(o).extend(1.0))
+}
+
+/// Where a ray first meets an object.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+  pub object: ObjectRef,
+  /// Index of the triangle in the object's mesh.
+  pub triangle: usize,
+  /// Weights of the triangle's vertices at the hit.
+  pub barycentric: [f32;3],
+  pub point: g3::Point,
+  /// Distance from the start of the ray, in units of its direction.
+  pub distance: f32,
+}
+
+/// The nearest object hit by a world space ray. Each object's bounds are tested before its triangles,
+/// `meshes` holds the geometry of every [`MeshRef`].
+pub fn pick(objects:&[Object], meshes:&[TriMesh], ray:&Ray)->Option<Hit> {
+  let mut nearest:Option<Hit> = None;
+  for (i, object) in objects.iter().enumerate() {
+    let mesh = &meshes[object.mesh.0 as usize];
+    let local = ray.transform(&glam::Affine3A::from_mat4(object.matrix()).inverse());
+    match (mesh.bounds().ray(local.origin, local.direction), nearest) {
+      (None, _) => continue,
+      (Some(t), Some(hit)) if t > hit.distance => continue,
+      _ => {}
+    }
+    let hit = match mesh.ray(&local) { Some(hit) => hit, None => continue };
+    if matches!(nearest, Some(n) if n.distance <= hit.distance) { continue }
+    let p = ray.at(hit.distance);
+    nearest = Some(Hit{object: ObjectRef(i as u16), triangle: hit.triangle, barycentric: hit.barycentric, point: g3::point(p.x, p.y, p.z), distance: hit.distance});
+  }
+  nearest
+}
+
+/// Objects chosen by the user, which are outlined, and the object under the cursor, which is tinted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selection {
+  objects: Vec<ObjectRef>,
+  pub hovered: Option<ObjectRef>,
+  /// Width of the outline in physical pixels.
+  pub width: f32,
+  pub color: Color,
+  /// Mixed into the color of the hovered object by its alpha.
+  pub highlight: Color,
+}
+
+impl Default for Selection {
+  fn default() -> Self {
+    Selection{objects: Vec::new(), hovered: None, width: 3.0, color: Color(0xFF8C00FF), highlight: Color(0xFFFFFF50)}
+  }
+}
+
+impl Selection {
+  /// Add an object, returns false if it was already selected.
+  pub fn select(&mut self, object:ObjectRef)->bool {
+    if self.contains(object) { return false }
+    self.objects.push(object);
+    true
+  }
+
+  /// Remove an object, returns false if it wasn't selected.
+  pub fn deselect(&mut self, object:ObjectRef)->bool {
+    let len = self.objects.len();
+    self.objects.retain(|&o| o != object);
+    self.objects.len() != len
+  }
+
+  /// Select an object if it isn't and deselect it otherwise.
+  pub fn toggle(&mut self, object:ObjectRef) {
+    if !self.deselect(object) { self.objects.push(object) }
+  }
+
+  pub fn clear(&mut self) { self.objects.clear() }
+
+  pub fn contains(&self, object:ObjectRef)->bool { self.objects.contains(&object) }
+
+  /// Selected objects in the order they were selected.
+  pub fn iter(&self)->impl Iterator<Item = ObjectRef> + '_ { self.objects.iter().copied() }
+
+  pub fn len(&self)->usize { self.objects.len() }
+
+  pub fn is_empty(&self)->bool { self.objects.is_empty() }
+
+  /// The linear color an object is drawn with, tinted when it is hovered.
+  pub fn tint(&self, object:ObjectRef, color:[f32;4])->[f32;4] {
+    if self.hovered != Some(object) { return color }
+    let [r, g, b, a] = self.highlight.to_linear();
+    let mix = |c:f32, h:f32| c + (h - c) * a;
+    [mix(color[0], r), mix(color[1], g), mix(color[2], b), color[3]]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn matrix() {
+    assert!(motor_to_mat4(&identity()).abs_diff_eq(glam::Mat4::IDENTITY, 1e-6));
+    let m = (g3::point(1.0, 2.0, 3.0)/g3::point(0.0, 0.0, 0.0)).sqrt();
+    assert!(motor_to_mat4(&m).abs_diff_eq(glam::Mat4::from_translation(glam::vec3(1.0, 2.0, 3.0)), 1e-5));
+  }
+
+  #[test] fn picking() {
+    use glam::Vec3;
+    let meshes = vec![TriMesh::from_mesh(&crate::Mesh::cuboid([0.5; 3]))];
+    let mut objects = vec![Object::new(MeshRef(0), Color::RED.into()), Object::new(MeshRef(0), Color::BLUE.into())];
+    objects[1].transform = (g3::point(3.0, 0.0, 0.0)/g3::point(0.0, 0.0, 0.0)).sqrt();
+    let hit = pick(&objects, &meshes, &Ray::new(Vec3::new(3.2, 5.0, 0.1), -Vec3::Y)).unwrap();
+    assert_eq!(hit.object, ObjectRef(1));
+    let p:[f32;3] = hit.point.into();
+    assert!(Vec3::from(p).abs_diff_eq(Vec3::new(3.2, 0.5, 0.1), 1e-5) && (hit.distance - 4.5).abs() < 1e-5);
+    // The nearer of two objects in a row.
+    let hit = pick(&objects, &meshes, &Ray::new(Vec3::new(5.0, 0.0, 0.0), -Vec3::X)).unwrap();
+    assert_eq!(hit.object, ObjectRef(1));
+    assert!((hit.distance - 1.5).abs() < 1e-5);
+    assert!(pick(&objects, &meshes, &Ray::new(Vec3::new(1.5, 5.0, 0.0), -Vec3::Y)).is_none());
+  }
+
+  #[test] fn selection() {
+    let mut selection = Selection::default();
+    assert!(selection.select(ObjectRef(2)) && selection.select(ObjectRef(0)) && !selection.select(ObjectRef(2)));
+    assert_eq!(selection.iter().collect::<Vec<_>>(), vec![ObjectRef(2), ObjectRef(0)]);
+    selection.toggle(ObjectRef(2));
+    selection.toggle(ObjectRef(1));
+    assert!(!selection.contains(ObjectRef(2)) && selection.contains(ObjectRef(1)) && selection.len() == 2);
+    assert!(!selection.deselect(ObjectRef(3)));
+    selection.clear();
+    assert!(selection.is_empty());
+    // Only the hovered object is tinted, towards the highlight by its alpha.
+    selection.hovered = Some(ObjectRef(1));
+    selection.highlight = Color(0xFFFFFF80);
+    let color = [0.0, 0.5, 1.0, 0.25];
+    assert_eq!(selection.tint(ObjectRef(0), color), color);
+    let tinted = selection.tint(ObjectRef(1), color);
+    assert!((tinted[0] - 128.0 / 255.0).abs() < 1e-6 && (tinted[2] - 1.0).abs() < 1e-6 && tinted[3] == 0.25);
+  }
+}
@@ -0,0 +1,182 @@
+//! Fonts, the atlas their glyphs are rasterized into and the layout of text in pixels.
+
+use std::collections::HashMap;
+use crate::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontRef(pub(crate) u16);
+
+/// A TrueType or OpenType font, added with [`crate::Cx::add_font`].
+pub struct Font(fontdue::Font);
+
+impl Font {
+  pub fn from_bytes(bytes:&[u8])->anyhow::Result<Font> {
+    Ok(Font(fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(|e| anyhow::anyhow!("{}", e))?))
+  }
+
+  pub fn load<P:AsRef<std::path::Path>>(path:P)->anyhow::Result<Font> {
+    let path = path.as_ref();
+    Font::from_bytes(&std::fs::read(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?)
+  }
+}
+
+/// How the lines of a text are placed relative to its position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+  Left,
+  Center,
+  Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+  pub font: FontRef,
+  /// Height of the font in pixels.
+  pub size: f32,
+  pub color: Color,
+  pub align: Align,
+}
+
+impl Default for TextStyle {
+  fn default() -> Self { TextStyle{font: FontRef(0), size: 16.0, color: Color::WHITE, align: Align::Left} }
+}
+
+/// Where a text is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+  /// Pixels from the top left of the target to the top of the first line.
+  Screen([f32;2]),
+  /// A point in the world the last line stands on, facing the camera at the same size from any distance.
+  World(glam::Vec3),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+  pub text: String,
+  pub anchor: Anchor,
+  pub style: TextStyle,
+}
+
+/// A glyph in the atlas, with its bitmap's offset from the pen on the baseline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+  rect: [u32;4],
+  offset: [f32;2],
+  advance: f32,
+}
+
+/// A glyph placed relative to the anchor of its text, in pixels with y down, and where it is in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Placed {
+  pub offset: [f32;2],
+  pub size: [f32;2],
+  pub uv: [f32;4],
+}
+
+/// Rasterized glyphs packed into rows of a single channel texture, cleared when it is full.
+pub(crate) struct Atlas {
+  pub size: u32,
+  pub pixels: Vec<u8>,
+  /// Rows that changed since the last upload.
+  pub dirty: Option<(u32, u32)>,
+  /// Top, height and filled width of every row.
+  shelves: Vec<(u32, u32, u32)>,
+  glyphs: HashMap<(FontRef, char, u32), Entry>,
+}
+
+/// Space left around every glyph so filtering doesn't bleed into its neighbours.
+const PADDING:u32 = 1;
+
+impl Atlas {
+  pub fn new(size:u32)->Atlas { Atlas{size, pixels: vec![0; (size * size) as usize], dirty: Some((0, size)), shelves: vec![], glyphs: HashMap::new()} }
+
+  pub fn clear(&mut self) {
+    self.pixels.iter_mut().for_each(|p| *p = 0);
+    self.shelves.clear();
+    self.glyphs.clear();
+    self.dirty = Some((0, self.size));
+  }
+
+  /// Find room for a rectangle on the first row it fits, or start a new row.
+  fn allocate(&mut self, width:u32, height:u32)->Option<[u32;2]> {
+    let (w, h) = (width + PADDING, height + PADDING);
+    if w > self.size { return None }
+    let size = self.size;
+    if let Some(shelf) = self.shelves.iter_mut().find(|s| s.1 >= h && s.1 <= h + h / 2 + 2 && s.2 + w <= size) {
+      shelf.2 += w;
+      return Some([shelf.2 - w, shelf.0])
+    }
+    let top = self.shelves.last().map(|s| s.0 + s.1).unwrap_or(0);
+    if top + h > self.size { return None }
+    self.shelves.push((top, h, w));
+    Some([0, top])
+  }
+
+  /// The glyph of a character at a size, rasterized into the atlas the first time. `None` when the atlas is full.
+  fn glyph(&mut self, font_ref:FontRef, font:&Font, c:char, px:u32)->Option<Entry> {
+    if let Some(entry) = self.glyphs.get(&(font_ref, c, px)) { return Some(*entry) }
+    let (m, bitmap) = font.0.rasterize(c, px as f32);
+    let (width, height) = (m.width as u32, m.height as u32);
+    let [x, y] = self.allocate(width, height)?;
+    for row in 0..height {
+      let start = ((y + row) * self.size + x) as usize;
+      self.pixels[start..start + width as usize].copy_from_slice(&bitmap[(row * width) as usize..((row + 1) * width) as usize]);
+    }
+    self.dirty = Some(match self.dirty { Some((a, b)) => (a.min(y), b.max(y + height)), None => (y, y + height) });
+    let entry = Entry{rect: [x, y, width, height], offset: [m.xmin as f32, -(m.ymin as f32 + height as f32)], advance: m.advance_width};
+    self.glyphs.insert((font_ref, c, px), entry);
+    Some(entry)
+  }
+
+  /// Place the glyphs of a text, lines one below the other and aligned at the anchor with the top of the first at it.
+  /// Returns the glyphs and the height of the text, or `None` when the atlas ran out of space.
+  pub fn layout(&mut self, font_ref:FontRef, font:&Font, text:&str, style:&TextStyle)->Option<(Vec<Placed>, f32)> {
+    let px = style.size.round().max(1.0) as u32;
+    let (ascent, line_height) = match font.0.horizontal_line_metrics(px as f32) {
+      Some(m) => (m.ascent, m.new_line_size),
+      None => (px as f32 * 0.8, px as f32 * 1.2),
+    };
+    let mut placed = vec![];
+    let mut lines = 0;
+    for (i, line) in text.lines().enumerate() {
+      let mut glyphs = vec![];
+      let (mut pen, mut prev) = (0.0, None);
+      for c in line.chars() {
+        if let Some(p) = prev { pen += font.0.horizontal_kern(p, c, px as f32).unwrap_or(0.0) }
+        let entry = self.glyph(font_ref, font, c, px)?;
+        glyphs.push((pen.round(), entry));
+        pen += entry.advance;
+        prev = Some(c);
+      }
+      let start = match style.align { Align::Left => 0.0, Align::Center => -(pen / 2.0).round(), Align::Right => -pen.round() };
+      let baseline = (ascent + i as f32 * line_height).round();
+      let s = self.size as f32;
+      placed.extend(glyphs.into_iter().filter(|(_, e)| e.rect[2] > 0 && e.rect[3] > 0).map(|(x, e)| {
+        let [u, v, w, h] = e.rect.map(|c| c as f32);
+        Placed{offset: [start + x + e.offset[0], baseline + e.offset[1]], size: [w, h], uv: [u / s, v / s, (u + w) / s, (v + h) / s]}
+      }));
+      lines = i + 1;
+    }
+    Some((placed, lines as f32 * line_height))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn atlas() {
+    let mut atlas = Atlas::new(32);
+    assert_eq!(atlas.allocate(10, 8), Some([0, 0]));
+    assert_eq!(atlas.allocate(10, 7), Some([11, 0]));
+    // Rows are reused for glyphs up to half again as tall, taller ones start a new row.
+    assert_eq!(atlas.allocate(10, 20), Some([0, 9]));
+    assert_eq!(atlas.allocate(9, 8), Some([22, 0]));
+    // Full rows and no room below for another.
+    assert_eq!(atlas.allocate(10, 8), None);
+    assert_eq!(atlas.allocate(40, 1), None);
+    atlas.clear();
+    assert_eq!(atlas.allocate(31, 31), Some([0, 0]));
+    assert_eq!(atlas.dirty, Some((0, 32)));
+  }
+}
@@ -0,0 +1,177 @@
+use std::path::Path;
+use super::{decode, Image};
+
+/// Block compressed image with the mip levels stored in the file, as read from KTX2 or DDS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompressedImage {
+  pub width: u32,
+  pub height: u32,
+  pub format: wgpu::TextureFormat,
+  /// Tightly packed rows of blocks for each level, starting with the largest.
+  pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+  /// Load a KTX2 or DDS file.
+  pub fn load<P:AsRef<Path>>(path:P)->anyhow::Result<CompressedImage> {
+    Self::decode(&std::fs::read(path)?)
+  }
+
+  /// Read a KTX2 or DDS file that is already in memory, told apart by their magic bytes.
+  pub fn decode(bytes:&[u8])->anyhow::Result<CompressedImage> {
+    if bytes.starts_with(b"\xabKTX 20\xbb") { Self::from_ktx2(bytes) }
+    else if bytes.starts_with(b"DDS ") { Self::from_dds(bytes) }
+    else { anyhow::bail!("not a KTX2 or DDS file") }
+  }
+
+  fn from_ktx2(bytes:&[u8])->anyhow::Result<CompressedImage> {
+    let reader = ktx2::Reader::new(bytes)?;
+    let header = reader.header();
+    anyhow::ensure!(header.supercompression_scheme.is_none(), "supercompressed KTX2 files are not supported");
+    anyhow::ensure!(header.face_count == 1 && header.layer_count <= 1, "only single 2D KTX2 textures are supported");
+    let format = header.format.and_then(ktx2_format)
+      .ok_or_else(|| anyhow::anyhow!("unsupported KTX2 format {:?}", header.format))?;
+    let levels = reader.levels().map(|level| level.to_vec()).collect();
+    Self::new(header.pixel_width, header.pixel_height, format, levels)
+  }
+
+  fn from_dds(bytes:&[u8])->anyhow::Result<CompressedImage> {
+    let dds = ddsfile::Dds::read(bytes)?;
+    // Legacy DXT1/3/5 files without a DX10 header come back as the sRGB BC formats.
+    let format = dds.get_dxgi_format().and_then(dxgi_format)
+      .ok_or_else(|| anyhow::anyhow!("unsupported DDS format"))?;
+    let (width, height) = (dds.get_width(), dds.get_height());
+    // All levels of the first layer follow each other.
+    let mut data = dds.get_data(0)?;
+    let mut levels = Vec::new();
+    for level in 0..dds.get_num_mipmap_levels().max(1) {
+      let size = level_size(format, width, height, level);
+      anyhow::ensure!(data.len() >= size, "DDS file is truncated");
+      levels.push(data[..size].to_vec());
+      data = &data[size..];
+    }
+    Self::new(width, height, format, levels)
+  }
+
+  fn new(width:u32, height:u32, format:wgpu::TextureFormat, levels:Vec<Vec<u8>>)->anyhow::Result<CompressedImage> {
+    anyhow::ensure!(!levels.is_empty(), "texture has no levels");
+    for (level, data) in levels.iter().enumerate() {
+      anyhow::ensure!(data.len() == level_size(format, width, height, level as u32), "level {} has the wrong size", level);
+    }
+    Ok(CompressedImage{width, height, format, levels})
+  }
+
+  /// Device features needed to sample the image without decompressing it.
+  pub fn required_features(&self)->wgpu::Features { self.format.describe().required_features }
+
+  /// Decode the first level on the CPU, for adapters that can't sample the format. Only BC1-5 have a software decoder.
+  pub fn decompress(&self)->anyhow::Result<Image> {
+    let pixels = decode::decompress(self.format, self.width, self.height, &self.levels[0])
+      .ok_or_else(|| anyhow::anyhow!("the device can't sample {:?} and there is no software decoder for it", self.format))?;
+    Ok(Image{width: self.width, height: self.height, pixels})
+  }
+
+  /// Whether the colors are sRGB encoded.
+  pub fn is_srgb(&self)->bool { self.format.describe().srgb }
+}
+
+/// Bytes in a level of a block compressed texture, partial blocks at the edges are stored whole.
+pub fn level_size(format:wgpu::TextureFormat, width:u32, height:u32, level:u32)->usize {
+  let info = format.describe();
+  let (bw, bh) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+  let (width, height) = ((width >> level).max(1), (height >> level).max(1));
+  (width.div_ceil(bw) * height.div_ceil(bh)) as usize * info.block_size as usize
+}
+
+fn ktx2_format(format:ktx2::Format)->Option<wgpu::TextureFormat> {
+  use wgpu::TextureFormat::*;
+  use ktx2::Format as F;
+  Some(match format {
+    F::BC1_RGBA_UNORM_BLOCK | F::BC1_RGB_UNORM_BLOCK => Bc1RgbaUnorm,
+    F::BC1_RGBA_SRGB_BLOCK | F::BC1_RGB_SRGB_BLOCK => Bc1RgbaUnormSrgb,
+    F::BC2_UNORM_BLOCK => Bc2RgbaUnorm,
+    F::BC2_SRGB_BLOCK => Bc2RgbaUnormSrgb,
+    F::BC3_UNORM_BLOCK => Bc3RgbaUnorm,
+    F::BC3_SRGB_BLOCK => Bc3RgbaUnormSrgb,
+    F::BC4_UNORM_BLOCK => Bc4RUnorm,
+    F::BC4_SNORM_BLOCK => Bc4RSnorm,
+    F::BC5_UNORM_BLOCK => Bc5RgUnorm,
+    F::BC5_SNORM_BLOCK => Bc5RgSnorm,
+    F::BC6H_UFLOAT_BLOCK => Bc6hRgbUfloat,
+    F::BC6H_SFLOAT_BLOCK => Bc6hRgbSfloat,
+    F::BC7_UNORM_BLOCK => Bc7RgbaUnorm,
+    F::BC7_SRGB_BLOCK => Bc7RgbaUnormSrgb,
+    F::ETC2_R8G8B8_UNORM_BLOCK => Etc2Rgb8Unorm,
+    F::ETC2_R8G8B8_SRGB_BLOCK => Etc2Rgb8UnormSrgb,
+    F::ETC2_R8G8B8A1_UNORM_BLOCK => Etc2Rgb8A1Unorm,
+    F::ETC2_R8G8B8A1_SRGB_BLOCK => Etc2Rgb8A1UnormSrgb,
+    F::ETC2_R8G8B8A8_UNORM_BLOCK => Etc2Rgba8Unorm,
+    F::ETC2_R8G8B8A8_SRGB_BLOCK => Etc2Rgba8UnormSrgb,
+    F::EAC_R11_UNORM_BLOCK => EacR11Unorm,
+    F::EAC_R11_SNORM_BLOCK => EacR11Snorm,
+    F::EAC_R11G11_UNORM_BLOCK => EacRg11Unorm,
+    F::EAC_R11G11_SNORM_BLOCK => EacRg11Snorm,
+    _ => return astc_format(format.0.get()),
+  })
+}
+
+/// The ASTC LDR formats come in unorm/sRGB pairs in the same block order as [`wgpu::AstcBlock`].
+fn astc_format(vk_format:u32)->Option<wgpu::TextureFormat> {
+  use wgpu::AstcBlock::*;
+  const BLOCKS:[wgpu::AstcBlock;14] = [B4x4, B5x4, B5x5, B6x5, B6x6, B8x5, B8x6, B8x8, B10x5, B10x6, B10x8, B10x10, B12x10, B12x12];
+  let index = vk_format.checked_sub(ktx2::Format::ASTC_4x4_UNORM_BLOCK.0.get())? as usize;
+  let block = *BLOCKS.get(index / 2)?;
+  let channel = if index % 2 == 1 { wgpu::AstcChannel::UnormSrgb } else { wgpu::AstcChannel::Unorm };
+  Some(wgpu::TextureFormat::Astc{block, channel})
+}
+
+fn dxgi_format(format:ddsfile::DxgiFormat)->Option<wgpu::TextureFormat> {
+  use wgpu::TextureFormat::*;
+  use ddsfile::DxgiFormat as F;
+  Some(match format {
+    F::BC1_UNorm => Bc1RgbaUnorm,
+    F::BC1_UNorm_sRGB => Bc1RgbaUnormSrgb,
+    F::BC2_UNorm => Bc2RgbaUnorm,
+    F::BC2_UNorm_sRGB => Bc2RgbaUnormSrgb,
+    F::BC3_UNorm => Bc3RgbaUnorm,
+    F::BC3_UNorm_sRGB => Bc3RgbaUnormSrgb,
+    F::BC4_UNorm => Bc4RUnorm,
+    F::BC4_SNorm => Bc4RSnorm,
+    F::BC5_UNorm => Bc5RgUnorm,
+    F::BC5_SNorm => Bc5RgSnorm,
+    F::BC6H_UF16 => Bc6hRgbUfloat,
+    F::BC6H_SF16 => Bc6hRgbSfloat,
+    F::BC7_UNorm => Bc7RgbaUnorm,
+    F::BC7_UNorm_sRGB => Bc7RgbaUnormSrgb,
+    _ => return None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::texture::Pixels;
+
+  #[test] fn formats() {
+    assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 16, 16, 0), 128);
+    assert_eq!(level_size(wgpu::TextureFormat::Bc1RgbaUnorm, 16, 16, 3), 8);
+    assert_eq!(level_size(wgpu::TextureFormat::Bc7RgbaUnorm, 6, 6, 0), 64);
+    assert_eq!(ktx2_format(ktx2::Format::BC7_SRGB_BLOCK), Some(wgpu::TextureFormat::Bc7RgbaUnormSrgb));
+    assert_eq!(ktx2_format(ktx2::Format::ASTC_8x8_SRGB_BLOCK),
+      Some(wgpu::TextureFormat::Astc{block: wgpu::AstcBlock::B8x8, channel: wgpu::AstcChannel::UnormSrgb}));
+    assert_eq!(ktx2_format(ktx2::Format::R8G8B8A8_UNORM), None);
+  }
+
+  #[test] fn dds() {
+    // Header of a 4x4 DXT1 file with a single red block.
+    let mut bytes = b"DDS ".to_vec();
+    let header:[u32;31] = [124, 0x1 | 0x2 | 0x4 | 0x1000, 4, 4, 8, 0, 1, 0,0,0,0,0,0,0,0,0,0,0,
+      32, 0x4, u32::from_le_bytes(*b"DXT1"), 0, 0, 0, 0, 0, 0x1000, 0, 0, 0, 0];
+    bytes.extend(header.iter().flat_map(|v| v.to_le_bytes()));
+    bytes.extend([0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0]);
+    let image = CompressedImage::decode(&bytes).unwrap();
+    assert_eq!((image.width, image.height, image.format), (4, 4, wgpu::TextureFormat::Bc1RgbaUnormSrgb));
+    assert_eq!(image.levels.len(), 1);
+    assert_eq!(image.decompress().unwrap().pixels, Pixels::Rgba8([255, 0, 0, 255].repeat(16)));
+  }
+}
@@ -0,0 +1,203 @@
+//! Software decoders for the block compressed formats, used when the adapter can't sample them.
+//! BC1-5 are handled, BC6H, BC7, ETC2, EAC and ASTC have to be supported by the hardware.
+
+use super::Pixels;
+
+/// Decode the blocks of one level to tightly packed RGBA pixels, signed formats to floats so they keep their sign.
+pub fn decompress(format:wgpu::TextureFormat, width:u32, height:u32, data:&[u8])->Option<Pixels> {
+  use wgpu::TextureFormat::*;
+  let unorm = |decode:fn(&[u8])->[[u8;4];16]| Pixels::Rgba8(blocks(width, height, data, format.describe().block_size as usize, decode));
+  let float = |decode:fn(&[u8])->[[f32;4];16]| Pixels::Rgba32Float(blocks(width, height, data, format.describe().block_size as usize, decode));
+  Some(match format {
+    Bc1RgbaUnorm | Bc1RgbaUnormSrgb => unorm(|b| bc1(b, true)),
+    Bc2RgbaUnorm | Bc2RgbaUnormSrgb => unorm(bc2),
+    Bc3RgbaUnorm | Bc3RgbaUnormSrgb => unorm(bc3),
+    Bc4RUnorm => unorm(bc4),
+    Bc4RSnorm => float(|b| bc4_signed(b).map(|r| [r, 0.0, 0.0, 1.0])),
+    Bc5RgUnorm => unorm(bc5),
+    Bc5RgSnorm => float(bc5_signed),
+    _ => return None,
+  })
+}
+
+/// Decode every 4x4 block and copy the texels that are inside the image to their place in it.
+fn blocks<T:Copy+Default>(width:u32, height:u32, data:&[u8], block_size:usize, decode:impl Fn(&[u8])->[[T;4];16])->Vec<T> {
+  let blocks_wide = (width as usize).div_ceil(4);
+  let (width, height) = (width as usize, height as usize);
+  let mut pixels = vec![T::default(); width * height * 4];
+  for (i, block) in data.chunks_exact(block_size).enumerate() {
+    let (bx, by) = (i % blocks_wide * 4, i / blocks_wide * 4);
+    for (j, texel) in decode(block).iter().enumerate() {
+      let (x, y) = (bx + j % 4, by + j / 4);
+      if x < width && y < height {
+        pixels[(y * width + x) * 4..][..4].copy_from_slice(texel);
+      }
+    }
+  }
+  pixels
+}
+
+fn rgb565(c:u16)->[i32;3] {
+  let (r, g, b) = ((c >> 11) as i32, (c >> 5 & 0x3f) as i32, (c & 0x1f) as i32);
+  [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// Color endpoints with 2 bit indices, `punch_through` selects the 3 color mode with transparent black.
+fn bc1(block:&[u8], punch_through:bool)->[[u8;4];16] {
+  let (c0, c1) = (u16::from_le_bytes([block[0], block[1]]), u16::from_le_bytes([block[2], block[3]]));
+  let (e0, e1) = (rgb565(c0), rgb565(c1));
+  let mix = |a:i32, b:i32, d:i32| (0..3).map(|i| ((a * e0[i] + b * e1[i] + d / 2) / d) as u8).collect::<Vec<_>>();
+  let palette:[[u8;4];4] = if c0 > c1 || !punch_through {
+    let (p2, p3) = (mix(2, 1, 3), mix(1, 2, 3));
+    [rgba(mix(1, 0, 1), 255), rgba(mix(0, 1, 1), 255), rgba(p2, 255), rgba(p3, 255)]
+  } else {
+    [rgba(mix(1, 0, 1), 255), rgba(mix(0, 1, 1), 255), rgba(mix(1, 1, 2), 255), [0; 4]]
+  };
+  let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+  let mut out = [[0; 4]; 16];
+  for (i, texel) in out.iter_mut().enumerate() { *texel = palette[(indices >> (2 * i) & 3) as usize]; }
+  out
+}
+
+fn rgba(rgb:Vec<u8>, a:u8)->[u8;4] { [rgb[0], rgb[1], rgb[2], a] }
+
+/// Explicit 4 bit alpha followed by a BC1 color block.
+fn bc2(block:&[u8])->[[u8;4];16] {
+  let mut out = bc1(&block[8..], false);
+  for (i, texel) in out.iter_mut().enumerate() { texel[3] = (block[i / 2] >> (4 * (i % 2)) & 0xf) * 17; }
+  out
+}
+
+/// Interpolated alpha followed by a BC1 color block.
+fn bc3(block:&[u8])->[[u8;4];16] {
+  let mut out = bc1(&block[8..], false);
+  for (texel, a) in out.iter_mut().zip(bc4_channel(block)) { texel[3] = a; }
+  out
+}
+
+fn bc4(block:&[u8])->[[u8;4];16] { bc4_channel(block).map(|r| [r, 0, 0, 255]) }
+
+fn bc5(block:&[u8])->[[u8;4];16] {
+  let (r, g) = (bc4_channel(block), bc4_channel(&block[8..]));
+  let mut out = [[0; 4]; 16];
+  for i in 0..16 { out[i] = [r[i], g[i], 0, 255]; }
+  out
+}
+
+/// Two endpoints with 3 bit indices into 8 values, or 6 values plus 0 and 255.
+fn bc4_channel(block:&[u8])->[u8;16] {
+  let (a0, a1) = (block[0] as u32, block[1] as u32);
+  let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+  if a0 > a1 {
+    for i in 1..7 { palette[i + 1] = (a0 * (7 - i as u32) + a1 * i as u32) / 7; }
+  } else {
+    for i in 1..5 { palette[i + 1] = (a0 * (5 - i as u32) + a1 * i as u32) / 5; }
+  }
+  let bits = block[2..8].iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+  let mut out = [0; 16];
+  for (i, a) in out.iter_mut().enumerate() { *a = palette[(bits >> (3 * i) & 7) as usize] as u8; }
+  out
+}
+
+/// BC4 with signed endpoints, -128 reads as -127, to values in -1..1.
+fn bc4_signed(block:&[u8])->[f32;16] {
+  let endpoint = |b:u8| (b as i8).max(-127) as f32 / 127.0;
+  let (a0, a1) = (endpoint(block[0]), endpoint(block[1]));
+  let mut palette = [a0, a1, 0.0, 0.0, 0.0, 0.0, -1.0, 1.0];
+  if a0 > a1 {
+    for i in 1..7 { palette[i + 1] = (a0 * (7 - i) as f32 + a1 * i as f32) / 7.0; }
+  } else {
+    for i in 1..5 { palette[i + 1] = (a0 * (5 - i) as f32 + a1 * i as f32) / 5.0; }
+  }
+  let bits = block[2..8].iter().rev().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+  let mut out = [0.0; 16];
+  for (i, a) in out.iter_mut().enumerate() { *a = palette[((bits >> (3 * i)) & 7) as usize]; }
+  out
+}
+
+fn bc5_signed(block:&[u8])->[[f32;4];16] {
+  let (r, g) = (bc4_signed(block), bc4_signed(&block[8..]));
+  let mut out = [[0.0; 4]; 16];
+  for i in 0..16 { out[i] = [r[i], g[i], 0.0, 1.0]; }
+  out
+}
+
+#[cfg(test)]
+// The DXT codec of image is deprecated but still the reference at hand.
+#[allow(deprecated)]
+mod tests {
+  use super::*;
+  use image::ImageDecoder;
+  use image::codecs::dxt::{DxtDecoder, DxtEncoder, DxtVariant};
+
+  /// Blocks of an 8x8 image from the reference encoder and what the reference decoder makes of them.
+  fn reference(variant:DxtVariant, pixels:&[u8])->(Vec<u8>, Vec<u8>) {
+    let mut blocks = vec![];
+    DxtEncoder::new(&mut blocks).encode(pixels, 8, 8, variant).unwrap();
+    let decoder = DxtDecoder::new(blocks.as_slice(), 8, 8, variant).unwrap();
+    let mut decoded = vec![0; decoder.total_bytes() as usize];
+    decoder.read_image(&mut decoded).unwrap();
+    (blocks, decoded)
+  }
+
+  /// Gradients with some noise, so the blocks don't all have the same indices.
+  fn test_image(seed:u32)->Vec<u8> {
+    (0..8 * 8 * 4u32).map(|i| {
+      let (x, y, c) = (i / 4 % 8, i / 32, i % 4);
+      let noise = (i.wrapping_mul(2654435761) ^ seed).rotate_left(7) % 40;
+      ((x * 29 + y * 7 * (c + 1) + noise) % 256) as u8
+    }).collect()
+  }
+
+  fn rgba8(pixels:Option<Pixels>)->Vec<u8> {
+    match pixels { Some(Pixels::Rgba8(pixels)) => pixels, p => panic!("expected 8 bit pixels, got {:?}", p) }
+  }
+
+  /// Channels of two images are at most 1 apart, the reference expands 5 and 6 bit endpoints by scaling instead of
+  /// repeating their bits.
+  fn assert_close(a:impl Iterator<Item = u8>, b:impl Iterator<Item = u8>) {
+    for (i, (a, b)) in a.zip(b).enumerate() { assert!((a as i32 - b as i32).abs() <= 1, "channel {}: {} and {}", i, a, b) }
+  }
+
+  #[test] fn bc() {
+    // Red and blue endpoints, the first row picks each palette entry in turn.
+    let block = [0x00, 0xf8, 0x1f, 0x00, 0b11100100, 0, 0, 0];
+    let texels = bc1(&block, true);
+    assert_eq!(&texels[..4], &[[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]);
+    assert_eq!(texels[4], [255, 0, 0, 255]);
+    // Swapping the endpoints selects the mode with a transparent entry.
+    let swapped = [0x1f, 0x00, 0x00, 0xf8, 0b11100100, 0, 0, 0];
+    assert_eq!(&bc1(&swapped, true)[..4], &[[0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0]]);
+    let alpha = bc4_channel(&[255, 0, 0b001, 0, 0, 0, 0, 0]);
+    assert_eq!((alpha[0], alpha[1]), (0, 255));
+    let texels = decompress(wgpu::TextureFormat::Bc3RgbaUnorm, 2, 2, &[[255, 255, 0, 0, 0, 0, 0, 0], block].concat());
+    assert_eq!(texels, Some(Pixels::Rgba8([[255, 0, 0, 255], [0, 0, 255, 255], [255, 0, 0, 255], [255, 0, 0, 255]].concat())));
+    // Signed endpoints of 127 and -128 at the extremes.
+    let signed = bc4_signed(&[127, 0x80, 0b001, 0, 0, 0, 0, 0]);
+    assert_eq!((signed[0], signed[1], signed[2]), (-1.0, 1.0, 1.0));
+    assert_eq!(decompress(wgpu::TextureFormat::Bc7RgbaUnorm, 4, 4, &[0; 16]), None);
+  }
+
+  #[test] fn reference_vectors() {
+    use wgpu::TextureFormat::*;
+    let (image, other) = (test_image(1), test_image(2));
+    let rgb:Vec<u8> = image.chunks(4).flat_map(|p| p[..3].to_vec()).collect();
+    // The reference decodes BC1 without alpha, its transparent entry is black.
+    let (blocks, expected) = reference(DxtVariant::DXT1, &rgb);
+    let pixels = rgba8(decompress(Bc1RgbaUnorm, 8, 8, &blocks));
+    assert_close(pixels.chunks(4).flat_map(|p| p[..3].to_vec()), expected.into_iter());
+    let (blocks, expected) = reference(DxtVariant::DXT3, &image);
+    assert_close(rgba8(decompress(Bc2RgbaUnorm, 8, 8, &blocks)).into_iter(), expected.into_iter());
+    let (blocks, expected) = reference(DxtVariant::DXT5, &image);
+    assert_close(rgba8(decompress(Bc3RgbaUnorm, 8, 8, &blocks)).into_iter(), expected.iter().copied());
+    // BC4 and BC5 channels are BC3 alpha blocks.
+    let alpha_blocks = |blocks:&[u8]| blocks.chunks(16).map(|b| b[..8].to_vec()).collect::<Vec<_>>();
+    let red = alpha_blocks(&blocks);
+    let pixels = rgba8(decompress(Bc4RUnorm, 8, 8, &red.concat()));
+    assert_eq!(pixels.chunks(4).map(|p| p[0]).collect::<Vec<_>>(), expected.chunks(4).map(|p| p[3]).collect::<Vec<_>>());
+    let (blocks, expected_green) = reference(DxtVariant::DXT5, &other);
+    let rg:Vec<u8> = red.iter().zip(alpha_blocks(&blocks)).flat_map(|(r, g)| [r.clone(), g].concat()).collect();
+    let pixels = rgba8(decompress(Bc5RgUnorm, 8, 8, &rg));
+    assert!(pixels.chunks(4).zip(expected.chunks(4).zip(expected_green.chunks(4))).all(|(p, (r, g))| p == [r[3], g[3], 0, 255]));
+  }
+}
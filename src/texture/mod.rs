@@ -0,0 +1,244 @@
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::Path;
+
+mod compressed;
+mod decode;
+
+pub use compressed::CompressedImage;
+
+/// Decoded pixels, 8 bit images are sRGB encoded and floating point images are linear.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pixels {
+  Rgba8(Vec<u8>),
+  Rgba32Float(Vec<f32>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Pixels,
+}
+
+impl Image {
+  /// Load a PNG, JPEG or Radiance HDR file.
+  pub fn load<P:AsRef<Path>>(path:P)->anyhow::Result<Image> {
+    Self::decode(&std::fs::read(path)?)
+  }
+
+  /// Decode an image file that is already in memory, the format is guessed from its contents.
+  pub fn decode(bytes:&[u8])->anyhow::Result<Image> {
+    let image = image::load_from_memory(bytes)?;
+    let (width, height) = (image.width(), image.height());
+    let pixels = match image {
+      image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => Pixels::Rgba32Float(image.to_rgba32f().into_raw()),
+      _ => Pixels::Rgba8(image.to_rgba8().into_raw()),
+    };
+    Ok(Image{width, height, pixels})
+  }
+
+  pub fn from_rgba8(width:u32, height:u32, data:Vec<u8>)->Image {
+    assert_eq!(data.len(), (4 * width * height) as usize, "expected 4 bytes per pixel");
+    Image{width, height, pixels: Pixels::Rgba8(data)}
+  }
+
+  pub fn is_hdr(&self)->bool { matches!(self.pixels, Pixels::Rgba32Float(_)) }
+
+  /// Format of the texture the image is uploaded to, 8 bit color images are sampled as sRGB.
+  pub fn format(&self, srgb:bool)->wgpu::TextureFormat {
+    match self.pixels {
+      Pixels::Rgba8(_) if srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+      Pixels::Rgba8(_) => wgpu::TextureFormat::Rgba8Unorm,
+      // Half floats are filterable everywhere, full floats need a feature.
+      Pixels::Rgba32Float(_) => wgpu::TextureFormat::Rgba16Float,
+    }
+  }
+
+  /// Bytes as laid out in a texture of [`Image::format`] with the number of bytes per row.
+  pub fn texels(&self)->(Vec<u8>, u32) {
+    match self.pixels {
+      Pixels::Rgba8(ref data) => (data.clone(), 4 * self.width),
+      Pixels::Rgba32Float(ref data) => {
+        let halfs:Vec<u16> = data.iter().map(|&f| f32_to_f16(f)).collect();
+        (bytemuck::cast_slice(&halfs).to_vec(), 8 * self.width)
+      }
+    }
+  }
+}
+
+/// How a texture is filtered and repeated when sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerConfig {
+  pub address_mode: wgpu::AddressMode,
+  pub mag_filter: wgpu::FilterMode,
+  pub min_filter: wgpu::FilterMode,
+  pub mipmap_filter: wgpu::FilterMode,
+  /// Maximum anisotropy, 1 turns anisotropic filtering off. Ignored by devices that don't support it.
+  pub anisotropy: u8,
+}
+
+impl Default for SamplerConfig {
+  fn default() -> Self {
+    SamplerConfig {
+      address_mode: wgpu::AddressMode::Repeat,
+      mag_filter: wgpu::FilterMode::Linear,
+      min_filter: wgpu::FilterMode::Linear,
+      mipmap_filter: wgpu::FilterMode::Linear,
+      anisotropy: 1,
+    }
+  }
+}
+
+impl SamplerConfig {
+  /// Blocky pixels, for pixel art and lookup tables.
+  pub const NEAREST: Self = SamplerConfig {
+    address_mode: wgpu::AddressMode::ClampToEdge,
+    mag_filter: wgpu::FilterMode::Nearest,
+    min_filter: wgpu::FilterMode::Nearest,
+    mipmap_filter: wgpu::FilterMode::Nearest,
+    anisotropy: 1,
+  };
+
+  /// Trilinear filtering with up to `level` samples along the direction of the slope, rounded down to 1, 2, 4, 8 or 16.
+  pub fn anisotropic(level:u8)->Self {
+    let level = level.clamp(1, 16);
+    SamplerConfig{anisotropy: 1 << (7 - level.leading_zeros()), ..Default::default()}
+  }
+
+  pub fn create_sampler(&self, device:&wgpu::Device)->wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+      label: Some("texture"),
+      address_mode_u: self.address_mode,
+      address_mode_v: self.address_mode,
+      address_mode_w: self.address_mode,
+      mag_filter: self.mag_filter,
+      min_filter: self.min_filter,
+      mipmap_filter: self.mipmap_filter,
+      anisotropy_clamp: NonZeroU8::new(self.anisotropy).filter(|&a| a.get() > 1),
+      ..Default::default()})
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureRef(pub(crate) u16);
+
+/// An image on the GPU, bound together with its sampler as used by materials.
+pub struct Texture {
+  pub texture: wgpu::Texture,
+  pub view: wgpu::TextureView,
+  pub sampler: wgpu::Sampler,
+  pub size: wgpu::Extent3d,
+  pub format: wgpu::TextureFormat,
+  pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl Texture {
+  /// Layout of the texture and sampler bindings of a material.
+  pub fn bind_group_layout(device:&wgpu::Device)->wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("texture"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float{filterable: true},
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false}},
+        wgpu::BindGroupLayoutEntry {
+          binding: 1, count: None, visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)}]})
+  }
+
+  /// Upload the first level, the others are left for [`crate::pass::Mipmaps`] to fill in.
+  pub fn upload(device:&wgpu::Device, queue:&wgpu::Queue, layout:&wgpu::BindGroupLayout, image:&Image, srgb:bool, sampler:&SamplerConfig, mip_level_count:u32)->Texture {
+    let size = wgpu::Extent3d{width: image.width, height: image.height, depth_or_array_layers: 1};
+    let format = image.format(srgb);
+    let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+    if mip_level_count > 1 { usage |= wgpu::TextureUsages::RENDER_ATTACHMENT }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Texture"), size, mip_level_count, sample_count: 1,
+      dimension: wgpu::TextureDimension::D2, format, usage});
+    let (texels, bytes_per_row) = image.texels();
+    queue.write_texture(
+      texture.as_image_copy(), &texels,
+      wgpu::ImageDataLayout{offset: 0, bytes_per_row: NonZeroU32::new(bytes_per_row), rows_per_image: None},
+      size);
+    Self::bind(device, layout, texture, size, format, sampler)
+  }
+
+  /// Upload all levels of a block compressed image, the device needs its [`CompressedImage::required_features`].
+  pub fn upload_compressed(device:&wgpu::Device, queue:&wgpu::Queue, layout:&wgpu::BindGroupLayout, image:&CompressedImage, sampler:&SamplerConfig)->Texture {
+    let size = wgpu::Extent3d{width: image.width, height: image.height, depth_or_array_layers: 1};
+    let format = image.format;
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+      label: Some("Compressed Texture"), size, mip_level_count: image.levels.len() as u32, sample_count: 1,
+      dimension: wgpu::TextureDimension::D2, format,
+      usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST});
+    let info = format.describe();
+    for (level, data) in image.levels.iter().enumerate() {
+      // Copies cover whole blocks, even when the level is smaller than one.
+      let extent = size.mip_level_size(level as u32, false).physical_size(format);
+      let blocks_wide = extent.width / info.block_dimensions.0 as u32;
+      queue.write_texture(
+        wgpu::ImageCopyTexture{texture: &texture, mip_level: level as u32, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All},
+        data,
+        wgpu::ImageDataLayout{offset: 0, bytes_per_row: NonZeroU32::new(blocks_wide * info.block_size as u32), rows_per_image: None},
+        extent);
+    }
+    Self::bind(device, layout, texture, size, format, sampler)
+  }
+
+  fn bind(device:&wgpu::Device, layout:&wgpu::BindGroupLayout, texture:wgpu::Texture, size:wgpu::Extent3d, format:wgpu::TextureFormat, sampler:&SamplerConfig)->Texture {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = sampler.create_sampler(device);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("texture"), layout,
+      entries: &[
+        wgpu::BindGroupEntry {binding: 0, resource: wgpu::BindingResource::TextureView(&view)},
+        wgpu::BindGroupEntry {binding: 1, resource: wgpu::BindingResource::Sampler(&sampler)}]});
+    Texture{texture, view, sampler, size, format, bind_group}
+  }
+}
+
+/// Convert to half precision, rounding towards zero and flushing values that are too small to zero.
+pub(crate) fn f32_to_f16(value:f32)->u16 {
+  let bits = value.to_bits();
+  let sign = ((bits >> 16) & 0x8000) as u16;
+  let exponent = ((bits >> 23) & 0xff) as i32;
+  let mantissa = bits & 0x7fffff;
+  if exponent == 0xff { return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 } }
+  let exponent = exponent - 127 + 15;
+  if exponent >= 0x1f { return sign | 0x7c00 }
+  if exponent <= 0 { return sign }
+  sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn half() {
+    assert_eq!(f32_to_f16(0.0), 0x0000);
+    assert_eq!(f32_to_f16(1.0), 0x3c00);
+    assert_eq!(f32_to_f16(-2.0), 0xc000);
+    assert_eq!(f32_to_f16(0.5), 0x3800);
+    assert_eq!(f32_to_f16(1e6), 0x7c00);
+  }
+
+  #[test] fn decode_png() {
+    // 1x1 red pixel
+    let mut png = Vec::new();
+    image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]))
+      .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).unwrap();
+    let image = Image::decode(&png).unwrap();
+    assert_eq!((image.width, image.height), (1, 1));
+    assert_eq!(image.pixels, Pixels::Rgba8(vec![255, 0, 0, 255]));
+    assert_eq!(image.format(true), wgpu::TextureFormat::Rgba8UnormSrgb);
+  }
+
+  #[test] fn anisotropy() {
+    assert_eq!(SamplerConfig::anisotropic(0).anisotropy, 1);
+    assert_eq!(SamplerConfig::anisotropic(6).anisotropy, 4);
+    assert_eq!(SamplerConfig::anisotropic(255).anisotropy, 16);
+    assert_eq!(SamplerConfig::anisotropic(16).mipmap_filter, wgpu::FilterMode::Linear);
+  }
+}
@@ -0,0 +1,177 @@
+//! Frame timing and a fixed timestep for simulations that shouldn't depend on the frame rate.
+
+use glam::Vec3;
+
+/// Seconds since the first call, from a monotonic clock.
+pub fn now()->f64 {
+  cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+      web_sys::window().and_then(|w| w.performance()).map_or(0.0, |p| p.now() / 1000.0)
+    } else {
+      thread_local!{ static START: std::time::Instant = std::time::Instant::now(); }
+      START.with(|start| start.elapsed().as_secs_f64())
+    }
+  }
+}
+
+/// Frame clock with an accumulator that hands out fixed simulation steps.
+/// Rendering happens in between steps, blend the last two states by [`Time::alpha`] to hide the stutter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Time {
+  /// Frames started so far.
+  pub frame: u64,
+  /// Seconds between the last two frames.
+  pub dt: f32,
+  /// Frames per second, smoothed over the last few dozen frames.
+  pub fps: f32,
+  /// Seconds since the first frame.
+  pub elapsed: f64,
+  /// Length of a simulation step in seconds.
+  pub step: f32,
+  /// Simulation steps taken so far.
+  pub ticks: u64,
+  /// Most steps per frame, a slow frame drops the time it can't catch up on instead of freezing.
+  pub max_steps: u32,
+  pub paused: bool,
+  accumulator: f32,
+  pending: u32,
+  start: Option<f64>,
+  last: f64,
+}
+
+impl Default for Time {
+  fn default() -> Self { Self::new(1.0 / 60.0) }
+}
+
+impl Time {
+  pub fn new(step:f32)->Self {
+    Time{frame: 0, dt: 0.0, fps: 0.0, elapsed: 0.0, step, ticks: 0, max_steps: 8, paused: false, accumulator: 0.0, pending: 0, start: None, last: 0.0}
+  }
+
+  /// Start a frame now, returns how many simulation steps to take.
+  pub fn tick(&mut self)->u32 { self.tick_at(now()) }
+
+  /// Start a frame at `now` seconds, returns how many simulation steps to take.
+  pub fn tick_at(&mut self, now:f64)->u32 {
+    let start = *self.start.get_or_insert(now);
+    self.dt = if self.frame == 0 { 0.0 } else { (now - self.last).max(0.0) as f32 };
+    self.last = now;
+    self.elapsed = now - start;
+    self.frame += 1;
+    if self.dt > 0.0 {
+      self.fps = if self.fps == 0.0 { 1.0 / self.dt } else { 1.0 / (0.95 / self.fps + 0.05 * self.dt) };
+    }
+    let steps = if self.paused {
+      std::mem::take(&mut self.pending)
+    } else {
+      self.accumulator += self.dt;
+      let steps = (self.accumulator / self.step) as u32;
+      self.accumulator -= steps as f32 * self.step;
+      if steps > self.max_steps { self.accumulator = 0.0 }
+      steps.min(self.max_steps)
+    };
+    self.ticks += steps as u64;
+    steps
+  }
+
+  /// How far the current frame is between the last simulation step and the next, from 0 to 1.
+  pub fn alpha(&self)->f32 { if self.paused { 1.0 } else { (self.accumulator / self.step).clamp(0.0, 1.0) } }
+
+  pub fn pause(&mut self) { self.paused = true }
+
+  pub fn resume(&mut self) { self.paused = false; self.pending = 0 }
+
+  pub fn toggle_pause(&mut self) { if self.paused { self.resume() } else { self.pause() } }
+
+  /// Take a single simulation step on the next frame while paused.
+  pub fn step_once(&mut self) { if self.paused { self.pending += 1 } }
+}
+
+/// Values that can be blended, for drawing between two simulation steps.
+pub trait Lerp {
+  fn lerp(&self, other:&Self, t:f32)->Self;
+}
+
+impl Lerp for f32 {
+  fn lerp(&self, other:&f32, t:f32)->f32 { self + (other - self) * t }
+}
+
+impl Lerp for Vec3 {
+  fn lerp(&self, other:&Vec3, t:f32)->Vec3 { Vec3::lerp(*self, *other, t) }
+}
+
+impl Lerp for g3::Motor {
+  /// Normalized blend of the coefficients, which is close to the screw motion in between for the
+  /// small changes of one step.
+  fn lerp(&self, other:&g3::Motor, t:f32)->g3::Motor {
+    let parts = |m:&g3::Motor| [m.scalar(), m.e23(), m.e31(), m.e12(), m.e01(), m.e02(), m.e03(), m.e0123()];
+    let (a, mut b) = (parts(self), parts(other));
+    // Both signs of a motor move the same way, blend towards the nearer one.
+    if a[..4].iter().zip(&b[..4]).map(|(x, y)| x * y).sum::<f32>() < 0.0 { b = b.map(|x| -x) }
+    let c:Vec<f32> = a.iter().zip(&b).map(|(x, y)| x + (y - x) * t).collect();
+    g3::Motor::new(c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]).normalized()
+  }
+}
+
+/// The last two states of something simulated in fixed steps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interpolated<T> {
+  pub previous: T,
+  pub current: T,
+}
+
+impl<T:Clone + Lerp> Interpolated<T> {
+  pub fn new(value:T)->Self { Interpolated{previous: value.clone(), current: value} }
+
+  /// Record the state after a step.
+  pub fn push(&mut self, value:T) { self.previous = std::mem::replace(&mut self.current, value) }
+
+  /// The state to draw, `alpha` of the way from the previous step to the current one.
+  pub fn get(&self, alpha:f32)->T { self.previous.lerp(&self.current, alpha) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn fixed_steps() {
+    let mut time = Time::new(0.01);
+    assert_eq!(time.tick_at(10.0), 0);
+    assert_eq!(time.tick_at(10.025), 2);
+    assert!((time.alpha() - 0.5).abs() < 1e-3);
+    assert_eq!(time.tick_at(10.037), 1);
+    assert_eq!((time.frame, time.ticks), (3, 3));
+    assert!((time.elapsed - 0.037).abs() < 1e-9);
+    // A long stall is capped instead of replayed.
+    assert_eq!(time.tick_at(11.0), 8);
+    assert_eq!(time.alpha(), 0.0);
+  }
+
+  #[test] fn pause() {
+    let mut time = Time::new(0.01);
+    time.tick_at(0.0);
+    time.pause();
+    assert_eq!(time.tick_at(1.0), 0);
+    time.step_once();
+    time.step_once();
+    assert_eq!(time.tick_at(1.1), 2);
+    assert_eq!(time.tick_at(1.2), 0);
+    time.resume();
+    assert_eq!(time.tick_at(1.215), 1);
+  }
+
+  #[test] fn fps() {
+    let mut time = Time::default();
+    for i in 0..200 { time.tick_at(i as f64 / 50.0); }
+    assert!((time.fps - 50.0).abs() < 0.1);
+  }
+
+  #[test] fn interpolate() {
+    let mut x = Interpolated::new(Vec3::ZERO);
+    x.push(Vec3::X);
+    assert_eq!(x.get(0.25), Vec3::new(0.25, 0.0, 0.0));
+    let (a, b) = (crate::scene::identity(), g3::Motor::from(g3::translator(1.0, 0.0, 1.0, 0.0)));
+    let p:[f32;3] = a.lerp(&b, 0.5)(g3::point(0.0, 0.0, 0.0)).into();
+    assert!((Vec3::from(p) - Vec3::new(0.0, 0.5, 0.0)).length() < 1e-5);
+  }
+}
@@ -0,0 +1,307 @@
+//! An immediate mode debug UI drawn over the frame with egui, and the panels that come with it.
+
+use std::collections::VecDeque;
+use egui::{DragValue, Event, Key as EguiKey, Modifiers, PointerButton, Pos2, RawInput};
+use crate::{Attachment, Color, Cx, NodeRef, ObjectRef, Time};
+use crate::input::{InputEvent, Key, ModifiersState, MouseButton};
+use crate::pass::MAX_LIGHTS;
+
+/// Points egui scrolls per line of the wheel.
+const POINTS_PER_LINE:f32 = 50.0;
+
+/// Frame times kept for the stats panel.
+const HISTORY:usize = 120;
+
+/// Which of the built-in windows are open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Panels {
+  pub camera: bool,
+  pub outline: bool,
+  pub stats: bool,
+  pub lights: bool,
+}
+
+impl Default for Panels {
+  fn default() -> Self { Panels{camera: true, outline: true, stats: true, lights: true} }
+}
+
+/// The egui context and the input gathered for its next frame, see [`crate::App::ui`].
+/// While the UI is under the pointer or has keyboard focus it takes the input and the app doesn't see it.
+pub struct Ui {
+  ctx: egui::Context,
+  input: RawInput,
+  modifiers: ModifiersState,
+  cursor: Option<Pos2>,
+  /// Seconds taken by the last frames, the latest at the back.
+  frame_times: VecDeque<f32>,
+  /// Physical pixels per point, set from the scale factor of the window.
+  pub pixels_per_point: f32,
+  /// Hidden UIs don't run, draw or take input, it starts hidden. F1 toggles it in [`crate::run_app`], through the `"ui"` action.
+  pub visible: bool,
+  pub panels: Panels,
+  pub(crate) output: Option<(Vec<egui::ClippedPrimitive>, egui::TexturesDelta)>,
+}
+
+impl Default for Ui {
+  fn default() -> Self { Ui::new() }
+}
+
+impl Ui {
+  pub fn new()->Ui {
+    Ui{ctx: egui::Context::default(), input: RawInput::default(), modifiers: ModifiersState::empty(), cursor: None, frame_times: VecDeque::new(),
+      pixels_per_point: 1.0, visible: false, panels: Panels::default(), output: None}
+  }
+
+  pub fn context(&self)->&egui::Context { &self.ctx }
+
+  pub fn toggle(&mut self) { self.visible = !self.visible }
+
+  /// The pointer is over a window or dragging something in it.
+  pub fn wants_pointer(&self)->bool { self.visible && (self.ctx.is_pointer_over_area() || self.ctx.wants_pointer_input()) }
+
+  /// A text field has focus.
+  pub fn wants_keyboard(&self)->bool { self.visible && self.ctx.wants_keyboard_input() }
+
+  fn modifiers(&self)->Modifiers {
+    let m = self.modifiers;
+    let mac = cfg!(target_os = "macos");
+    Modifiers{alt: m.alt(), ctrl: m.ctrl(), shift: m.shift(), mac_cmd: mac && m.logo(), command: if mac { m.logo() } else { m.ctrl() }}
+  }
+
+  /// Queue an input event for the next frame of the UI, returns true when the UI takes it from the app.
+  /// Cursor movement and releases always reach the app as well, so its buttons don't get stuck.
+  pub fn handle(&mut self, event:&InputEvent)->bool {
+    if let InputEvent::Modifiers(m) = event { self.modifiers = *m }
+    if !self.visible { return false }
+    let scale = self.pixels_per_point;
+    let modifiers = self.modifiers();
+    match *event {
+      InputEvent::CursorMoved{position: [x, y]} => {
+        let pos = Pos2::new(x / scale, y / scale);
+        self.cursor = Some(pos);
+        self.input.events.push(Event::PointerMoved(pos));
+        false
+      }
+      InputEvent::CursorLeft => {
+        self.cursor = None;
+        self.input.events.push(Event::PointerGone);
+        false
+      }
+      InputEvent::MouseButton{button, pressed} => {
+        let (button, pos) = match (pointer_button(button), self.cursor) { (Some(b), Some(p)) => (b, p), _ => return false };
+        self.input.events.push(Event::PointerButton{pos, button, pressed, modifiers});
+        pressed && self.wants_pointer()
+      }
+      InputEvent::Wheel{delta: [x, y]} => {
+        self.input.events.push(Event::Scroll(egui::vec2(x, y) * POINTS_PER_LINE));
+        self.wants_pointer()
+      }
+      InputEvent::Key{key, pressed} => {
+        let key = match egui_key(key) { Some(key) => key, None => return pressed && self.wants_keyboard() };
+        if pressed && modifiers.command && key == EguiKey::C { self.input.events.push(Event::Copy) }
+        if pressed && modifiers.command && key == EguiKey::X { self.input.events.push(Event::Cut) }
+        self.input.events.push(Event::Key{key, pressed, modifiers});
+        pressed && self.wants_keyboard()
+      }
+      InputEvent::Character(c) => {
+        // Control characters arrive as keys, the private use area holds function keys on macOS.
+        if c.is_control() || ('\u{F700}'..='\u{F8FF}').contains(&c) { return self.wants_keyboard() }
+        self.input.events.push(Event::Text(c.to_string()));
+        self.wants_keyboard()
+      }
+      InputEvent::Focused(false) => { self.modifiers = ModifiersState::empty(); false }
+      _ => false,
+    }
+  }
+
+  /// Start a frame, returns the input gathered since the last one for a screen of `size` physical pixels.
+  pub(crate) fn begin(&mut self, size:[u32;2], time:&Time)->RawInput {
+    self.frame_times.push_back(time.dt);
+    if self.frame_times.len() > HISTORY { self.frame_times.pop_front(); }
+    self.take_input(size, time.elapsed)
+  }
+
+  fn take_input(&mut self, size:[u32;2], time:f64)->RawInput {
+    let scale = self.pixels_per_point;
+    let mut input = std::mem::take(&mut self.input);
+    input.screen_rect = Some(egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(size[0] as f32, size[1] as f32) / scale));
+    input.pixels_per_point = Some(scale);
+    input.time = Some(time);
+    input.modifiers = self.modifiers();
+    input
+  }
+}
+
+fn pointer_button(button:MouseButton)->Option<PointerButton> {
+  match button {
+    MouseButton::Left => Some(PointerButton::Primary),
+    MouseButton::Right => Some(PointerButton::Secondary),
+    MouseButton::Middle => Some(PointerButton::Middle),
+    MouseButton::Other(_) => None,
+  }
+}
+
+fn egui_key(key:Key)->Option<EguiKey> {
+  Some(match key {
+    Key::Down => EguiKey::ArrowDown, Key::Left => EguiKey::ArrowLeft, Key::Right => EguiKey::ArrowRight, Key::Up => EguiKey::ArrowUp,
+    Key::Escape => EguiKey::Escape, Key::Tab => EguiKey::Tab, Key::Back => EguiKey::Backspace, Key::Return | Key::NumpadEnter => EguiKey::Enter,
+    Key::Space => EguiKey::Space, Key::Insert => EguiKey::Insert, Key::Delete => EguiKey::Delete, Key::Home => EguiKey::Home,
+    Key::End => EguiKey::End, Key::PageUp => EguiKey::PageUp, Key::PageDown => EguiKey::PageDown,
+    Key::Key0 | Key::Numpad0 => EguiKey::Num0, Key::Key1 | Key::Numpad1 => EguiKey::Num1, Key::Key2 | Key::Numpad2 => EguiKey::Num2,
+    Key::Key3 | Key::Numpad3 => EguiKey::Num3, Key::Key4 | Key::Numpad4 => EguiKey::Num4, Key::Key5 | Key::Numpad5 => EguiKey::Num5,
+    Key::Key6 | Key::Numpad6 => EguiKey::Num6, Key::Key7 | Key::Numpad7 => EguiKey::Num7, Key::Key8 | Key::Numpad8 => EguiKey::Num8,
+    Key::Key9 | Key::Numpad9 => EguiKey::Num9,
+    Key::A => EguiKey::A, Key::B => EguiKey::B, Key::C => EguiKey::C, Key::D => EguiKey::D, Key::E => EguiKey::E, Key::F => EguiKey::F,
+    Key::G => EguiKey::G, Key::H => EguiKey::H, Key::I => EguiKey::I, Key::J => EguiKey::J, Key::K => EguiKey::K, Key::L => EguiKey::L,
+    Key::M => EguiKey::M, Key::N => EguiKey::N, Key::O => EguiKey::O, Key::P => EguiKey::P, Key::Q => EguiKey::Q, Key::R => EguiKey::R,
+    Key::S => EguiKey::S, Key::T => EguiKey::T, Key::U => EguiKey::U, Key::V => EguiKey::V, Key::W => EguiKey::W, Key::X => EguiKey::X,
+    Key::Y => EguiKey::Y, Key::Z => EguiKey::Z,
+    _ => return None,
+  })
+}
+
+fn color_edit(ui:&mut egui::Ui, color:&mut Color)->egui::Response {
+  let mut rgba = color.0.to_be_bytes();
+  let response = ui.color_edit_button_srgba_unmultiplied(&mut rgba);
+  *color = Color(u32::from_be_bytes(rgba));
+  response
+}
+
+fn vec3_edit(ui:&mut egui::Ui, label:&str, v:&mut glam::Vec3)->bool {
+  ui.horizontal(|ui| {
+    ui.label(label);
+    let mut c = v.to_array();
+    let changed = c.iter_mut().fold(false, |changed, c| ui.add(DragValue::new(c).speed(0.05)).changed() | changed);
+    *v = c.into();
+    changed
+  }).inner
+}
+
+/// The built-in windows, shown for one frame of the UI.
+pub(crate) fn panels(cx:&mut Cx, ctx:&egui::Context, time:&mut Time) {
+  let mut panels = cx.ui().panels;
+  stats_panel(cx, ctx, time, &mut panels.stats);
+  camera_panel(cx, ctx, &mut panels.camera);
+  outline_panel(cx, ctx, &mut panels.outline);
+  lights_panel(cx, ctx, &mut panels.lights);
+  cx.ui_mut().panels = panels;
+}
+
+fn stats_panel(cx:&mut Cx, ctx:&egui::Context, time:&mut Time, open:&mut bool) {
+  let times = &cx.ui().frame_times;
+  let (worst, mean) = (times.iter().copied().fold(0.0, f32::max), times.iter().sum::<f32>() / times.len().max(1) as f32);
+  let (objects, nodes, lights) = (cx.objects().len(), cx.graph().iter().count(), cx.lights().len());
+  egui::Window::new("Stats").open(open).default_pos([8.0, 8.0]).resizable(false).show(ctx, |ui| {
+    ui.label(format!("{:.0} fps, {:.2} ms mean, {:.2} ms worst", time.fps, mean * 1000.0, worst * 1000.0));
+    ui.label(format!("frame {}, {} steps of {:.1} ms", time.frame, time.ticks, time.step * 1000.0));
+    ui.label(format!("{} objects, {} nodes, {} lights", objects, nodes, lights));
+    ui.horizontal(|ui| {
+      let mut paused = time.paused;
+      if ui.checkbox(&mut paused, "Paused").changed() { time.toggle_pause() }
+      if ui.add_enabled(time.paused, egui::Button::new("Step")).clicked() { time.step_once() }
+    });
+  });
+}
+
+fn camera_panel(cx:&mut Cx, ctx:&egui::Context, open:&mut bool) {
+  let camera = cx.camera_mut();
+  egui::Window::new("Camera").open(open).default_pos([8.0, 140.0]).resizable(false).show(ctx, |ui| {
+    let (mut eye, mut target) = (camera.eye(), camera.target());
+    if vec3_edit(ui, "Eye", &mut eye) | vec3_edit(ui, "Target", &mut target) { camera.look_at(eye, target) }
+    let mut fov = camera.fov_y();
+    if ui.add(egui::Slider::new(&mut fov, 10.0..=150.0).text("Vertical FOV").suffix("°")).changed() { camera.set_fov_y(fov) }
+    if let Some(node) = camera.node { ui.label(format!("Following node {}", node.0)); }
+  });
+}
+
+/// The tree of nodes with what is attached to them, then the objects that aren't attached to any.
+fn outline_panel(cx:&mut Cx, ctx:&egui::Context, open:&mut bool) {
+  egui::Window::new("Outline").open(open).default_pos([8.0, 260.0]).default_width(200.0).show(ctx, |ui| {
+    egui::ScrollArea::vertical().show(ui, |ui| {
+      for root in cx.graph().roots().to_vec() { outline_node(cx, ui, root) }
+      let attached:Vec<_> = cx.graph().iter().flat_map(|n| cx.graph().node(n).attachments.clone()).collect();
+      for i in 0..cx.objects().len() {
+        let object = ObjectRef(i as u16);
+        if !attached.contains(&Attachment::Object(object)) { outline_object(cx, ui, object) }
+      }
+    });
+  });
+}
+
+fn outline_node(cx:&mut Cx, ui:&mut egui::Ui, node:NodeRef) {
+  let (name, attachments, children) = {
+    let n = cx.graph().node(node);
+    (n.name.clone(), n.attachments.clone(), n.children().to_vec())
+  };
+  egui::CollapsingHeader::new(if name.is_empty() { format!("Node {}", node.0) } else { name }).id_source(node).default_open(true).show(ui, |ui| {
+    for attachment in attachments {
+      match attachment {
+        Attachment::Object(object) => outline_object(cx, ui, object),
+        Attachment::Light(_) => { ui.label("Light"); }
+      }
+    }
+    for child in children { outline_node(cx, ui, child) }
+  });
+}
+
+/// A label that selects the object, or toggles it in the selection with shift held.
+fn outline_object(cx:&mut Cx, ui:&mut egui::Ui, object:ObjectRef) {
+  let selected = cx.selection().contains(object);
+  if ui.selectable_label(selected, format!("Object {}", object.0)).clicked() {
+    let selection = cx.selection_mut();
+    if ui.input().modifiers.shift { selection.toggle(object) } else { selection.clear(); selection.select(object); }
+  }
+}
+
+fn lights_panel(cx:&mut Cx, ctx:&egui::Context, open:&mut bool) {
+  let graph = cx.graph_mut();
+  egui::Window::new("Lights").open(open).default_pos([8.0, 480.0]).resizable(false).show(ctx, |ui| {
+    let mut count = 0;
+    for node in graph.iter().collect::<Vec<_>>() {
+      let name = graph.node(node).name.clone();
+      for (i, attachment) in graph.attachments_mut(node).iter_mut().enumerate() {
+        let light = match attachment { Attachment::Light(light) => light, _ => continue };
+        count += 1;
+        ui.push_id((node, i), |ui| {
+          ui.horizontal(|ui| {
+            color_edit(ui, &mut light.color);
+            ui.label(&name);
+          });
+          // Lights are shaded in the order they are listed, up to a limit.
+          if count > MAX_LIGHTS { ui.label(format!("Not rendered, only the first {} lights shade the scene.", MAX_LIGHTS)); }
+          ui.add(egui::Slider::new(&mut light.intensity, 0.0..=10.0).text("Intensity"));
+          ui.add(egui::Slider::new(&mut light.range, 0.0..=100.0).text("Range"));
+        });
+        ui.separator();
+      }
+    }
+    if count == 0 { ui.label("No lights are attached to nodes."); }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test] fn input() {
+    let mut ui = Ui{pixels_per_point: 2.0, visible: true, ..Ui::new()};
+    // Nothing is under the pointer before the first frame, so everything reaches the app.
+    assert!(!ui.handle(&InputEvent::CursorMoved{position: [20.0, 10.0]}));
+    assert!(!ui.handle(&InputEvent::MouseButton{button: MouseButton::Left, pressed: true}));
+    assert!(!ui.handle(&InputEvent::Character('\u{8}')));
+    assert!(!ui.handle(&InputEvent::Character('é')));
+    assert!(!ui.handle(&InputEvent::Modifiers(ModifiersState::SHIFT)));
+    assert!(!ui.handle(&InputEvent::Key{key: Key::A, pressed: true}));
+    let input = ui.take_input([200, 100], 1.0);
+    assert_eq!(input.screen_rect, Some(egui::Rect::from_min_size(Pos2::ZERO, egui::vec2(100.0, 50.0))));
+    assert_eq!(input.events.len(), 4);
+    assert_eq!(input.events[0], Event::PointerMoved(Pos2::new(10.0, 5.0)));
+    assert_eq!(input.events[2], Event::Text("é".to_string()));
+    assert!(matches!(input.events[3], Event::Key{key: EguiKey::A, pressed: true, modifiers} if modifiers.shift));
+    assert!(ui.take_input([200, 100], 2.0).events.is_empty());
+    // Hidden, nothing is queued.
+    ui.toggle();
+    ui.handle(&InputEvent::CursorMoved{position: [0.0, 0.0]});
+    assert!(ui.input.events.is_empty());
+  }
+}